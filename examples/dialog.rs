@@ -1,3 +1,4 @@
+#[cfg(windows)]
 #[tokio::main]
 async fn main() {
     let (_window, mut rx) = witas::Window::builder()
@@ -33,3 +34,6 @@ async fn main() {
         }
     }
 }
+
+#[cfg(not(windows))]
+fn main() {}
@@ -1,3 +1,4 @@
+#[cfg(windows)]
 #[tokio::main]
 async fn main() {
     let (_window, mut rx) = witas::Window::builder()
@@ -34,3 +35,6 @@ async fn main() {
     }
     th.await.unwrap();
 }
+
+#[cfg(not(windows))]
+fn main() {}
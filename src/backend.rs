@@ -0,0 +1,238 @@
+//! The platform layer behind `Window`.
+//!
+//! Every operation of `Window` is forwarded to a [`Backend`]. witas selects the Win32 backend on
+//! Windows and the Wayland or X11 backend on other targets, and [`set_backend`] replaces the
+//! selection with `headless::Headless` or a custom implementation such as a test double. When no
+//! backend is available, building a window fails with `Error::NoBackend`. A backend reports what
+//! happens to its windows through [`EventSink`].
+
+use crate::window::Style;
+use crate::*;
+use once_cell::sync::OnceCell;
+use std::future::Future;
+use std::pin::Pin;
+
+pub use crate::context::MessageTimeGuard;
+
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// The parameters of `WindowBuilder` passed to `Backend::create_window`.
+pub struct WindowParams {
+    pub title: String,
+    pub position: ScreenPosition,
+    /// Returns the inner size for a DPI.
+    pub size: Box<dyn Fn(u32) -> PhysicalSize<u32> + Send>,
+    pub style: Box<dyn Style + Send>,
+    pub visibility: bool,
+    pub enable_ime: bool,
+    pub visible_ime_candidate_window: bool,
+    pub accept_drop_files: bool,
+    pub enable_raw_input: bool,
+    pub icon: Option<Icon>,
+    pub queue_policy: QueuePolicy,
+    pub event_mask: EventMask,
+    /// How long the UI thread waits for a `Reply`.
+    pub reply_timeout: std::time::Duration,
+    #[cfg(windows)]
+    pub message_hook: Option<MessageHook>,
+    /// Sends `Event::Message` of the messages which the backend does not handle.
+    pub forward_messages: bool,
+}
+
+/// Creates and operates windows.
+///
+/// Methods are called from any thread, and operations on a closed window are ignored.
+/// `create_window` registers the window by `EventSink::register_window` and `destroy` unregisters
+/// it by `EventSink::close_window`.
+pub trait Backend: Send + Sync + 'static {
+    fn create_window(&self, params: WindowParams) -> BoxFuture<Result<(WindowId, EventReceiver)>>;
+    fn position(&self, id: WindowId) -> BoxFuture<Option<ScreenPosition>>;
+    fn inner_size(&self, id: WindowId) -> BoxFuture<Option<PhysicalSize<u32>>>;
+    fn dpi(&self, id: WindowId) -> BoxFuture<Option<u32>>;
+    fn show(&self, id: WindowId);
+    fn hide(&self, id: WindowId);
+    fn minimize(&self, id: WindowId);
+    fn maximize(&self, id: WindowId);
+    fn restore(&self, id: WindowId);
+    fn accept_drop_files(&self, id: WindowId, accept: bool);
+    fn ime(&self, id: WindowId, enable: bool);
+    fn set_cursor(&self, id: WindowId, cursor: Cursor);
+    /// Asks the app to close the window by `EventSink::request_close`.
+    fn close(&self, id: WindowId) {
+        EventSink::request_close(id);
+    }
+    /// Closes the window without asking. It may be called while events are being sent.
+    fn destroy(&self, id: WindowId);
+    /// Sends `Event::Draw` of `invalid_rect`, or of the whole window when it is `None`.
+    fn redraw(&self, id: WindowId, invalid_rect: Option<PhysicalRect<i32>>);
+
+    /// Copies `image` to the client area at `position`. Backends without framebuffers ignore it.
+    fn present(&self, _id: WindowId, _position: PhysicalPosition<i32>, _image: &Framebuffer) {}
+
+    /// Returns the presented pixels. Backends without framebuffers return `None`.
+    fn capture(&self, _id: WindowId) -> BoxFuture<Option<Capture>> {
+        Box::pin(std::future::ready(None))
+    }
+}
+
+/// Delivers events of the windows of a backend to their `EventReceiver`.
+pub struct EventSink;
+
+impl EventSink {
+    /// Registers a window and returns the receiver of its events.
+    #[inline]
+    pub fn register_window(
+        id: WindowId,
+        enable_raw_input: bool,
+        queue_policy: QueuePolicy,
+        event_mask: EventMask,
+    ) -> EventReceiver {
+        Context::register_window(id, enable_raw_input, queue_policy, event_mask)
+    }
+
+    #[inline]
+    pub fn send_event(id: WindowId, event: Event) {
+        Context::send_event(id, event);
+    }
+
+    /// Sends an event to `RawInputEventRecevier` if the window enables raw input.
+    #[inline]
+    pub fn send_raw_input_event(id: WindowId, event: raw_input::RawInputEvent) {
+        Context::send_raw_input_event(id, event);
+    }
+
+    /// Sets `Timestamp::message_time` of the events sent on the current thread until the guard
+    /// is dropped. Their `Timestamp::arrival` is the time of this call.
+    #[inline]
+    pub fn set_message_time(time: Option<u32>) -> MessageTimeGuard {
+        Context::set_message_time(time)
+    }
+
+    /// Sends `Event::CloseRequested`. `Backend::destroy` is called when the app does not cancel
+    /// it, so the backend must not hold a lock which `destroy` takes.
+    #[inline]
+    pub fn request_close(id: WindowId) {
+        Context::request_close(id);
+    }
+
+    /// Sends `Event::Closed` and unregisters the window.
+    ///
+    /// Returns `true` when the last window was closed. In that case `Event::Quit` is also sent.
+    #[inline]
+    pub fn close_window(id: WindowId) -> bool {
+        Context::close_window(id)
+    }
+}
+
+static BACKEND: OnceCell<Box<dyn Backend>> = OnceCell::new();
+
+#[cfg(windows)]
+fn default_backend() -> Box<dyn Backend> {
+    Box::new(win32::Win32)
+}
+
+/// Fails to create windows when no display server can be connected.
+#[cfg(not(windows))]
+struct NoBackend;
+
+#[cfg(not(windows))]
+impl Backend for NoBackend {
+    fn create_window(&self, _params: WindowParams) -> BoxFuture<Result<(WindowId, EventReceiver)>> {
+        Box::pin(std::future::ready(Err(Error::NoBackend)))
+    }
+
+    fn position(&self, _id: WindowId) -> BoxFuture<Option<ScreenPosition>> {
+        Box::pin(std::future::ready(None))
+    }
+
+    fn inner_size(&self, _id: WindowId) -> BoxFuture<Option<PhysicalSize<u32>>> {
+        Box::pin(std::future::ready(None))
+    }
+
+    fn dpi(&self, _id: WindowId) -> BoxFuture<Option<u32>> {
+        Box::pin(std::future::ready(None))
+    }
+
+    fn show(&self, _id: WindowId) {}
+    fn hide(&self, _id: WindowId) {}
+    fn minimize(&self, _id: WindowId) {}
+    fn maximize(&self, _id: WindowId) {}
+    fn restore(&self, _id: WindowId) {}
+    fn accept_drop_files(&self, _id: WindowId, _accept: bool) {}
+    fn ime(&self, _id: WindowId, _enable: bool) {}
+    fn set_cursor(&self, _id: WindowId, _cursor: Cursor) {}
+    fn destroy(&self, _id: WindowId) {}
+    fn redraw(&self, _id: WindowId, _invalid_rect: Option<PhysicalRect<i32>>) {}
+}
+
+#[cfg(not(windows))]
+fn default_backend() -> Box<dyn Backend> {
+    #[cfg(all(unix, feature = "wayland", not(test)))]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        if let Some(backend) = wayland::Wayland::connect() {
+            return Box::new(backend);
+        }
+    }
+    #[cfg(all(unix, feature = "x11", not(test)))]
+    if let Some(backend) = x11::X11::connect() {
+        return Box::new(backend);
+    }
+    Box::new(NoBackend)
+}
+
+/// Replaces the default backend.
+///
+/// This must be called before the first window is built. Returns `Error::BackendAlreadySet`
+/// when the backend is already in use.
+#[inline]
+pub fn set_backend(backend: impl Backend) -> Result<()> {
+    BACKEND
+        .set(Box::new(backend))
+        .map_err(|_| Error::BackendAlreadySet)
+}
+
+#[inline]
+pub(crate) fn get() -> &'static dyn Backend {
+    BACKEND.get_or_init(default_backend).as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_backend_after_init() {
+        let _lock = crate::headless::tests::lock();
+        get();
+        assert!(matches!(
+            set_backend(headless::Headless),
+            Err(Error::BackendAlreadySet)
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn no_backend() {
+        let params = WindowParams {
+            title: "no_backend".into(),
+            position: ScreenPosition::new(0, 0),
+            size: Box::new(|_| PhysicalSize::new(320, 240)),
+            style: Box::new(WindowStyle::default()),
+            visibility: true,
+            enable_ime: false,
+            visible_ime_candidate_window: false,
+            accept_drop_files: false,
+            enable_raw_input: false,
+            icon: None,
+            queue_policy: QueuePolicy::default(),
+            event_mask: EventMask::ALL,
+            reply_timeout: DEFAULT_REPLY_TIMEOUT,
+            forward_messages: false,
+        };
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let ret = rt.block_on(NoBackend.create_window(params));
+        assert!(matches!(ret, Err(Error::NoBackend)));
+    }
+}
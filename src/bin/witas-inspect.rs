@@ -0,0 +1,132 @@
+//! Prints the records streamed by `witas::inspector::Inspector`.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::time::{Duration, Instant};
+use witas::inspector::Record;
+
+const USAGE: &str = "\
+usage: witas-inspect <ADDRESS> [--kind <KIND>]... [--rates]
+
+ADDRESS is HOST:PORT, or unix:PATH on Unix.
+
+options:
+    --kind <KIND>    prints only records of KIND, such as CursorMoved or RawMouse
+    --rates          prints the records per second of each window instead of records";
+
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Args {
+    address: String,
+    kinds: Vec<String>,
+    rates: bool,
+}
+
+fn parse_args() -> Option<Args> {
+    let mut address = None;
+    let mut kinds = vec![];
+    let mut rates = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--kind" => kinds.push(args.next()?),
+            "--rates" => rates = true,
+            "-h" | "--help" => return None,
+            _ if address.is_none() && !arg.starts_with('-') => address = Some(arg),
+            _ => return None,
+        }
+    }
+    Some(Args {
+        address: address?,
+        kinds,
+        rates,
+    })
+}
+
+fn connect(address: &str) -> std::io::Result<Box<dyn Read>> {
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix("unix:") {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(RATE_INTERVAL))?;
+        return Ok(Box::new(stream));
+    }
+    let stream = std::net::TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(RATE_INTERVAL))?;
+    Ok(Box::new(stream))
+}
+
+fn print_record(record: &Record) {
+    let payload = match &record.item {
+        witas::inspector::Item::Event(event) => format!("{event:?}"),
+        witas::inspector::Item::RawInput(event) => format!("{event:?}"),
+    };
+    let message_time = record
+        .message_time
+        .map_or_else(|| "-".to_string(), |t| t.to_string());
+    println!(
+        "{:>10.3}s  window {:<8} {:>10}  {:<20} {payload}",
+        record.elapsed.as_secs_f64(),
+        record.window.raw(),
+        message_time,
+        record.item.kind(),
+    );
+}
+
+fn print_rates(counts: &mut BTreeMap<isize, u64>, elapsed: Duration) {
+    let secs = elapsed.as_secs_f64();
+    let rates = counts
+        .iter()
+        .map(|(window, count)| format!("window {window}: {:.1}/s", *count as f64 / secs))
+        .collect::<Vec<_>>();
+    println!("{}", rates.join("  "));
+    counts.values_mut().for_each(|count| *count = 0);
+}
+
+fn main() {
+    let Some(args) = parse_args() else {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    };
+    let stream = match connect(&args.address) {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("witas-inspect: {}: {e}", args.address);
+            std::process::exit(1);
+        }
+    };
+    let mut reader = BufReader::new(stream);
+    let mut line = vec![];
+    let mut counts = BTreeMap::new();
+    let mut last = Instant::now();
+    loop {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                match serde_json::from_slice::<Record>(&line) {
+                    Ok(record) => {
+                        let kind = record.item.kind();
+                        if args.kinds.is_empty() || args.kinds.iter().any(|k| k == kind) {
+                            if args.rates {
+                                *counts.entry(record.window.raw()).or_insert(0) += 1;
+                            } else {
+                                print_record(&record);
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("witas-inspect: invalid record: {e}"),
+                }
+                line.clear();
+            }
+            // A partial line stays in `line` until the rest arrives.
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => {
+                eprintln!("witas-inspect: {e}");
+                std::process::exit(1);
+            }
+        }
+        if args.rates && last.elapsed() >= RATE_INTERVAL {
+            print_rates(&mut counts, last.elapsed());
+            last = Instant::now();
+        }
+    }
+}
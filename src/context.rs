@@ -1,124 +1,350 @@
-use crate::window::WindowProperties;
+use crate::window::WindowId;
 use crate::*;
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::sync::Mutex;
-use std::task::Waker;
-use tokio::sync::mpsc;
-use windows::Win32::Foundation::HWND;
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
 
 pub(crate) struct Object {
-    pub props: WindowProperties,
-    pub sender: mpsc::UnboundedSender<Event>,
-    pub waker: Option<Waker>,
-    pub raw_input_sender: Option<mpsc::UnboundedSender<raw_input::RawInputEvent>>,
-    pub raw_input_waker: Option<Waker>,
+    /// The sender to the receiver returned by `register_window`.
+    pub sender: queue::Sender<Event>,
+    pub subscribers: Vec<queue::Sender<Event>>,
+    pub raw_input_sender: Option<queue::Sender<raw_input::RawInputEvent>>,
+}
+
+impl Object {
+    /// Returns the depth of the queue of the receiver returned by `register_window`.
+    fn send(&mut self, event: Event, timestamp: Timestamp) -> usize {
+        self.subscribers.retain(|sender| sender.is_connected());
+        for sender in &self.subscribers {
+            sender.send(event.clone(), timestamp);
+        }
+        self.sender.send(event, timestamp)
+    }
+
+    /// Sends `event` of the window `id` to its receivers and to `app_senders`.
+    ///
+    /// The answer of a `Reply` is taken from one receiver which has been polled: the receiver
+    /// returned by `register_window`, or else the first subscriber or the first app receiver. The
+    /// others get detached events. When no receiver has been polled, the backend takes the default
+    /// answer at once.
+    ///
+    /// Returns the depth of the queue of the receiver returned by `register_window`.
+    fn send_with_reply(
+        &mut self,
+        app_senders: &mut Vec<queue::Sender<(WindowId, Event)>>,
+        id: WindowId,
+        event: Event,
+        timestamp: Timestamp,
+    ) -> usize {
+        self.subscribers.retain(|sender| sender.is_connected());
+        app_senders.retain(|sender| sender.is_connected());
+        let primary = self.sender.is_polled();
+        let subscriber = if primary {
+            None
+        } else {
+            self.subscribers
+                .iter()
+                .position(|sender| sender.is_polled())
+        };
+        let app = if primary || subscriber.is_some() {
+            None
+        } else {
+            app_senders.iter().position(|sender| sender.is_polled())
+        };
+        let detached = event.detached();
+        let mut live = Some(event);
+        let mut take = |is_live: bool| {
+            if is_live {
+                live.take().unwrap()
+            } else {
+                detached.clone()
+            }
+        };
+        for (i, sender) in app_senders.iter().enumerate() {
+            sender.send((id, take(app == Some(i))), timestamp);
+        }
+        for (i, sender) in self.subscribers.iter().enumerate() {
+            sender.send(take(subscriber == Some(i)), timestamp);
+        }
+        self.sender.send(take(primary), timestamp)
+    }
 }
 
 pub(crate) struct Context {
-    window_map: HashMap<isize, Object>,
+    window_map: HashMap<WindowId, Object>,
+    app_senders: Vec<queue::Sender<(WindowId, Event)>>,
+    #[cfg(feature = "inspector")]
+    app_raw_input_senders: Vec<queue::Sender<(WindowId, raw_input::RawInputEvent)>>,
+}
+
+thread_local! {
+    static MESSAGE_TIME: Cell<Option<u32>> = const { Cell::new(None) };
+    static MESSAGE_ARRIVAL: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Restores the message time of the current thread when dropped.
+#[must_use]
+pub struct MessageTimeGuard(Option<u32>, Option<Instant>);
+
+impl Drop for MessageTimeGuard {
+    fn drop(&mut self) {
+        MESSAGE_TIME.with(|t| t.set(self.0));
+        MESSAGE_ARRIVAL.with(|t| t.set(self.1));
+    }
+}
+
+fn set_mask(id: WindowId, mask: EventMask) {
+    let mut masks = EVENT_MASKS.write().unwrap();
+    if mask == EventMask::ALL {
+        masks.remove(&id);
+    } else {
+        masks.insert(id, mask);
+    }
+}
+
+fn timestamp() -> Timestamp {
+    let instant = Instant::now();
+    Timestamp {
+        message_time: MESSAGE_TIME.with(|t| t.get()),
+        instant,
+        arrival: MESSAGE_ARRIVAL.with(|t| t.get()).unwrap_or(instant),
+    }
 }
 
 static CONTEXT: once_cell::sync::Lazy<Mutex<Context>> =
     once_cell::sync::Lazy::new(|| Mutex::new(Context::new()));
 
+// Masks are kept apart from `CONTEXT` so that filtered events do not take its lock. Windows which
+// receive every event have no entry.
+static EVENT_MASKS: once_cell::sync::Lazy<RwLock<HashMap<WindowId, EventMask>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(HashMap::new()));
+
 impl Context {
     fn new() -> Self {
         Self {
             window_map: HashMap::new(),
+            app_senders: vec![],
+            #[cfg(feature = "inspector")]
+            app_raw_input_senders: vec![],
         }
     }
 
-    pub fn is_empty() -> bool {
-        let ctx = CONTEXT.lock().unwrap();
-        ctx.window_map.is_empty()
-    }
-
     pub fn register_window(
-        hwnd: HWND,
-        props: WindowProperties,
-        sender: mpsc::UnboundedSender<Event>,
-        raw_input_sender: Option<mpsc::UnboundedSender<raw_input::RawInputEvent>>,
-    ) {
+        id: impl Into<WindowId>,
+        enable_raw_input: bool,
+        queue_policy: QueuePolicy,
+        event_mask: EventMask,
+    ) -> EventReceiver {
+        let id = id.into();
+        let (sender, rx) = queue::channel(queue_policy);
+        let (raw_input_sender, raw_input_rx) = if enable_raw_input {
+            let (tx, rx) = queue::channel(queue_policy);
+            (Some(tx), Some(rx))
+        } else {
+            (None, None)
+        };
         let mut ctx = CONTEXT.lock().unwrap();
+        set_mask(id, event_mask);
         ctx.window_map.insert(
-            hwnd.0,
+            id,
             Object {
-                props,
                 sender,
-                waker: None,
+                subscribers: vec![],
                 raw_input_sender,
-                raw_input_waker: None,
             },
         );
+        EventReceiver::new(id, rx, raw_input_rx)
     }
 
-    pub fn send_event(hwnd: HWND, event: Event) {
+    /// Returns `false` when the window does not exist.
+    pub fn send_event(id: impl Into<WindowId>, event: Event) -> bool {
+        let id = id.into();
+        // Dropping the last `CloseRequested` destroys the window, which must not happen while
+        // `CONTEXT` is locked.
+        let _close_requested = match &event {
+            Event::CloseRequested(e) => Some(e.clone()),
+            _ => None,
+        };
+        let masked = EVENT_MASKS
+            .read()
+            .unwrap()
+            .get(&id)
+            .is_some_and(|mask| !mask.matches(&event));
+        // Only registered windows have a mask, so the window exists.
+        if masked {
+            return true;
+        }
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "send_event",
+            window = ?id,
+            ?event,
+            depth = tracing::field::Empty
+        )
+        .entered();
         let mut ctx = CONTEXT.lock().unwrap();
-        let Some(obj) = ctx.window_map.get_mut(&hwnd.0) else { return };
-        obj.sender.send(event).unwrap_or(());
-        if let Some(waker) = obj.waker.take() {
-            waker.wake();
+        if !ctx.window_map.contains_key(&id) {
+            return false;
         }
+        let timestamp = timestamp();
+        let ctx = &mut *ctx;
+        let obj = ctx.window_map.get_mut(&id).unwrap();
+        let depth = obj.send_with_reply(&mut ctx.app_senders, id, event, timestamp);
+        #[cfg(feature = "tracing")]
+        span.record("depth", depth);
+        #[cfg(not(feature = "tracing"))]
+        let _ = depth;
+        true
     }
 
-    pub fn send_raw_input_event(hwnd: HWND, event: raw_input::RawInputEvent) {
+    pub fn send_raw_input_event(id: impl Into<WindowId>, event: raw_input::RawInputEvent) {
+        let id = id.into();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("send_raw_input_event", window = ?id, ?event).entered();
         let mut ctx = CONTEXT.lock().unwrap();
-        let Some(obj) = ctx.window_map.get_mut(&hwnd.0) else { return };
-        if let Some(sender) = obj.raw_input_sender.as_ref() {
-            sender.send(event).unwrap_or(());
+        let timestamp = timestamp();
+        #[cfg(feature = "inspector")]
+        if ctx.window_map.contains_key(&id) {
+            ctx.app_raw_input_senders
+                .retain(|sender| sender.is_connected());
+            for sender in &ctx.app_raw_input_senders {
+                sender.send((id, event.clone()), timestamp);
+            }
         }
-        if let Some(waker) = obj.raw_input_waker.take() {
-            waker.wake();
+        let Some(obj) = ctx.window_map.get_mut(&id) else { return };
+        if let Some(sender) = obj.raw_input_sender.as_ref() {
+            sender.send(event, timestamp);
         }
     }
 
+    /// Sends `Event::CloseRequested`. The window is destroyed unless the request is cancelled.
+    pub fn request_close(id: impl Into<WindowId>) {
+        let id = id.into();
+        Self::send_event(id, Event::CloseRequested(events::CloseRequested::new(id)));
+    }
+
+    #[cfg(windows)]
     pub fn quit() {
         let mut ctx = CONTEXT.lock().unwrap();
+        let timestamp = timestamp();
+        if let Some(&id) = ctx.window_map.keys().next() {
+            ctx.send_app_event(id, &Event::Quit, timestamp);
+        }
         for (_, obj) in ctx.window_map.iter_mut() {
-            obj.sender.send(Event::Quit).unwrap_or(());
-            if let Some(waker) = obj.waker.take() {
-                waker.wake();
-            }
+            obj.send(Event::Quit, timestamp);
         }
     }
 
-    pub fn remove_window(hwnd: HWND) -> Option<Object> {
+    /// Sends `Event::Closed` and unregisters the window.
+    ///
+    /// Returns `true` when the last window was closed. In that case `Event::Quit` is also sent.
+    pub fn close_window(id: impl Into<WindowId>) -> bool {
+        let id = id.into();
         let mut ctx = CONTEXT.lock().unwrap();
-        ctx.window_map.remove(&hwnd.0)
+        let Some(mut obj) = ctx.window_map.remove(&id) else { return false };
+        EVENT_MASKS.write().unwrap().remove(&id);
+        let timestamp = timestamp();
+        ctx.send_app_event(id, &Event::Closed, timestamp);
+        obj.send(Event::Closed, timestamp);
+        let last = ctx.window_map.is_empty();
+        if last {
+            ctx.send_app_event(id, &Event::Quit, timestamp);
+            obj.send(Event::Quit, timestamp);
+            if let Some(sender) = obj.raw_input_sender {
+                sender.send(raw_input::RawInputEvent::Quit, timestamp);
+            }
+        }
+        last
     }
 
-    pub fn set_waker(hwnd: HWND, waker: Waker) {
+    /// Adds a receiver of the events of a window. The receiver is disconnected at once when the
+    /// window has been closed.
+    pub fn subscribe(id: WindowId, queue_policy: QueuePolicy) -> EventReceiver {
+        let (sender, rx) = queue::channel(queue_policy);
         let mut ctx = CONTEXT.lock().unwrap();
-        let Some(mut obj) = ctx.window_map.get_mut(&hwnd.0) else { return };
-        obj.waker = Some(waker);
+        if let Some(obj) = ctx.window_map.get_mut(&id) {
+            obj.subscribers.push(sender);
+        }
+        EventReceiver::new(id, rx, None)
     }
 
-    pub fn set_raw_input_waker(hwnd: HWND, waker: Waker) {
-        let mut ctx = CONTEXT.lock().unwrap();
-        let Some(mut obj) = ctx.window_map.get_mut(&hwnd.0) else { return };
-        obj.raw_input_waker = Some(waker);
+    pub fn subscribe_app() -> AppEventReceiver {
+        let (sender, rx) = queue::channel(QueuePolicy::default());
+        CONTEXT.lock().unwrap().app_senders.push(sender);
+        AppEventReceiver::from_queue(rx)
     }
 
-    pub fn get_window_property<F, T>(hwnd: HWND, f: F) -> Option<T>
-    where
-        F: FnOnce(&WindowProperties) -> T,
-    {
-        let ctx = CONTEXT.lock().unwrap();
-        ctx.window_map.get(&hwnd.0).map(|obj| f(&obj.props))
+    /// Adds a receiver of the raw input of every window.
+    #[cfg(feature = "inspector")]
+    pub fn subscribe_app_raw_input() -> queue::Receiver<(WindowId, raw_input::RawInputEvent)> {
+        let (sender, rx) = queue::channel(QueuePolicy::default());
+        CONTEXT.lock().unwrap().app_raw_input_senders.push(sender);
+        rx
     }
 
-    pub fn set_window_property<F>(hwnd: HWND, f: F)
-    where
-        F: FnOnce(&mut WindowProperties),
-    {
-        let mut ctx = CONTEXT.lock().unwrap();
-        if let Some(obj) = ctx.window_map.get_mut(&hwnd.0) {
-            f(&mut obj.props);
+    fn send_app_event(&mut self, id: WindowId, event: &Event, timestamp: Timestamp) {
+        self.app_senders.retain(|sender| sender.is_connected());
+        for sender in &self.app_senders {
+            sender.send((id, event.clone()), timestamp);
         }
     }
 
-    pub fn window_is_closed(hwnd: HWND) -> bool {
+    /// Sets the OS time of the message which is being dispatched on the current thread, and
+    /// takes now as its arrival.
+    ///
+    /// Events sent on this thread carry the times until the returned guard is dropped.
+    pub fn set_message_time(time: Option<u32>) -> MessageTimeGuard {
+        MessageTimeGuard(
+            MESSAGE_TIME.with(|t| t.replace(time)),
+            MESSAGE_ARRIVAL.with(|t| t.replace(Some(Instant::now()))),
+        )
+    }
+
+    pub fn set_event_mask(id: WindowId, mask: EventMask) {
         let ctx = CONTEXT.lock().unwrap();
-        !ctx.window_map.contains_key(&hwnd.0)
+        if ctx.window_map.contains_key(&id) {
+            set_mask(id, mask);
+        }
+    }
+
+    pub fn window_is_closed(id: WindowId) -> bool {
+        let ctx = CONTEXT.lock().unwrap();
+        !ctx.window_map.contains_key(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        let _lock = crate::headless::tests::lock();
+        let id = WindowId(-1);
+        let mut rx = Context::register_window(id, false, QueuePolicy::default(), EventMask::ALL);
+        Context::send_event(id, Event::Activated);
+        {
+            let _time = Context::set_message_time(Some(100));
+            {
+                let _time = Context::set_message_time(Some(200));
+                Context::send_event(id, Event::Minimized);
+            }
+            Context::send_event(id, Event::Inactivated);
+        }
+        Context::close_window(id);
+        let mut times = vec![];
+        while let Ok(Some(_)) = rx.try_recv() {
+            times.push(rx.timestamp().unwrap());
+        }
+        let message_times = times.iter().map(|t| t.message_time).collect::<Vec<_>>();
+        assert_eq!(message_times[..3], [None, Some(200), Some(100)]);
+        assert_eq!(message_times[3], None);
+        assert!(times.windows(2).all(|t| t[0].instant <= t[1].instant));
+        // Events carry the arrival of their message, and the inner message arrived last.
+        assert!(times[0].arrival <= times[2].arrival);
+        assert!(times[2].arrival <= times[1].arrival);
+        assert!(times[1].arrival <= times[3].arrival);
+        assert!(times.iter().all(|t| t.arrival <= t.instant));
     }
 }
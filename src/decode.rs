@@ -0,0 +1,608 @@
+//! Decoding window messages without side effects.
+//!
+//! `decode` turns the parameters of a message into values, and `procedure` applies them. Memory
+//! which parameters point to, such as the `RECT` of `WM_SIZING`, is read by the handlers.
+
+use crate::*;
+
+const WM_DESTROY: u32 = 0x0002;
+const WM_SIZE: u32 = 0x0005;
+const WM_ACTIVATE: u32 = 0x0006;
+const WM_PAINT: u32 = 0x000f;
+const WM_CLOSE: u32 = 0x0010;
+const WM_SETCURSOR: u32 = 0x0020;
+const WM_WINDOWPOSCHANGED: u32 = 0x0047;
+const WM_NCCREATE: u32 = 0x0081;
+const WM_INPUT_DEVICE_CHANGE: u32 = 0x00fe;
+const WM_INPUT: u32 = 0x00ff;
+const WM_KEYDOWN: u32 = 0x0100;
+const WM_KEYUP: u32 = 0x0101;
+const WM_CHAR: u32 = 0x0102;
+const WM_IME_STARTCOMPOSITION: u32 = 0x010d;
+const WM_IME_ENDCOMPOSITION: u32 = 0x010e;
+const WM_IME_COMPOSITION: u32 = 0x010f;
+const WM_MOUSEMOVE: u32 = 0x0200;
+const WM_LBUTTONDOWN: u32 = 0x0201;
+const WM_LBUTTONUP: u32 = 0x0202;
+const WM_RBUTTONDOWN: u32 = 0x0204;
+const WM_RBUTTONUP: u32 = 0x0205;
+const WM_MBUTTONDOWN: u32 = 0x0207;
+const WM_MBUTTONUP: u32 = 0x0208;
+const WM_MOUSEWHEEL: u32 = 0x020a;
+const WM_XBUTTONDOWN: u32 = 0x020b;
+const WM_XBUTTONUP: u32 = 0x020c;
+const WM_MOUSEHWHEEL: u32 = 0x020e;
+const WM_SIZING: u32 = 0x0214;
+const WM_EXITSIZEMOVE: u32 = 0x0232;
+const WM_DROPFILES: u32 = 0x0233;
+const WM_IME_SETCONTEXT: u32 = 0x0281;
+const WM_MOUSELEAVE: u32 = 0x02a3;
+const WM_DPICHANGED: u32 = 0x02e0;
+const WM_GETDPISCALEDSIZE: u32 = 0x02e4;
+
+const WMSZ_LEFT: usize = 1;
+const WMSZ_RIGHT: usize = 2;
+const WMSZ_TOP: usize = 3;
+const WMSZ_TOPLEFT: usize = 4;
+const WMSZ_TOPRIGHT: usize = 5;
+const WMSZ_BOTTOM: usize = 6;
+const WMSZ_BOTTOMLEFT: usize = 7;
+const WMSZ_BOTTOMRIGHT: usize = 8;
+
+const SIZE_RESTORED: usize = 0;
+const SIZE_MINIMIZED: usize = 1;
+const SIZE_MAXIMIZED: usize = 2;
+
+const WA_ACTIVE: usize = 1;
+const WA_CLICKACTIVE: usize = 2;
+
+const HTCLIENT: i16 = 1;
+
+#[cfg(windows)]
+const _: () = {
+    use windows::Win32::UI::{Controls, WindowsAndMessaging as wm};
+
+    assert!(WM_DESTROY == wm::WM_DESTROY);
+    assert!(WM_SIZE == wm::WM_SIZE);
+    assert!(WM_ACTIVATE == wm::WM_ACTIVATE);
+    assert!(WM_PAINT == wm::WM_PAINT);
+    assert!(WM_CLOSE == wm::WM_CLOSE);
+    assert!(WM_SETCURSOR == wm::WM_SETCURSOR);
+    assert!(WM_WINDOWPOSCHANGED == wm::WM_WINDOWPOSCHANGED);
+    assert!(WM_NCCREATE == wm::WM_NCCREATE);
+    assert!(WM_INPUT_DEVICE_CHANGE == wm::WM_INPUT_DEVICE_CHANGE);
+    assert!(WM_INPUT == wm::WM_INPUT);
+    assert!(WM_KEYDOWN == wm::WM_KEYDOWN);
+    assert!(WM_KEYUP == wm::WM_KEYUP);
+    assert!(WM_CHAR == wm::WM_CHAR);
+    assert!(WM_IME_STARTCOMPOSITION == wm::WM_IME_STARTCOMPOSITION);
+    assert!(WM_IME_ENDCOMPOSITION == wm::WM_IME_ENDCOMPOSITION);
+    assert!(WM_IME_COMPOSITION == wm::WM_IME_COMPOSITION);
+    assert!(WM_MOUSEMOVE == wm::WM_MOUSEMOVE);
+    assert!(WM_LBUTTONDOWN == wm::WM_LBUTTONDOWN);
+    assert!(WM_LBUTTONUP == wm::WM_LBUTTONUP);
+    assert!(WM_RBUTTONDOWN == wm::WM_RBUTTONDOWN);
+    assert!(WM_RBUTTONUP == wm::WM_RBUTTONUP);
+    assert!(WM_MBUTTONDOWN == wm::WM_MBUTTONDOWN);
+    assert!(WM_MBUTTONUP == wm::WM_MBUTTONUP);
+    assert!(WM_MOUSEWHEEL == wm::WM_MOUSEWHEEL);
+    assert!(WM_XBUTTONDOWN == wm::WM_XBUTTONDOWN);
+    assert!(WM_XBUTTONUP == wm::WM_XBUTTONUP);
+    assert!(WM_MOUSEHWHEEL == wm::WM_MOUSEHWHEEL);
+    assert!(WM_SIZING == wm::WM_SIZING);
+    assert!(WM_EXITSIZEMOVE == wm::WM_EXITSIZEMOVE);
+    assert!(WM_DROPFILES == wm::WM_DROPFILES);
+    assert!(WM_IME_SETCONTEXT == wm::WM_IME_SETCONTEXT);
+    assert!(WM_MOUSELEAVE == Controls::WM_MOUSELEAVE);
+    assert!(WM_DPICHANGED == wm::WM_DPICHANGED);
+    assert!(WM_GETDPISCALEDSIZE == wm::WM_GETDPISCALEDSIZE);
+    assert!(WMSZ_LEFT == wm::WMSZ_LEFT as usize);
+    assert!(WMSZ_RIGHT == wm::WMSZ_RIGHT as usize);
+    assert!(WMSZ_TOP == wm::WMSZ_TOP as usize);
+    assert!(WMSZ_TOPLEFT == wm::WMSZ_TOPLEFT as usize);
+    assert!(WMSZ_TOPRIGHT == wm::WMSZ_TOPRIGHT as usize);
+    assert!(WMSZ_BOTTOM == wm::WMSZ_BOTTOM as usize);
+    assert!(WMSZ_BOTTOMLEFT == wm::WMSZ_BOTTOMLEFT as usize);
+    assert!(WMSZ_BOTTOMRIGHT == wm::WMSZ_BOTTOMRIGHT as usize);
+    assert!(SIZE_RESTORED == wm::SIZE_RESTORED as usize);
+    assert!(SIZE_MINIMIZED == wm::SIZE_MINIMIZED as usize);
+    assert!(SIZE_MAXIMIZED == wm::SIZE_MAXIMIZED as usize);
+    assert!(WA_ACTIVE == wm::WA_ACTIVE as usize);
+    assert!(WA_CLICKACTIVE == wm::WA_CLICKACTIVE as usize);
+    assert!(HTCLIENT == wm::HTCLIENT as i16);
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum SizeKind {
+    Minimized,
+    Maximized,
+    Restored,
+    Other,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum DecodedMessage {
+    Input,
+    Paint,
+    MouseMove(MouseState),
+    SetCursor {
+        client: bool,
+    },
+    MouseLeave {
+        buttons: MouseButtons,
+    },
+    MouseInput {
+        button: MouseButton,
+        button_state: ButtonState,
+        mouse_state: MouseState,
+    },
+    MouseWheel {
+        axis: MouseWheelAxis,
+        distance: i32,
+        mouse_state: MouseState,
+    },
+    KeyInput {
+        vkey: u16,
+        scan_code: ScanCode,
+        key_state: KeyState,
+        prev_pressed: bool,
+    },
+    /// `None` when the code unit is a surrogate.
+    CharInput(Option<char>),
+    ImeSetContext,
+    ImeStartComposition,
+    ImeComposition,
+    ImeEndComposition,
+    /// The window rect is pointed to by `lparam`.
+    Sizing(ResizingEdge),
+    Size {
+        kind: SizeKind,
+        size: PhysicalSize<u32>,
+    },
+    /// The `WINDOWPOS` is pointed to by `lparam`.
+    WindowPosChanged,
+    ExitSizeMove,
+    Activate {
+        active: bool,
+    },
+    /// The suggested window rect is pointed to by `lparam`.
+    DpiChanged {
+        new_dpi: u32,
+    },
+    /// The `SIZE` to return is pointed to by `lparam`.
+    GetDpiScaledSize {
+        new_dpi: u32,
+    },
+    DropFiles {
+        hdrop: usize,
+    },
+    NcCreate,
+    Close,
+    Destroy,
+    InputDeviceChange,
+    Other,
+}
+
+fn loword(x: usize) -> u16 {
+    (x & 0xffff) as _
+}
+
+fn hiword(x: usize) -> u16 {
+    ((x >> 16) & 0xffff) as _
+}
+
+/// Client coordinates are signed because they are negative outside the client area.
+fn lparam_to_point(lparam: isize) -> PhysicalPosition<i32> {
+    PhysicalPosition::new(
+        loword(lparam as _) as i16 as i32,
+        hiword(lparam as _) as i16 as i32,
+    )
+}
+
+fn lparam_to_size(lparam: isize) -> PhysicalSize<u32> {
+    PhysicalSize::new(loword(lparam as _) as u32, hiword(lparam as _) as u32)
+}
+
+fn mouse_state(wparam: usize, lparam: isize) -> MouseState {
+    MouseState {
+        position: lparam_to_point(lparam),
+        buttons: MouseButtons::from_wparam(wparam),
+    }
+}
+
+fn mouse_input(
+    button: MouseButton,
+    button_state: ButtonState,
+    wparam: usize,
+    lparam: isize,
+) -> DecodedMessage {
+    DecodedMessage::MouseInput {
+        button,
+        button_state,
+        mouse_state: mouse_state(wparam, lparam),
+    }
+}
+
+fn xbutton(wparam: usize) -> Option<MouseButton> {
+    match hiword(wparam) {
+        0x0001 => Some(MouseButton::Ex(0)),
+        0x0002 => Some(MouseButton::Ex(1)),
+        _ => None,
+    }
+}
+
+fn mouse_wheel(axis: MouseWheelAxis, wparam: usize, lparam: isize) -> DecodedMessage {
+    DecodedMessage::MouseWheel {
+        axis,
+        distance: hiword(wparam) as i16 as i32,
+        mouse_state: mouse_state(wparam, lparam),
+    }
+}
+
+fn key_input(key_state: KeyState, wparam: usize, lparam: isize) -> DecodedMessage {
+    DecodedMessage::KeyInput {
+        vkey: wparam as u16,
+        scan_code: ScanCode(((lparam >> 16) & 0x7f) as u32),
+        key_state,
+        prev_pressed: (lparam >> 30) & 0x01 != 0,
+    }
+}
+
+fn resizing_edge(wparam: usize) -> Option<ResizingEdge> {
+    let edge = match wparam {
+        WMSZ_LEFT => ResizingEdge::Left,
+        WMSZ_RIGHT => ResizingEdge::Right,
+        WMSZ_TOP => ResizingEdge::Top,
+        WMSZ_BOTTOM => ResizingEdge::Bottom,
+        WMSZ_TOPLEFT => ResizingEdge::TopLeft,
+        WMSZ_TOPRIGHT => ResizingEdge::TopRight,
+        WMSZ_BOTTOMLEFT => ResizingEdge::BottomLeft,
+        WMSZ_BOTTOMRIGHT => ResizingEdge::BottomLRight,
+        _ => return None,
+    };
+    Some(edge)
+}
+
+pub(crate) fn decode(msg: u32, wparam: usize, lparam: isize) -> DecodedMessage {
+    match msg {
+        WM_INPUT => DecodedMessage::Input,
+        WM_PAINT => DecodedMessage::Paint,
+        WM_MOUSEMOVE => DecodedMessage::MouseMove(mouse_state(wparam, lparam)),
+        WM_SETCURSOR => DecodedMessage::SetCursor {
+            client: loword(lparam as _) as i16 == HTCLIENT,
+        },
+        WM_MOUSELEAVE => DecodedMessage::MouseLeave {
+            buttons: MouseButtons::from_wparam(wparam),
+        },
+        WM_LBUTTONDOWN => mouse_input(MouseButton::Left, ButtonState::Pressed, wparam, lparam),
+        WM_RBUTTONDOWN => mouse_input(MouseButton::Right, ButtonState::Pressed, wparam, lparam),
+        WM_MBUTTONDOWN => mouse_input(MouseButton::Middle, ButtonState::Pressed, wparam, lparam),
+        WM_LBUTTONUP => mouse_input(MouseButton::Left, ButtonState::Released, wparam, lparam),
+        WM_RBUTTONUP => mouse_input(MouseButton::Right, ButtonState::Released, wparam, lparam),
+        WM_MBUTTONUP => mouse_input(MouseButton::Middle, ButtonState::Released, wparam, lparam),
+        WM_XBUTTONDOWN | WM_XBUTTONUP => {
+            let Some(button) = xbutton(wparam) else { return DecodedMessage::Other };
+            let button_state = if msg == WM_XBUTTONDOWN {
+                ButtonState::Pressed
+            } else {
+                ButtonState::Released
+            };
+            mouse_input(button, button_state, wparam, lparam)
+        }
+        WM_MOUSEWHEEL => mouse_wheel(MouseWheelAxis::Vertical, wparam, lparam),
+        WM_MOUSEHWHEEL => mouse_wheel(MouseWheelAxis::Horizontal, wparam, lparam),
+        WM_KEYDOWN => key_input(KeyState::Pressed, wparam, lparam),
+        WM_KEYUP => key_input(KeyState::Released, wparam, lparam),
+        WM_CHAR => DecodedMessage::CharInput(char::from_u32(wparam as u32)),
+        WM_IME_SETCONTEXT => DecodedMessage::ImeSetContext,
+        WM_IME_STARTCOMPOSITION => DecodedMessage::ImeStartComposition,
+        WM_IME_COMPOSITION => DecodedMessage::ImeComposition,
+        WM_IME_ENDCOMPOSITION => DecodedMessage::ImeEndComposition,
+        WM_SIZING => match resizing_edge(wparam) {
+            Some(edge) => DecodedMessage::Sizing(edge),
+            None => DecodedMessage::Other,
+        },
+        WM_SIZE => DecodedMessage::Size {
+            kind: match wparam {
+                SIZE_MINIMIZED => SizeKind::Minimized,
+                SIZE_MAXIMIZED => SizeKind::Maximized,
+                SIZE_RESTORED => SizeKind::Restored,
+                _ => SizeKind::Other,
+            },
+            size: lparam_to_size(lparam),
+        },
+        WM_WINDOWPOSCHANGED => DecodedMessage::WindowPosChanged,
+        WM_EXITSIZEMOVE => DecodedMessage::ExitSizeMove,
+        WM_ACTIVATE => DecodedMessage::Activate {
+            active: loword(wparam) as usize & (WA_ACTIVE | WA_CLICKACTIVE) != 0,
+        },
+        WM_DPICHANGED => DecodedMessage::DpiChanged {
+            new_dpi: hiword(wparam) as u32,
+        },
+        WM_GETDPISCALEDSIZE => DecodedMessage::GetDpiScaledSize {
+            new_dpi: wparam as u32,
+        },
+        WM_DROPFILES => DecodedMessage::DropFiles { hdrop: wparam },
+        WM_NCCREATE => DecodedMessage::NcCreate,
+        WM_CLOSE => DecodedMessage::Close,
+        WM_DESTROY => DecodedMessage::Destroy,
+        WM_INPUT_DEVICE_CHANGE => DecodedMessage::InputDeviceChange,
+        _ => DecodedMessage::Other,
+    }
+}
+
+/// Returns the client size of a window rect of `WM_SIZING`. `frame` is the difference between
+/// the current window size and the client size.
+pub(crate) fn sizing_client_size(
+    (left, top, right, bottom): (i32, i32, i32, i32),
+    frame: PhysicalSize<i32>,
+) -> PhysicalSize<u32> {
+    PhysicalSize::new(
+        (right - left - frame.width).max(0) as u32,
+        (bottom - top - frame.height).max(0) as u32,
+    )
+}
+
+/// Returns the window rect of `WM_SIZING` whose client size is `size`. The dragged edges move and
+/// the opposite edges stay.
+pub(crate) fn sizing_window_rect(
+    (left, top, right, bottom): (i32, i32, i32, i32),
+    edge: ResizingEdge,
+    size: PhysicalSize<u32>,
+    frame: PhysicalSize<i32>,
+) -> (i32, i32, i32, i32) {
+    let width = size.width as i32 + frame.width;
+    let height = size.height as i32 + frame.height;
+    let (left, right) = match edge {
+        ResizingEdge::Left | ResizingEdge::TopLeft | ResizingEdge::BottomLeft => {
+            (right - width, right)
+        }
+        _ => (left, left + width),
+    };
+    let (top, bottom) = match edge {
+        ResizingEdge::Top | ResizingEdge::TopLeft | ResizingEdge::TopRight => {
+            (bottom - height, bottom)
+        }
+        _ => (top, top + height),
+    };
+    (left, top, right, bottom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_lparam(lo: u16, hi: u16) -> isize {
+        ((hi as u32) << 16 | lo as u32) as i32 as isize
+    }
+
+    fn mouse(x: i32, y: i32, buttons: MouseButtons) -> MouseState {
+        MouseState {
+            position: PhysicalPosition::new(x, y),
+            buttons,
+        }
+    }
+
+    #[test]
+    fn decode_table() {
+        let left = MouseButtons::from_wparam(0x0001);
+        let cases = [
+            (WM_PAINT, 0, 0, DecodedMessage::Paint),
+            (
+                WM_MOUSEMOVE,
+                0x0001,
+                make_lparam(10, 20),
+                DecodedMessage::MouseMove(mouse(10, 20, left)),
+            ),
+            (
+                WM_MOUSEMOVE,
+                0,
+                make_lparam(-5i16 as u16, -7i16 as u16),
+                DecodedMessage::MouseMove(mouse(-5, -7, MouseButtons::new())),
+            ),
+            (
+                WM_SETCURSOR,
+                0,
+                make_lparam(1, WM_MOUSEMOVE as u16),
+                DecodedMessage::SetCursor { client: true },
+            ),
+            (
+                WM_SETCURSOR,
+                0,
+                make_lparam(2, 0),
+                DecodedMessage::SetCursor { client: false },
+            ),
+            (
+                WM_LBUTTONDOWN,
+                0x0001,
+                make_lparam(1, 2),
+                DecodedMessage::MouseInput {
+                    button: MouseButton::Left,
+                    button_state: ButtonState::Pressed,
+                    mouse_state: mouse(1, 2, left),
+                },
+            ),
+            (
+                WM_RBUTTONUP,
+                0,
+                make_lparam(3, 4),
+                DecodedMessage::MouseInput {
+                    button: MouseButton::Right,
+                    button_state: ButtonState::Released,
+                    mouse_state: mouse(3, 4, MouseButtons::new()),
+                },
+            ),
+            (
+                WM_XBUTTONDOWN,
+                0x0002 << 16 | 0x0040,
+                make_lparam(5, 6),
+                DecodedMessage::MouseInput {
+                    button: MouseButton::Ex(1),
+                    button_state: ButtonState::Pressed,
+                    mouse_state: mouse(5, 6, MouseButtons::from_wparam(0x0040)),
+                },
+            ),
+            (WM_XBUTTONUP, 0x0003 << 16, 0, DecodedMessage::Other),
+            (
+                WM_MOUSEWHEEL,
+                (-120i16 as u16 as usize) << 16,
+                make_lparam(7, 8),
+                DecodedMessage::MouseWheel {
+                    axis: MouseWheelAxis::Vertical,
+                    distance: -120,
+                    mouse_state: mouse(7, 8, MouseButtons::new()),
+                },
+            ),
+            (
+                WM_MOUSEHWHEEL,
+                120 << 16,
+                0,
+                DecodedMessage::MouseWheel {
+                    axis: MouseWheelAxis::Horizontal,
+                    distance: 120,
+                    mouse_state: mouse(0, 0, MouseButtons::new()),
+                },
+            ),
+            (
+                WM_KEYDOWN,
+                0x41,
+                0x4000_0000 | 0x1e << 16 | 1,
+                DecodedMessage::KeyInput {
+                    vkey: 0x41,
+                    scan_code: ScanCode(0x1e),
+                    key_state: KeyState::Pressed,
+                    prev_pressed: true,
+                },
+            ),
+            (
+                WM_KEYUP,
+                0x41,
+                0x1e << 16 | 1,
+                DecodedMessage::KeyInput {
+                    vkey: 0x41,
+                    scan_code: ScanCode(0x1e),
+                    key_state: KeyState::Released,
+                    prev_pressed: false,
+                },
+            ),
+            (
+                WM_CHAR,
+                'あ' as usize,
+                0,
+                DecodedMessage::CharInput(Some('あ')),
+            ),
+            (WM_CHAR, 0xd800, 0, DecodedMessage::CharInput(None)),
+            (
+                WM_SIZING,
+                WMSZ_BOTTOMRIGHT,
+                0,
+                DecodedMessage::Sizing(ResizingEdge::BottomLRight),
+            ),
+            (WM_SIZING, 0, 0, DecodedMessage::Other),
+            (
+                WM_SIZE,
+                SIZE_MAXIMIZED,
+                make_lparam(40000, 35000),
+                DecodedMessage::Size {
+                    kind: SizeKind::Maximized,
+                    size: PhysicalSize::new(40000, 35000),
+                },
+            ),
+            (
+                WM_SIZE,
+                SIZE_RESTORED,
+                make_lparam(640, 480),
+                DecodedMessage::Size {
+                    kind: SizeKind::Restored,
+                    size: PhysicalSize::new(640, 480),
+                },
+            ),
+            (
+                WM_SIZE,
+                SIZE_MINIMIZED,
+                0,
+                DecodedMessage::Size {
+                    kind: SizeKind::Minimized,
+                    size: PhysicalSize::new(0, 0),
+                },
+            ),
+            (
+                WM_ACTIVATE,
+                WA_CLICKACTIVE | 1 << 16,
+                0,
+                DecodedMessage::Activate { active: true },
+            ),
+            (
+                WM_ACTIVATE,
+                0,
+                0,
+                DecodedMessage::Activate { active: false },
+            ),
+            (
+                WM_DPICHANGED,
+                144 << 16 | 144,
+                0,
+                DecodedMessage::DpiChanged { new_dpi: 144 },
+            ),
+            (
+                WM_GETDPISCALEDSIZE,
+                120,
+                0,
+                DecodedMessage::GetDpiScaledSize { new_dpi: 120 },
+            ),
+            (
+                WM_DROPFILES,
+                0x1234,
+                0,
+                DecodedMessage::DropFiles { hdrop: 0x1234 },
+            ),
+            (WM_CLOSE, 0, 0, DecodedMessage::Close),
+            (WM_DESTROY, 0, 0, DecodedMessage::Destroy),
+            (0x0400, 1, 2, DecodedMessage::Other),
+        ];
+        for (msg, wparam, lparam, expected) in cases {
+            assert_eq!(
+                decode(msg, wparam, lparam),
+                expected,
+                "msg = {msg:#x}, wparam = {wparam:#x}, lparam = {lparam:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn sizing() {
+        let cases = [
+            ((0, 0, 100, 50), (0, 0), (100, 50)),
+            ((100, 200, 916, 839), (16, 39), (800, 600)),
+            ((500, 10, 520, 20), (16, 39), (4, 0)),
+        ];
+        for (rect, (fw, fh), (w, h)) in cases {
+            assert_eq!(
+                sizing_client_size(rect, PhysicalSize::new(fw, fh)),
+                PhysicalSize::new(w, h)
+            );
+        }
+    }
+
+    #[test]
+    fn sizing_rect() {
+        let rect = (100, 200, 916, 839);
+        let frame = PhysicalSize::new(16, 39);
+        let size = PhysicalSize::new(640, 480);
+        let cases = [
+            (ResizingEdge::BottomLRight, (100, 200, 756, 719)),
+            (ResizingEdge::Right, (100, 200, 756, 719)),
+            (ResizingEdge::TopLeft, (260, 320, 916, 839)),
+            (ResizingEdge::TopRight, (100, 320, 756, 839)),
+            (ResizingEdge::BottomLeft, (260, 200, 916, 719)),
+        ];
+        for (edge, expected) in cases {
+            assert_eq!(sizing_window_rect(rect, edge, size, frame), expected);
+        }
+        let unchanged = sizing_client_size(rect, frame);
+        assert_eq!(
+            sizing_window_rect(rect, ResizingEdge::Left, unchanged, frame),
+            rect
+        );
+    }
+}
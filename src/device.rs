@@ -1,5 +1,7 @@
 use crate::*;
+#[cfg(windows)]
 use tokio::sync::oneshot;
+#[cfg(windows)]
 use windows::Win32::{Foundation::WPARAM, UI::Input::KeyboardAndMouse::*};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
@@ -41,6 +43,7 @@ impl std::fmt::Display for MouseButton {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouseButtons(u32);
 
 impl MouseButtons {
@@ -59,6 +62,16 @@ impl MouseButtons {
         let btn = btn.as_u32();
         self.0 & btn == btn
     }
+
+    #[inline]
+    pub(crate) fn insert(&mut self, btn: MouseButton) {
+        self.0 |= btn.as_u32();
+    }
+
+    #[inline]
+    pub(crate) fn remove(&mut self, btn: MouseButton) {
+        self.0 &= !btn.as_u32();
+    }
 }
 
 impl std::ops::BitOr for MouseButton {
@@ -70,9 +83,19 @@ impl std::ops::BitOr for MouseButton {
     }
 }
 
+#[cfg(any(windows, test))]
+impl MouseButtons {
+    /// Takes the `MK_*` flags in the low word of `wparam`.
+    #[inline]
+    pub(crate) fn from_wparam(wparam: usize) -> Self {
+        Self((wparam & 0xffff) as _)
+    }
+}
+
+#[cfg(windows)]
 impl From<WPARAM> for MouseButtons {
     fn from(src: WPARAM) -> Self {
-        Self((src.0 & 0xffff) as _)
+        Self::from_wparam(src.0)
     }
 }
 
@@ -111,7 +134,8 @@ impl Default for MouseButtons {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouseState {
     pub position: PhysicalPosition<i32>,
     pub buttons: MouseButtons,
@@ -240,6 +264,7 @@ impl std::fmt::Display for VirtualKey {
 pub struct ScanCode(pub u32);
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyCode {
     pub vkey: VirtualKey,
     pub scan_code: ScanCode,
@@ -252,6 +277,7 @@ impl KeyCode {
     }
 }
 
+#[cfg(windows)]
 pub(crate) fn as_virtual_key(k: VIRTUAL_KEY) -> VirtualKey {
     match k {
         VK_1 => VirtualKey::Key1,
@@ -356,6 +382,7 @@ pub(crate) fn as_virtual_key(k: VIRTUAL_KEY) -> VirtualKey {
     }
 }
 
+#[cfg(windows)]
 pub(crate) fn as_raw_virtual_key(k: VirtualKey) -> VIRTUAL_KEY {
     match k {
         VirtualKey::Key1 => VK_1,
@@ -460,6 +487,7 @@ pub(crate) fn as_raw_virtual_key(k: VirtualKey) -> VIRTUAL_KEY {
     }
 }
 
+#[cfg(windows)]
 #[inline]
 pub async fn get_key_state(k: VirtualKey) -> bool {
     let (tx, rx) = oneshot::channel();
@@ -470,6 +498,7 @@ pub async fn get_key_state(k: VirtualKey) -> bool {
     rx.await.unwrap_or(false)
 }
 
+#[cfg(windows)]
 #[inline]
 pub async fn get_keyboard_state(mut keys: Vec<VirtualKey>) -> Vec<VirtualKey> {
     let (tx, rx) = oneshot::channel();
@@ -1,45 +1,62 @@
+//! File dialogs.
+//!
+//! On Windows `FileOpenDialog` and `FileSaveDialog` show the common item dialogs. A responder
+//! registered by [`set_responder`] answers dialogs instead on every platform, which makes code
+//! behind the dialogs testable.
+
 use crate::*;
+use once_cell::sync::Lazy;
 use std::{
     future::{Future, IntoFuture},
     path::PathBuf,
     pin::Pin,
+    sync::{Arc, Mutex},
 };
+#[cfg(windows)]
 use tokio::sync::oneshot;
+#[cfg(windows)]
 use windows::core::{Interface, HSTRING, PCWSTR, PWSTR};
+#[cfg(windows)]
 use windows::Win32::{
-    Foundation::{ERROR_CANCELLED, E_FAIL, HWND},
+    Foundation::{ERROR_CANCELLED, E_FAIL},
     System::Com::*,
     UI::Shell::Common::*,
     UI::Shell::*,
 };
 
+/// The values of `FILEOPENDIALOGOPTIONS`.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct FileDialogOptions(pub u32);
 
 impl FileDialogOptions {
-    pub const OVERWRITE_PROMPT: Self = FileDialogOptions(FOS_OVERWRITEPROMPT.0);
-    pub const STRICT_FILE_TYPES: Self = FileDialogOptions(FOS_STRICTFILETYPES.0);
-    pub const NO_CHANGE_DIR: Self = FileDialogOptions(FOS_NOCHANGEDIR.0);
-    pub const PICK_FOLDERS: Self = FileDialogOptions(FOS_PICKFOLDERS.0);
-    pub const FORCE_FILE_SYSTEM: Self = FileDialogOptions(FOS_FORCEFILESYSTEM.0);
-    pub const ALL_NON_STORAGE_ITEMS: Self = FileDialogOptions(FOS_ALLNONSTORAGEITEMS.0);
-    pub const NO_VALIDATE: Self = FileDialogOptions(FOS_NOVALIDATE.0);
-    const ALLOW_MULTI_SELECT: Self = FileDialogOptions(FOS_ALLOWMULTISELECT.0);
-    pub const PATH_MUST_EXIST: Self = FileDialogOptions(FOS_PATHMUSTEXIST.0);
-    pub const FILE_MUST_EXIST: Self = FileDialogOptions(FOS_FILEMUSTEXIST.0);
-    pub const CREATE_PROMPT: Self = FileDialogOptions(FOS_CREATEPROMPT.0);
-    pub const SHARE_AWARE: Self = FileDialogOptions(FOS_SHAREAWARE.0);
-    pub const NO_READONLY_RETURN: Self = FileDialogOptions(FOS_NOREADONLYRETURN.0);
-    pub const NO_TEST_FILE_CREATE: Self = FileDialogOptions(FOS_NOTESTFILECREATE.0);
-    pub const HIDE_MRU_PLACES: Self = FileDialogOptions(FOS_HIDEMRUPLACES.0);
-    pub const HIDE_PINNED_PLACES: Self = FileDialogOptions(FOS_HIDEPINNEDPLACES.0);
-    pub const NODE_REFERENCE_LINKS: Self = FileDialogOptions(FOS_NODEREFERENCELINKS.0);
-    pub const OK_BUTTON_NEED_SINTERACTION: Self = FileDialogOptions(FOS_OKBUTTONNEEDSINTERACTION.0);
-    pub const DONT_ADD_TO_RECENT: Self = FileDialogOptions(FOS_DONTADDTORECENT.0);
-    pub const FORCE_SHOW_HIDDEN: Self = FileDialogOptions(FOS_FORCESHOWHIDDEN.0);
-    pub const DEFAULT_NO_MINI_MODE: Self = FileDialogOptions(FOS_DEFAULTNOMINIMODE.0);
-    pub const FORCE_PREVIEW_PANE_ON: Self = FileDialogOptions(FOS_FORCEPREVIEWPANEON.0);
-    pub const SUPPORT_STREAMABLE_ITEMS: Self = FileDialogOptions(FOS_SUPPORTSTREAMABLEITEMS.0);
+    pub const OVERWRITE_PROMPT: Self = FileDialogOptions(0x2);
+    pub const STRICT_FILE_TYPES: Self = FileDialogOptions(0x4);
+    pub const NO_CHANGE_DIR: Self = FileDialogOptions(0x8);
+    pub const PICK_FOLDERS: Self = FileDialogOptions(0x20);
+    pub const FORCE_FILE_SYSTEM: Self = FileDialogOptions(0x40);
+    pub const ALL_NON_STORAGE_ITEMS: Self = FileDialogOptions(0x80);
+    pub const NO_VALIDATE: Self = FileDialogOptions(0x100);
+    const ALLOW_MULTI_SELECT: Self = FileDialogOptions(0x200);
+    pub const PATH_MUST_EXIST: Self = FileDialogOptions(0x800);
+    pub const FILE_MUST_EXIST: Self = FileDialogOptions(0x1000);
+    pub const CREATE_PROMPT: Self = FileDialogOptions(0x2000);
+    pub const SHARE_AWARE: Self = FileDialogOptions(0x4000);
+    pub const NO_READONLY_RETURN: Self = FileDialogOptions(0x8000);
+    pub const NO_TEST_FILE_CREATE: Self = FileDialogOptions(0x10000);
+    pub const HIDE_MRU_PLACES: Self = FileDialogOptions(0x20000);
+    pub const HIDE_PINNED_PLACES: Self = FileDialogOptions(0x40000);
+    pub const NODE_REFERENCE_LINKS: Self = FileDialogOptions(0x100000);
+    pub const OK_BUTTON_NEED_SINTERACTION: Self = FileDialogOptions(0x200000);
+    pub const DONT_ADD_TO_RECENT: Self = FileDialogOptions(0x2000000);
+    pub const FORCE_SHOW_HIDDEN: Self = FileDialogOptions(0x10000000);
+    pub const DEFAULT_NO_MINI_MODE: Self = FileDialogOptions(0x20000000);
+    pub const FORCE_PREVIEW_PANE_ON: Self = FileDialogOptions(0x40000000);
+    pub const SUPPORT_STREAMABLE_ITEMS: Self = FileDialogOptions(0x80000000);
+
+    #[inline]
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
 }
 
 impl std::ops::BitAnd for FileDialogOptions {
@@ -90,12 +107,14 @@ impl std::ops::BitXorAssign for FileDialogOptions {
     }
 }
 
+#[cfg(windows)]
 impl From<FileDialogOptions> for FILEOPENDIALOGOPTIONS {
     fn from(src: FileDialogOptions) -> Self {
         Self(src.0)
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct FilterSpec {
     pub name: String,
     pub spec: String,
@@ -147,8 +166,10 @@ where
     }
 }
 
+#[cfg(windows)]
 struct DisplayName(PWSTR);
 
+#[cfg(windows)]
 impl DisplayName {
     fn to_path_buf(&self) -> Result<PathBuf> {
         unsafe {
@@ -162,6 +183,7 @@ impl DisplayName {
     }
 }
 
+#[cfg(windows)]
 impl Drop for DisplayName {
     fn drop(&mut self) {
         unsafe {
@@ -170,10 +192,12 @@ impl Drop for DisplayName {
     }
 }
 
+#[cfg(windows)]
 trait DisplayNameWrapper {
     fn display_name(&self) -> Result<PathBuf>;
 }
 
+#[cfg(windows)]
 impl DisplayNameWrapper for IShellItem {
     fn display_name(&self) -> Result<PathBuf> {
         unsafe { DisplayName(self.GetDisplayName(SIGDN_FILESYSPATH)?).to_path_buf() }
@@ -183,12 +207,23 @@ impl DisplayNameWrapper for IShellItem {
 pub trait OpenDialogResult: Sized + Send {
     const OPTIONS: FileDialogOptions;
 
+    #[cfg(windows)]
     fn get_result(dialog: &IFileOpenDialog) -> Result<Self>;
+    fn from_response(response: FileDialogResponse) -> Option<Self>;
 }
 
 impl OpenDialogResult for PathBuf {
     const OPTIONS: FileDialogOptions = FileDialogOptions(0);
 
+    fn from_response(response: FileDialogResponse) -> Option<Self> {
+        match response {
+            FileDialogResponse::Path(path) => Some(path),
+            FileDialogResponse::Paths(paths) => paths.into_iter().next(),
+            FileDialogResponse::Cancel => None,
+        }
+    }
+
+    #[cfg(windows)]
     fn get_result(dialog: &IFileOpenDialog) -> Result<Self> {
         unsafe {
             let result = dialog.GetResult()?;
@@ -201,6 +236,15 @@ impl OpenDialogResult for PathBuf {
 impl OpenDialogResult for Vec<PathBuf> {
     const OPTIONS: FileDialogOptions = FileDialogOptions::ALLOW_MULTI_SELECT;
 
+    fn from_response(response: FileDialogResponse) -> Option<Self> {
+        match response {
+            FileDialogResponse::Path(path) => Some(vec![path]),
+            FileDialogResponse::Paths(paths) => Some(paths),
+            FileDialogResponse::Cancel => None,
+        }
+    }
+
+    #[cfg(windows)]
     fn get_result(dialog: &IFileOpenDialog) -> Result<Self> {
         unsafe {
             let result = dialog.GetResults()?;
@@ -216,22 +260,32 @@ impl OpenDialogResult for Vec<PathBuf> {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileDialogKind {
+    Open,
+    Save,
+}
+
+/// The settings of a dialog which a responder receives.
+#[derive(Clone, Debug)]
 pub struct FileDialogParams {
-    title: Option<String>,
-    ok_button_label: Option<String>,
-    default_folder: Option<PathBuf>,
-    default_extension: Option<String>,
-    file_name_label: Option<String>,
-    file_types: Vec<FilterSpec>,
-    file_type_index: usize,
-    options: FileDialogOptions,
-    owner: Option<HWND>,
+    pub kind: FileDialogKind,
+    pub title: Option<String>,
+    pub ok_button_label: Option<String>,
+    pub default_folder: Option<PathBuf>,
+    pub default_extension: Option<String>,
+    pub file_name_label: Option<String>,
+    pub file_types: Vec<FilterSpec>,
+    pub file_type_index: usize,
+    pub options: FileDialogOptions,
+    pub owner: Option<WindowId>,
 }
 
-impl Default for FileDialogParams {
+impl FileDialogParams {
     #[inline]
-    fn default() -> Self {
+    fn new(kind: FileDialogKind, options: FileDialogOptions) -> Self {
         Self {
+            kind,
             title: None,
             ok_button_label: None,
             default_folder: None,
@@ -239,12 +293,62 @@ impl Default for FileDialogParams {
             file_name_label: None,
             file_types: vec![],
             file_type_index: 1,
-            options: FileDialogOptions(0),
+            options,
             owner: None,
         }
     }
+
+    /// Returns `true` when the dialog was built by `FileOpenDialog::allow_multi_select`.
+    #[inline]
+    pub fn is_multi_select(&self) -> bool {
+        self.options.contains(FileDialogOptions::ALLOW_MULTI_SELECT)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FileDialogResponse {
+    Path(PathBuf),
+    Paths(Vec<PathBuf>),
+    Cancel,
+}
+
+/// Answers file dialogs instead of the user.
+pub trait FileDialogResponder: Send + Sync + 'static {
+    fn respond(&self, params: &FileDialogParams) -> FileDialogResponse;
+}
+
+impl<F> FileDialogResponder for F
+where
+    F: Fn(&FileDialogParams) -> FileDialogResponse + Send + Sync + 'static,
+{
+    #[inline]
+    fn respond(&self, params: &FileDialogParams) -> FileDialogResponse {
+        self(params)
+    }
+}
+
+static RESPONDER: Lazy<Mutex<Option<Arc<dyn FileDialogResponder>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// Makes `responder` answer every following dialog.
+///
+/// Without a responder, dialogs are shown on Windows and canceled on other platforms.
+#[inline]
+pub fn set_responder(responder: impl FileDialogResponder) {
+    *RESPONDER.lock().unwrap() = Some(Arc::new(responder));
+}
+
+#[inline]
+pub fn clear_responder() {
+    *RESPONDER.lock().unwrap() = None;
+}
+
+fn respond(params: &FileDialogParams) -> Option<FileDialogResponse> {
+    let responder = RESPONDER.lock().unwrap().clone()?;
+    Some(responder.respond(params))
 }
 
+#[cfg(windows)]
 fn show_dialog<T>(dialog: &T, params: FileDialogParams) -> Result<()>
 where
     T: Interface,
@@ -301,10 +405,10 @@ impl FileOpenDialog<()> {
     #[inline]
     pub fn new() -> FileOpenDialog<PathBuf> {
         FileOpenDialog {
-            params: FileDialogParams {
-                options: FileDialogOptions::PATH_MUST_EXIST | FileDialogOptions::FILE_MUST_EXIST,
-                ..Default::default()
-            },
+            params: FileDialogParams::new(
+                FileDialogKind::Open,
+                FileDialogOptions::PATH_MUST_EXIST | FileDialogOptions::FILE_MUST_EXIST,
+            ),
             _t: std::marker::PhantomData,
         }
     }
@@ -372,13 +476,26 @@ where
 
     #[inline]
     pub fn owner(mut self, window: &Window) -> Self {
-        self.params.owner = Some(HWND(window.raw_handle() as _));
+        self.params.owner = Some(window.id());
         self
     }
 
     pub async fn show(mut self) -> Result<Option<T>> {
-        let (tx, rx): (oneshot::Sender<Result<T>>, _) = oneshot::channel();
         self.params.options |= T::OPTIONS;
+        if let Some(response) = respond(&self.params) {
+            return Ok(T::from_response(response));
+        }
+        self.show_dialog().await
+    }
+
+    #[cfg(not(windows))]
+    async fn show_dialog(self) -> Result<Option<T>> {
+        Ok(None)
+    }
+
+    #[cfg(windows)]
+    async fn show_dialog(self) -> Result<Option<T>> {
+        let (tx, rx): (oneshot::Sender<Result<T>>, _) = oneshot::channel();
         UiThread::send_task(move || {
             let dialog: IFileOpenDialog = unsafe {
                 match CoCreateInstance(&FileOpenDialog, None, CLSCTX_INPROC_SERVER) {
@@ -429,12 +546,12 @@ impl FileSaveDialog {
     #[inline]
     pub fn new() -> Self {
         Self {
-            params: FileDialogParams {
-                options: FileDialogOptions::PATH_MUST_EXIST
+            params: FileDialogParams::new(
+                FileDialogKind::Save,
+                FileDialogOptions::PATH_MUST_EXIST
                     | FileDialogOptions::NO_READONLY_RETURN
                     | FileDialogOptions::OVERWRITE_PROMPT,
-                ..Default::default()
-            },
+            ),
         }
     }
 
@@ -488,11 +605,24 @@ impl FileSaveDialog {
 
     #[inline]
     pub fn owner(mut self, window: &Window) -> Self {
-        self.params.owner = Some(HWND(window.raw_handle() as _));
+        self.params.owner = Some(window.id());
         self
     }
 
     pub async fn show(self) -> Result<Option<PathBuf>> {
+        if let Some(response) = respond(&self.params) {
+            return Ok(PathBuf::from_response(response));
+        }
+        self.show_dialog().await
+    }
+
+    #[cfg(not(windows))]
+    async fn show_dialog(self) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    #[cfg(windows)]
+    async fn show_dialog(self) -> Result<Option<PathBuf>> {
         let (tx, rx): (oneshot::Sender<Result<PathBuf>>, _) = oneshot::channel();
         UiThread::send_task(move || {
             let dialog: IFileSaveDialog = unsafe {
@@ -539,6 +669,13 @@ impl FileSaveDialog {
     }
 }
 
+impl Default for FileSaveDialog {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IntoFuture for FileSaveDialog {
     type Output = Result<Option<PathBuf>>;
     type IntoFuture = Pin<Box<dyn Future<Output = Self::Output>>>;
@@ -548,3 +685,47 @@ impl IntoFuture for FileSaveDialog {
         Box::pin(self.show())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responder() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let r = received.clone();
+        set_responder(move |params: &FileDialogParams| {
+            r.lock().unwrap().push(params.clone());
+            match params.kind {
+                FileDialogKind::Open if params.is_multi_select() => {
+                    FileDialogResponse::Paths(vec!["a.txt".into(), "b.txt".into()])
+                }
+                FileDialogKind::Open => FileDialogResponse::Path("a.txt".into()),
+                FileDialogKind::Save => FileDialogResponse::Cancel,
+            }
+        });
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let path = FileOpenDialog::new()
+                .title("open")
+                .default_folder("docs")
+                .file_types([("Text", "*.txt")])
+                .await
+                .unwrap();
+            assert_eq!(path, Some(PathBuf::from("a.txt")));
+            let paths = FileOpenDialog::new().allow_multi_select().await.unwrap();
+            assert_eq!(paths.unwrap().len(), 2);
+            assert_eq!(FileSaveDialog::new().await.unwrap(), None);
+        });
+        clear_responder();
+        let received = received.lock().unwrap();
+        assert_eq!(received[0].title.as_deref(), Some("open"));
+        assert_eq!(received[0].default_folder, Some(PathBuf::from("docs")));
+        assert_eq!(received[0].file_types, [FilterSpec::new("Text", "*.txt")]);
+        assert!(!received[0].is_multi_select());
+        assert!(received[1].is_multi_select());
+        assert_eq!(received[2].kind, FileDialogKind::Save);
+    }
+}
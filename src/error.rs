@@ -1,20 +1,35 @@
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum Error {
+    #[cfg(windows)]
     #[error("{0}")]
     Api(windows::core::Error),
+    #[cfg(all(unix, feature = "x11"))]
+    #[error("{0}")]
+    X11(x11rb::errors::ReplyOrIdError),
+    #[error("backend already set")]
+    BackendAlreadySet,
+    #[error("no backend available")]
+    NoBackend,
     #[error("ui thread closed")]
     UiThreadClosed,
+    #[error("window closed")]
+    WindowClosed,
+    #[cfg(feature = "record")]
+    #[error("unsupported recording format version {0}")]
+    UnsupportedRecordVersion(u32),
     #[error("{0}")]
     Io(std::io::Error),
 }
 
+#[cfg(windows)]
 impl Error {
     pub(crate) fn from_win32() -> Self {
         windows::core::Error::from_win32().into()
     }
 }
 
+#[cfg(windows)]
 impl From<windows::core::Error> for Error {
     fn from(src: windows::core::Error) -> Self {
         Self::Api(src)
@@ -1,18 +1,37 @@
 use crate::*;
+use std::any::Any;
 use std::path::PathBuf;
-use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Instant;
 
-#[derive(Debug)]
+/// When an event happened.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Timestamp {
+    /// The time of the OS message in milliseconds, such as `GetMessageTime` on Windows and the
+    /// time of an X11 or Wayland input event. The origin depends on the platform and the value
+    /// wraps around. `None` when the message has no time.
+    pub message_time: Option<u32>,
+    /// When the event was enqueued.
+    pub instant: Instant,
+    /// When the message which caused the event arrived, or `instant` if the event was not caused
+    /// by a message.
+    pub arrival: Instant,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Draw {
     pub invalid_rect: PhysicalRect<i32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Moved {
     pub position: ScreenPosition,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ResizingEdge {
     Left,
     Right,
@@ -24,106 +43,227 @@ pub enum ResizingEdge {
     BottomLRight,
 }
 
-#[derive(Debug)]
+/// The client size while the user drags an edge of a window.
+///
+/// Clones share the size, which the window is resized to when the last clone is dropped. The UI
+/// thread waits for the size up to the reply timeout of the window.
+#[derive(Clone)]
 pub struct Resizing {
-    pub size: PhysicalSize<u32>,
-    pub edge: ResizingEdge,
+    edge: ResizingEdge,
+    reply: Reply<PhysicalSize<u32>>,
 }
 
-#[derive(Debug)]
+impl Resizing {
+    #[inline]
+    pub(crate) fn new(edge: ResizingEdge, reply: Reply<PhysicalSize<u32>>) -> Self {
+        Self { edge, reply }
+    }
+
+    #[inline]
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.reply.get()
+    }
+
+    #[inline]
+    pub fn set_size(&mut self, size: impl Into<PhysicalSize<u32>>) {
+        self.reply.set(size.into());
+    }
+
+    #[inline]
+    pub fn edge(&self) -> ResizingEdge {
+        self.edge
+    }
+
+    fn detached(&self) -> Self {
+        Self::new(self.edge, self.reply.detached())
+    }
+}
+
+impl std::fmt::Debug for Resizing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Resizing")
+            .field("size", &self.size())
+            .field("edge", &self.edge)
+            .finish()
+    }
+}
+
+/// The serialized form of `Resizing` which has only the size and the edge.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Resizing")]
+struct ResizingData {
+    size: PhysicalSize<u32>,
+    edge: ResizingEdge,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Resizing {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        ResizingData {
+            size: self.size(),
+            edge: self.edge,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A deserialized `Resizing` is not connected to a window, so `set_size` does not resize it.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Resizing {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let data = ResizingData::deserialize(deserializer)?;
+        Ok(Self::new(data.edge, Reply::new(data.size, |_| {})))
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Resized {
     pub size: PhysicalSize<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouseInput {
     pub button: MouseButton,
     pub button_state: ButtonState,
     pub mouse_state: MouseState,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CursorMoved {
     pub mouse_state: MouseState,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CursorEntered {
     pub mouse_state: MouseState,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CursorLeft {
     pub mouse_state: MouseState,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouseWheel {
     pub axis: MouseWheelAxis,
     pub distance: i32,
     pub mouse_state: MouseState,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyInput {
     pub key_code: KeyCode,
     pub key_state: KeyState,
     pub prev_pressed: bool,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CharInput {
     pub c: char,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Maximized {
     pub size: PhysicalSize<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Restored {
     pub size: PhysicalSize<u32>,
 }
 
-pub struct ImeStartComposition {
-    position: PhysicalPosition<i32>,
-    tx: Sender<PhysicalPosition<i32>>,
-}
+/// Clones share the position, which is passed to the IME when the last clone is dropped.
+///
+/// The UI thread waits for the position up to the reply timeout of the window. Only one receiver
+/// gets the event whose position is passed to the IME. See `EventReceiver::subscribe`.
+#[derive(Clone)]
+pub struct ImeStartComposition(Reply<PhysicalPosition<i32>>);
 
 impl ImeStartComposition {
-    pub(crate) fn new(tx: Sender<PhysicalPosition<i32>>) -> Self {
-        Self {
-            position: (0, 0).into(),
-            tx,
-        }
+    #[inline]
+    pub(crate) fn new(reply: Reply<PhysicalPosition<i32>>) -> Self {
+        Self(reply)
+    }
+
+    #[inline]
+    pub fn position(&self) -> PhysicalPosition<i32> {
+        self.0.get()
     }
 
     #[inline]
     pub fn set_position(&mut self, position: impl Into<PhysicalPosition<i32>>) {
-        self.position = position.into();
+        self.0.set(position.into());
     }
-}
 
-impl Drop for ImeStartComposition {
-    fn drop(&mut self) {
-        self.tx.send(self.position).unwrap_or(());
+    fn detached(&self) -> Self {
+        Self(self.0.detached())
     }
 }
 
 impl std::fmt::Debug for ImeStartComposition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.position)
+        write!(f, "{:?}", self.position())
     }
 }
 
-#[derive(Debug)]
+/// The serialized form of `ImeStartComposition` which has only the position.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "ImeStartComposition")]
+struct ImeStartCompositionData {
+    position: PhysicalPosition<i32>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ImeStartComposition {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        ImeStartCompositionData {
+            position: self.position(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A deserialized `ImeStartComposition` is not connected to a window, so `set_position` does
+/// not move the candidate window.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ImeStartComposition {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let data = ImeStartCompositionData::deserialize(deserializer)?;
+        Ok(Self::new(Reply::new(data.position, |_| {})))
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImeComposition {
     pub chars: Vec<char>,
     pub clauses: Vec<ime::Clause>,
     pub cursor_position: usize,
 }
 
+#[cfg(windows)]
 impl ImeComposition {
     pub(crate) fn new(imc: &ime::Imc) -> Option<Self> {
         let s = imc.get_composition_string()?;
@@ -136,23 +276,136 @@ impl ImeComposition {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImeEndComposition {
     pub result: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DpiChanged {
     pub new_dpi: u32,
 }
 
-#[derive(Debug)]
+/// `WM_DROPFILES` comes after the drop has finished, so there is no drop effect to answer.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DropFiles {
     pub paths: Vec<PathBuf>,
     pub position: PhysicalPosition<i32>,
 }
 
-#[derive(Debug)]
+/// Asks whether to close a window.
+///
+/// Clones share the answer, and the window is closed when the last clone is dropped unless the
+/// request has been cancelled. Only one receiver gets the request which closes it and the others
+/// get a copy which closes nothing. See `EventReceiver::subscribe`.
+#[derive(Clone)]
+pub struct CloseRequested(Reply<bool>);
+
+impl CloseRequested {
+    pub(crate) fn new(id: WindowId) -> Self {
+        Self(Reply::new(true, move |close| {
+            if close {
+                backend::get().destroy(id);
+            }
+        }))
+    }
+
+    fn detached(&self) -> Self {
+        Self(self.0.detached())
+    }
+
+    /// Keeps the window open.
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.set(false);
+    }
+
+    /// Closes the window when the other clones have been dropped.
+    #[inline]
+    pub fn confirm(self) {
+        self.0.set(true);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        !self.0.get()
+    }
+}
+
+impl std::fmt::Debug for CloseRequested {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloseRequested")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CloseRequested {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_unit_struct("CloseRequested")
+    }
+}
+
+/// A deserialized `CloseRequested` is not connected to a window, so it closes nothing.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CloseRequested {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "CloseRequested")]
+        struct Data;
+
+        Data::deserialize(deserializer)?;
+        Ok(Self(Reply::new(true, |_| {})))
+    }
+}
+
+/// A window message which witas does not handle, forwarded by `WindowBuilder::forward_messages`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message {
+    pub msg: u32,
+    pub wparam: usize,
+    pub lparam: isize,
+}
+
+/// A value posted by `EventSender`.
+#[derive(Clone)]
+pub struct User(Arc<dyn Any + Send + Sync>);
+
+impl User {
+    #[inline]
+    pub(crate) fn new<T: Any + Send + Sync>(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+
+    #[inline]
+    pub fn is<T: Any>(&self) -> bool {
+        self.0.is::<T>()
+    }
+
+    #[inline]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref::<T>()
+    }
+}
+
+impl std::fmt::Debug for User {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("User").finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Event {
     Activated,
@@ -176,6 +429,229 @@ pub enum Event {
     Restored(Restored),
     DpiChanged(DpiChanged),
     DropFiles(DropFiles),
+    CloseRequested(CloseRequested),
+    /// Sent only on Windows.
+    Message(Message),
     Closed,
     Quit,
+    /// Cannot be serialized. It is the last variant so that the indices of the other variants
+    /// match between serializing and deserializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    User(User),
+}
+
+impl Event {
+    /// Returns the name of the variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Activated => "Activated",
+            Self::Inactivated => "Inactivated",
+            Self::Draw(_) => "Draw",
+            Self::Moved(_) => "Moved",
+            Self::Resizing(_) => "Resizing",
+            Self::Resized(_) => "Resized",
+            Self::MouseInput(_) => "MouseInput",
+            Self::CursorMoved(_) => "CursorMoved",
+            Self::CursorEntered(_) => "CursorEntered",
+            Self::CursorLeft(_) => "CursorLeft",
+            Self::MouseWheel(_) => "MouseWheel",
+            Self::KeyInput(_) => "KeyInput",
+            Self::CharInput(_) => "CharInput",
+            Self::ImeStartComposition(_) => "ImeStartComposition",
+            Self::ImeComposition(_) => "ImeComposition",
+            Self::ImeEndComposition(_) => "ImeEndComposition",
+            Self::Minimized => "Minimized",
+            Self::Maximized(_) => "Maximized",
+            Self::Restored(_) => "Restored",
+            Self::DpiChanged(_) => "DpiChanged",
+            Self::DropFiles(_) => "DropFiles",
+            Self::CloseRequested(_) => "CloseRequested",
+            Self::Message(_) => "Message",
+            Self::Closed => "Closed",
+            Self::Quit => "Quit",
+            Self::User(_) => "User",
+        }
+    }
+
+    /// Returns a clone for receivers other than the one which answers, whose answers are not
+    /// passed back to the backend.
+    pub(crate) fn detached(&self) -> Self {
+        match self {
+            Self::Resizing(e) => Self::Resizing(e.detached()),
+            Self::ImeStartComposition(e) => Self::ImeStartComposition(e.detached()),
+            Self::CloseRequested(e) => Self::CloseRequested(e.detached()),
+            _ => self.clone(),
+        }
+    }
+}
+
+/// The kinds of events which are delivered to `EventReceiver`.
+///
+/// `Event::Closed` and `Event::Quit` are always delivered.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct EventMask(u32);
+
+impl EventMask {
+    pub const ACTIVATED: Self = Self(1 << 0);
+    pub const INACTIVATED: Self = Self(1 << 1);
+    pub const DRAW: Self = Self(1 << 2);
+    pub const MOVED: Self = Self(1 << 3);
+    pub const RESIZING: Self = Self(1 << 4);
+    pub const RESIZED: Self = Self(1 << 5);
+    pub const MOUSE_INPUT: Self = Self(1 << 6);
+    pub const CURSOR_MOVED: Self = Self(1 << 7);
+    pub const CURSOR_ENTERED: Self = Self(1 << 8);
+    pub const CURSOR_LEFT: Self = Self(1 << 9);
+    pub const MOUSE_WHEEL: Self = Self(1 << 10);
+    pub const KEY_INPUT: Self = Self(1 << 11);
+    pub const CHAR_INPUT: Self = Self(1 << 12);
+    pub const IME_START_COMPOSITION: Self = Self(1 << 13);
+    pub const IME_COMPOSITION: Self = Self(1 << 14);
+    pub const IME_END_COMPOSITION: Self = Self(1 << 15);
+    pub const MINIMIZED: Self = Self(1 << 16);
+    pub const MAXIMIZED: Self = Self(1 << 17);
+    pub const RESTORED: Self = Self(1 << 18);
+    pub const DPI_CHANGED: Self = Self(1 << 19);
+    pub const DROP_FILES: Self = Self(1 << 20);
+    pub const USER: Self = Self(1 << 21);
+    /// A masked `Event::CloseRequested` closes the window at once.
+    pub const CLOSE_REQUESTED: Self = Self(1 << 22);
+    pub const MESSAGE: Self = Self(1 << 23);
+    pub const ALL: Self = Self((1 << 24) - 1);
+
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    #[inline]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    #[inline]
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Returns `true` when `event` passes the mask.
+    pub(crate) fn matches(&self, event: &Event) -> bool {
+        let kind = match event {
+            Event::Activated => Self::ACTIVATED,
+            Event::Inactivated => Self::INACTIVATED,
+            Event::Draw(_) => Self::DRAW,
+            Event::Moved(_) => Self::MOVED,
+            Event::Resizing(_) => Self::RESIZING,
+            Event::Resized(_) => Self::RESIZED,
+            Event::MouseInput(_) => Self::MOUSE_INPUT,
+            Event::CursorMoved(_) => Self::CURSOR_MOVED,
+            Event::CursorEntered(_) => Self::CURSOR_ENTERED,
+            Event::CursorLeft(_) => Self::CURSOR_LEFT,
+            Event::MouseWheel(_) => Self::MOUSE_WHEEL,
+            Event::KeyInput(_) => Self::KEY_INPUT,
+            Event::CharInput(_) => Self::CHAR_INPUT,
+            Event::ImeStartComposition(_) => Self::IME_START_COMPOSITION,
+            Event::ImeComposition(_) => Self::IME_COMPOSITION,
+            Event::ImeEndComposition(_) => Self::IME_END_COMPOSITION,
+            Event::Minimized => Self::MINIMIZED,
+            Event::Maximized(_) => Self::MAXIMIZED,
+            Event::Restored(_) => Self::RESTORED,
+            Event::DpiChanged(_) => Self::DPI_CHANGED,
+            Event::DropFiles(_) => Self::DROP_FILES,
+            Event::User(_) => Self::USER,
+            Event::CloseRequested(_) => Self::CLOSE_REQUESTED,
+            Event::Message(_) => Self::MESSAGE,
+            Event::Closed | Event::Quit => return true,
+        };
+        self.contains(kind)
+    }
+}
+
+impl Default for EventMask {
+    #[inline]
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl std::ops::BitOr for EventMask {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for EventMask {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    fn round_trip(event: Event) {
+        let json = serde_json::to_string(&event).unwrap();
+        let de: Event = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&de).unwrap(), json);
+        assert_eq!(format!("{:?}", de), format!("{:?}", event));
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let mouse_state = MouseState {
+            position: PhysicalPosition::new(10, 20),
+            buttons: MouseButton::Left | MouseButton::Ex(1),
+        };
+        round_trip(Event::Draw(Draw {
+            invalid_rect: PhysicalRect::new((1, 2), (3, 4)),
+        }));
+        round_trip(Event::Resizing(Resizing::new(
+            ResizingEdge::BottomLRight,
+            Reply::new(PhysicalSize::new(640, 480), |_| {}),
+        )));
+        round_trip(Event::MouseInput(MouseInput {
+            button: MouseButton::Right,
+            button_state: ButtonState::Pressed,
+            mouse_state,
+        }));
+        round_trip(Event::KeyInput(KeyInput {
+            key_code: KeyCode::new(VirtualKey::A, ScanCode(0x1e)),
+            key_state: KeyState::Released,
+            prev_pressed: true,
+        }));
+        round_trip(Event::ImeComposition(ImeComposition {
+            chars: "日本語".chars().collect(),
+            clauses: vec![ime::Clause {
+                range: 0..3,
+                target: true,
+            }],
+            cursor_position: 3,
+        }));
+        round_trip(Event::DropFiles(DropFiles {
+            paths: vec!["a.txt".into(), "dir/b.png".into()],
+            position: PhysicalPosition::new(5, 6),
+        }));
+        round_trip(Event::Quit);
+        let mut start = ImeStartComposition::new(Reply::new((0, 0).into(), |_| {}));
+        start.set_position((7, 8));
+        let json = serde_json::to_string(&Event::ImeStartComposition(start)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"ImeStartComposition":{"position":{"x":7,"y":8}}}"#
+        );
+        let Ok(Event::ImeStartComposition(de)) = serde_json::from_str(&json) else {
+            panic!()
+        };
+        assert_eq!(de.position(), PhysicalPosition::new(7, 8));
+    }
 }
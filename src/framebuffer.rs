@@ -0,0 +1,190 @@
+use crate::*;
+
+/// RGBA pixels of the client area of a window.
+///
+/// Pixels are stored from the top-left corner row by row with 4 bytes per pixel.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Framebuffer {
+    size: PhysicalSize<u32>,
+    data: Vec<u8>,
+}
+
+impl Framebuffer {
+    /// Creates a framebuffer filled with `color`.
+    #[inline]
+    pub fn new(size: impl Into<PhysicalSize<u32>>, color: [u8; 4]) -> Self {
+        let size = size.into();
+        Self {
+            size,
+            data: color.repeat((size.width * size.height) as usize),
+        }
+    }
+
+    /// Returns `None` when the length of `data` is not `width * height * 4`.
+    #[inline]
+    pub fn from_rgba(size: impl Into<PhysicalSize<u32>>, data: Vec<u8>) -> Option<Self> {
+        let size = size.into();
+        (data.len() == (size.width * size.height * 4) as usize).then_some(Self { size, data })
+    }
+
+    #[inline]
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[inline]
+    pub fn pixel(&self, position: impl Into<PhysicalPosition<u32>>) -> Option<[u8; 4]> {
+        let position = position.into();
+        if position.x >= self.size.width || position.y >= self.size.height {
+            return None;
+        }
+        let i = ((position.y * self.size.width + position.x) * 4) as usize;
+        self.data[i..i + 4].try_into().ok()
+    }
+
+    /// Fills `rect` with `color`. The part out of the framebuffer is ignored.
+    #[inline]
+    pub fn fill(&mut self, rect: PhysicalRect<i32>, color: [u8; 4]) {
+        let Some(rect) = self.clip(rect.position, rect.size) else { return };
+        for y in rect.position.y..rect.endpoint().y {
+            let begin = ((y as u32 * self.size.width + rect.position.x as u32) * 4) as usize;
+            let end = begin + rect.size.width as usize * 4;
+            self.data[begin..end]
+                .chunks_exact_mut(4)
+                .for_each(|p| p.copy_from_slice(&color));
+        }
+    }
+
+    /// Copies `src` to `position`. The part out of the framebuffer is ignored.
+    #[inline]
+    pub fn blit(&mut self, position: impl Into<PhysicalPosition<i32>>, src: &Framebuffer) {
+        let position = position.into();
+        let src_size = PhysicalSize::new(src.size.width as i32, src.size.height as i32);
+        let Some(rect) = self.clip(position, src_size) else { return };
+        let src_x = (rect.position.x - position.x) as usize;
+        for y in rect.position.y..rect.endpoint().y {
+            let src_y = (y - position.y) as usize;
+            let src_begin = (src_y * src.size.width as usize + src_x) * 4;
+            let len = rect.size.width as usize * 4;
+            let begin = ((y as u32 * self.size.width + rect.position.x as u32) * 4) as usize;
+            self.data[begin..begin + len].copy_from_slice(&src.data[src_begin..src_begin + len]);
+        }
+    }
+
+    /// Changes the size keeping the pixels at the top-left corner and fills new pixels with
+    /// `color`.
+    pub(crate) fn resize(&mut self, size: PhysicalSize<u32>, color: [u8; 4]) {
+        if self.size == size {
+            return;
+        }
+        let mut resized = Self::new(size, color);
+        resized.blit((0, 0), self);
+        *self = resized;
+    }
+
+    fn clip(
+        &self,
+        position: PhysicalPosition<i32>,
+        size: PhysicalSize<i32>,
+    ) -> Option<PhysicalRect<i32>> {
+        let left = position.x.max(0);
+        let top = position.y.max(0);
+        let right = (position.x + size.width).min(self.size.width as i32);
+        let bottom = (position.y + size.height).min(self.size.height as i32);
+        (left < right && top < bottom)
+            .then(|| PhysicalRect::from_positions((left, top), (right, bottom)))
+    }
+
+    /// Decodes a PNG image and converts it to RGBA.
+    #[cfg(feature = "png")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "png")))]
+    pub fn read_png(reader: impl std::io::Read) -> Result<Self> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(png_error)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(png_error)?;
+        buf.truncate(info.buffer_size());
+        let data = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 0xff])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            _ => buf.iter().flat_map(|&p| [p, p, p, 0xff]).collect(),
+        };
+        Ok(Self {
+            size: PhysicalSize::new(info.width, info.height),
+            data,
+        })
+    }
+
+    /// Encodes the pixels as an RGBA PNG image.
+    #[cfg(feature = "png")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "png")))]
+    pub fn write_png(&self, writer: impl std::io::Write) -> Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.size.width, self.size.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(&self.data).map_err(png_error)?;
+        writer.finish().map_err(png_error)
+    }
+}
+
+#[cfg(feature = "png")]
+fn png_error(e: impl std::error::Error + Send + Sync + 'static) -> Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e).into()
+}
+
+/// The result of `Window::capture`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Capture {
+    pub framebuffer: Framebuffer,
+    /// The rect of the last `Event::Draw`.
+    pub invalid_rect: Option<PhysicalRect<i32>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [0xff, 0, 0, 0xff];
+    const BLUE: [u8; 4] = [0, 0, 0xff, 0xff];
+
+    #[test]
+    fn fill_blit_and_resize() {
+        let mut fb = Framebuffer::new((4, 3), [0; 4]);
+        fb.fill(PhysicalRect::new((-1, -1), (3, 2)), RED);
+        assert_eq!(fb.pixel((1, 0)), Some(RED));
+        assert_eq!(fb.pixel((2, 0)), Some([0; 4]));
+        assert_eq!(fb.pixel((0, 1)), Some([0; 4]));
+        let src = Framebuffer::new((2, 2), BLUE);
+        fb.blit((3, 2), &src);
+        assert_eq!(fb.pixel((3, 2)), Some(BLUE));
+        assert_eq!(fb.pixel((2, 2)), Some([0; 4]));
+        fb.resize(PhysicalSize::new(2, 2), BLUE);
+        assert_eq!(fb.data(), [RED, RED, [0; 4], [0; 4]].concat());
+        assert_eq!(fb.pixel((2, 0)), None);
+        assert!(Framebuffer::from_rgba((2, 2), vec![0; 15]).is_none());
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_round_trip() {
+        let mut fb = Framebuffer::new((3, 2), BLUE);
+        fb.fill(PhysicalRect::new((1, 0), (1, 2)), RED);
+        let mut buf = vec![];
+        fb.write_png(&mut buf).unwrap();
+        assert_eq!(Framebuffer::read_png(buf.as_slice()).unwrap(), fb);
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(windows)]
 use windows::Win32::Foundation::{POINT, RECT};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -102,6 +103,13 @@ where
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize",
+        deserialize = "T: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Rect<T, Coord> {
     pub position: Position<T, Coord>,
     pub size: Size<T, Coord>,
@@ -360,6 +368,7 @@ where
     }
 }
 
+#[cfg(windows)]
 impl From<POINT> for PhysicalPosition<i32> {
     #[inline]
     fn from(src: POINT) -> Self {
@@ -367,6 +376,7 @@ impl From<POINT> for PhysicalPosition<i32> {
     }
 }
 
+#[cfg(windows)]
 impl From<RECT> for PhysicalRect<i32> {
     #[inline]
     fn from(src: RECT) -> Self {
@@ -374,6 +384,7 @@ impl From<RECT> for PhysicalRect<i32> {
     }
 }
 
+#[cfg(windows)]
 impl From<PhysicalRect<i32>> for RECT {
     #[inline]
     fn from(src: PhysicalRect<i32>) -> Self {
@@ -0,0 +1,1995 @@
+//! A backend without OS windows.
+//!
+//! The headless backend is used after `backend::set_backend(headless::Headless)`. Windows created
+//! by `Window::builder()` are managed by an in-process window manager which emits the same event
+//! sequences as the Win32 backend. [`WindowManager`] plays the role of the user and the OS, and
+//! [`Simulator`] generates mouse and keyboard input and drives a simulated Japanese IME. The
+//! screen is a layout of [`Monitor`]s and a window takes the DPI of the monitor which it overlaps
+//! most.
+
+use crate::backend::{BoxFuture, WindowParams};
+use crate::kana::{to_katakana, Romaji};
+use crate::window::WindowId;
+use crate::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+const DEFAULT_SCREEN_SIZE: PhysicalSize<i32> = PhysicalSize::new(1920, 1080);
+const MINIMIZED_POSITION: ScreenPosition = ScreenPosition::new(-32000, -32000);
+const BACKGROUND_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+/// A simulated monitor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Monitor {
+    pub rect: Rect<i32, Screen>,
+    /// The area which maximized windows fill.
+    pub work_area: Rect<i32, Screen>,
+    pub dpi: u32,
+}
+
+impl Monitor {
+    #[inline]
+    pub fn new(rect: Rect<i32, Screen>, work_area: Rect<i32, Screen>, dpi: u32) -> Self {
+        Self {
+            rect,
+            work_area,
+            dpi,
+        }
+    }
+
+    fn overlap(&self, rect: &Rect<i32, Screen>) -> i64 {
+        let lt = self.rect.position;
+        let rb = self.rect.endpoint();
+        let width = rb.x.min(rect.endpoint().x) - lt.x.max(rect.position.x);
+        let height = rb.y.min(rect.endpoint().y) - lt.y.max(rect.position.y);
+        if width <= 0 || height <= 0 {
+            return 0;
+        }
+        width as i64 * height as i64
+    }
+
+    fn distance(&self, pt: ScreenPosition) -> i64 {
+        let lt = self.rect.position;
+        let rb = self.rect.endpoint();
+        let dx = (lt.x - pt.x).max(pt.x - rb.x).max(0) as i64;
+        let dy = (lt.y - pt.y).max(pt.y - rb.y).max(0) as i64;
+        dx * dx + dy * dy
+    }
+}
+
+impl Default for Monitor {
+    #[inline]
+    fn default() -> Self {
+        let rect = Rect::new(
+            (0, 0),
+            (DEFAULT_SCREEN_SIZE.width, DEFAULT_SCREEN_SIZE.height),
+        );
+        Self::new(rect, rect, DEFAULT_DPI)
+    }
+}
+
+struct WindowState {
+    id: WindowId,
+    title: String,
+    position: ScreenPosition,
+    size: PhysicalSize<u32>,
+    dpi: u32,
+    visible: bool,
+    minimized: bool,
+    maximized: bool,
+    normal_position: ScreenPosition,
+    normal_size: PhysicalSize<u32>,
+    cursor: Cursor,
+    icon: Option<Icon>,
+    enable_ime: bool,
+    visible_ime_candidate_window: bool,
+    accept_drop_files: bool,
+    framebuffer: Framebuffer,
+    invalid_rect: Option<PhysicalRect<i32>>,
+}
+
+impl WindowState {
+    fn client_rect(&self) -> PhysicalRect<i32> {
+        PhysicalRect::new((0, 0), (self.size.width as i32, self.size.height as i32))
+    }
+
+    fn screen_rect(&self) -> Rect<i32, Screen> {
+        Rect::new(
+            self.position,
+            (self.size.width as i32, self.size.height as i32),
+        )
+    }
+
+    fn is_drawable(&self) -> bool {
+        self.visible && !self.minimized
+    }
+
+    // The framebuffer follows the size of the client area lazily.
+    fn framebuffer(&mut self) -> &mut Framebuffer {
+        self.framebuffer.resize(self.size, BACKGROUND_COLOR);
+        &mut self.framebuffer
+    }
+}
+
+struct Manager {
+    windows: Vec<WindowState>,
+    focused: Option<WindowId>,
+    next_id: isize,
+    monitors: Vec<Monitor>,
+}
+
+static MANAGER: once_cell::sync::Lazy<Mutex<Manager>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(Manager::new()));
+
+impl Manager {
+    fn new() -> Self {
+        Self {
+            windows: vec![],
+            focused: None,
+            next_id: 1,
+            monitors: vec![Monitor::default()],
+        }
+    }
+
+    // Like `MonitorFromPoint` with `MONITOR_DEFAULTTONEAREST`.
+    fn monitor_from_point(&self, pt: ScreenPosition) -> Monitor {
+        self.monitors
+            .iter()
+            .min_by_key(|m| m.distance(pt))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // Like `MonitorFromWindow` with `MONITOR_DEFAULTTONEAREST`.
+    fn monitor_from_rect(&self, rect: Rect<i32, Screen>) -> Monitor {
+        let monitor = self
+            .monitors
+            .iter()
+            .filter(|m| m.overlap(&rect) > 0)
+            .max_by_key(|m| m.overlap(&rect));
+        match monitor {
+            Some(monitor) => *monitor,
+            None => self.monitor_from_point(rect.position),
+        }
+    }
+
+    fn get_mut(&mut self, id: WindowId) -> Option<&mut WindowState> {
+        self.windows.iter_mut().find(|w| w.id == id)
+    }
+
+    fn get(&self, id: WindowId) -> Option<&WindowState> {
+        self.windows.iter().find(|w| w.id == id)
+    }
+
+    fn create_window(&mut self, params: WindowParams) -> (WindowId, EventReceiver) {
+        let id = WindowId(self.next_id);
+        self.next_id += 1;
+        let dpi = self.monitor_from_point(params.position).dpi;
+        let size = (params.size)(dpi);
+        self.windows.push(WindowState {
+            id,
+            title: params.title,
+            position: params.position,
+            size,
+            dpi,
+            visible: false,
+            minimized: false,
+            maximized: false,
+            normal_position: params.position,
+            normal_size: size,
+            cursor: Cursor::default(),
+            icon: params.icon,
+            enable_ime: params.enable_ime,
+            visible_ime_candidate_window: params.visible_ime_candidate_window,
+            accept_drop_files: params.accept_drop_files,
+            framebuffer: Framebuffer::new(size, BACKGROUND_COLOR),
+            invalid_rect: None,
+        });
+        let rx = Context::register_window(
+            id,
+            params.enable_raw_input,
+            params.queue_policy,
+            params.event_mask,
+        );
+        if params.visibility {
+            self.show(id);
+        }
+        (id, rx)
+    }
+
+    fn activate(&mut self, id: WindowId) {
+        if self.focused == Some(id) {
+            return;
+        }
+        if let Some(prev) = self.focused.take() {
+            Context::send_event(prev, Event::Inactivated);
+        }
+        self.focused = Some(id);
+        Context::send_event(id, Event::Activated);
+    }
+
+    fn deactivate(&mut self, id: WindowId) {
+        if self.focused != Some(id) {
+            return;
+        }
+        self.focused = None;
+        Context::send_event(id, Event::Inactivated);
+        let next = self
+            .windows
+            .iter()
+            .rev()
+            .find(|w| w.id != id && w.is_drawable())
+            .map(|w| w.id);
+        if let Some(next) = next {
+            self.activate(next);
+        }
+    }
+
+    fn draw(&mut self, id: WindowId, invalid_rect: Option<PhysicalRect<i32>>) {
+        let Some(w) = self.get_mut(id) else { return };
+        if !w.is_drawable() {
+            return;
+        }
+        let invalid_rect = invalid_rect.unwrap_or_else(|| w.client_rect());
+        w.invalid_rect = Some(invalid_rect);
+        Context::send_event(id, Event::Draw(events::Draw { invalid_rect }));
+    }
+
+    fn set_position(&mut self, id: WindowId, position: ScreenPosition) {
+        let Some(w) = self.get_mut(id) else { return };
+        if w.position == position {
+            return;
+        }
+        w.position = position;
+        Context::send_event(id, Event::Moved(events::Moved { position }));
+    }
+
+    // Applies the DPI of a new monitor like `WM_GETDPISCALEDSIZE` and `WM_DPICHANGED`. The
+    // suggested rect keeps the position and scales the client area by the ratio of the DPIs.
+    fn set_dpi(&mut self, id: WindowId, dpi: u32) {
+        let Some(w) = self.get_mut(id) else { return };
+        if w.dpi == dpi {
+            return;
+        }
+        w.size = PhysicalSize::new(w.size.width * dpi / w.dpi, w.size.height * dpi / w.dpi);
+        w.dpi = dpi;
+        Context::send_event(id, Event::DpiChanged(events::DpiChanged { new_dpi: dpi }));
+        self.draw(id, None);
+    }
+
+    fn update_dpi(&mut self, id: WindowId) {
+        let Some(w) = self.get(id) else { return };
+        if w.minimized {
+            return;
+        }
+        let dpi = self.monitor_from_rect(w.screen_rect()).dpi;
+        self.set_dpi(id, dpi);
+    }
+
+    fn show(&mut self, id: WindowId) {
+        let Some(w) = self.get_mut(id) else { return };
+        if w.visible {
+            return;
+        }
+        w.visible = true;
+        self.activate(id);
+        self.draw(id, None);
+    }
+
+    fn hide(&mut self, id: WindowId) {
+        let Some(w) = self.get_mut(id) else { return };
+        if !w.visible {
+            return;
+        }
+        w.visible = false;
+        self.deactivate(id);
+    }
+
+    fn minimize(&mut self, id: WindowId) {
+        let Some(w) = self.get_mut(id) else { return };
+        if w.minimized {
+            return;
+        }
+        if !w.maximized {
+            w.normal_position = w.position;
+            w.normal_size = w.size;
+        }
+        w.visible = true;
+        w.minimized = true;
+        self.set_position(id, MINIMIZED_POSITION);
+        Context::send_event(id, Event::Minimized);
+        self.deactivate(id);
+    }
+
+    fn maximize(&mut self, id: WindowId) {
+        let Some(w) = self.get(id) else { return };
+        let rect = if w.minimized {
+            Rect::new(
+                w.normal_position,
+                (w.normal_size.width as i32, w.normal_size.height as i32),
+            )
+        } else {
+            w.screen_rect()
+        };
+        let work_area = self.monitor_from_rect(rect).work_area;
+        let Some(w) = self.get_mut(id) else { return };
+        if w.maximized && !w.minimized {
+            return;
+        }
+        if !w.maximized && !w.minimized {
+            w.normal_position = w.position;
+            w.normal_size = w.size;
+        }
+        w.visible = true;
+        w.minimized = false;
+        w.maximized = true;
+        let size = PhysicalSize::new(work_area.size.width as u32, work_area.size.height as u32);
+        w.size = size;
+        self.activate(id);
+        self.set_position(id, work_area.position);
+        Context::send_event(id, Event::Maximized(events::Maximized { size }));
+        self.draw(id, None);
+    }
+
+    fn restore(&mut self, id: WindowId) {
+        let Some(w) = self.get_mut(id) else { return };
+        if w.minimized && w.maximized {
+            self.maximize(id);
+            return;
+        }
+        if !w.minimized && !w.maximized {
+            self.show(id);
+            self.activate(id);
+            return;
+        }
+        let position = w.normal_position;
+        let size = w.normal_size;
+        w.visible = true;
+        w.minimized = false;
+        w.maximized = false;
+        w.size = size;
+        self.activate(id);
+        self.set_position(id, position);
+        Context::send_event(id, Event::Restored(events::Restored { size }));
+        self.draw(id, None);
+    }
+
+    fn close(&mut self, id: WindowId) {
+        self.deactivate(id);
+        self.windows.retain(|w| w.id != id);
+        Context::close_window(id);
+    }
+}
+
+/// The headless backend. Pass it to `backend::set_backend` to use it in place of the default.
+pub struct Headless;
+
+impl backend::Backend for Headless {
+    fn create_window(&self, params: WindowParams) -> BoxFuture<Result<(WindowId, EventReceiver)>> {
+        let ret = MANAGER.lock().unwrap().create_window(params);
+        Box::pin(std::future::ready(Ok(ret)))
+    }
+
+    fn position(&self, id: WindowId) -> BoxFuture<Option<ScreenPosition>> {
+        let position = MANAGER.lock().unwrap().get(id).map(|w| w.position);
+        Box::pin(std::future::ready(position))
+    }
+
+    fn inner_size(&self, id: WindowId) -> BoxFuture<Option<PhysicalSize<u32>>> {
+        let size = MANAGER.lock().unwrap().get(id).map(|w| w.size);
+        Box::pin(std::future::ready(size))
+    }
+
+    fn dpi(&self, id: WindowId) -> BoxFuture<Option<u32>> {
+        let dpi = MANAGER.lock().unwrap().get(id).map(|w| w.dpi);
+        Box::pin(std::future::ready(dpi))
+    }
+
+    fn show(&self, id: WindowId) {
+        MANAGER.lock().unwrap().show(id);
+    }
+
+    fn hide(&self, id: WindowId) {
+        MANAGER.lock().unwrap().hide(id);
+    }
+
+    fn minimize(&self, id: WindowId) {
+        MANAGER.lock().unwrap().minimize(id);
+    }
+
+    fn maximize(&self, id: WindowId) {
+        MANAGER.lock().unwrap().maximize(id);
+    }
+
+    fn restore(&self, id: WindowId) {
+        MANAGER.lock().unwrap().restore(id);
+    }
+
+    fn accept_drop_files(&self, id: WindowId, accept: bool) {
+        if let Some(w) = MANAGER.lock().unwrap().get_mut(id) {
+            w.accept_drop_files = accept;
+        }
+    }
+
+    fn ime(&self, id: WindowId, enable: bool) {
+        if let Some(w) = MANAGER.lock().unwrap().get_mut(id) {
+            w.enable_ime = enable;
+        }
+    }
+
+    fn set_cursor(&self, id: WindowId, cursor: Cursor) {
+        if let Some(w) = MANAGER.lock().unwrap().get_mut(id) {
+            w.cursor = cursor;
+        }
+    }
+
+    fn destroy(&self, id: WindowId) {
+        MANAGER.lock().unwrap().close(id);
+    }
+
+    fn redraw(&self, id: WindowId, invalid_rect: Option<PhysicalRect<i32>>) {
+        MANAGER.lock().unwrap().draw(id, invalid_rect);
+    }
+
+    fn present(&self, id: WindowId, position: PhysicalPosition<i32>, image: &Framebuffer) {
+        if let Some(w) = MANAGER.lock().unwrap().get_mut(id) {
+            w.framebuffer().blit(position, image);
+        }
+    }
+
+    fn capture(&self, id: WindowId) -> BoxFuture<Option<Capture>> {
+        let capture = MANAGER.lock().unwrap().get_mut(id).map(|w| Capture {
+            framebuffer: w.framebuffer().clone(),
+            invalid_rect: w.invalid_rect,
+        });
+        Box::pin(std::future::ready(capture))
+    }
+}
+
+/// Drives the windows of the headless backend as a user and the OS would.
+pub struct WindowManager;
+
+impl WindowManager {
+    /// Moves the window as if the user dragged its title bar.
+    ///
+    /// When the window comes to overlap another monitor most, `Event::DpiChanged` is sent and
+    /// the client area is scaled to the DPI of the monitor.
+    #[inline]
+    pub fn move_window(window: &Window, position: impl Into<ScreenPosition>) {
+        let id = window.id();
+        let mut m = MANAGER.lock().unwrap();
+        let Some(w) = m.get(id) else { return };
+        if w.minimized {
+            return;
+        }
+        m.set_position(id, position.into());
+        m.update_dpi(id);
+        let Some(w) = m.get(id) else { return };
+        let size = w.size;
+        Context::send_event(id, Event::Resized(events::Resized { size }));
+    }
+
+    /// Resizes the client area as if the user dragged the bottom-right corner of the window.
+    ///
+    /// The window takes the DPI of the monitor which it overlaps most after resizing. The answer
+    /// of `Resizing` is not waited for.
+    #[inline]
+    pub fn resize(window: &Window, size: impl Into<PhysicalSize<u32>>) {
+        let id = window.id();
+        let size = size.into();
+        let mut m = MANAGER.lock().unwrap();
+        let Some(w) = m.get_mut(id) else { return };
+        w.size = size;
+        w.maximized = false;
+        Context::send_event(
+            id,
+            Event::Resizing(events::Resizing::new(
+                ResizingEdge::BottomLRight,
+                Reply::new(size, |_| {}),
+            )),
+        );
+        m.update_dpi(id);
+        let Some(w) = m.get(id) else { return };
+        let size = w.size;
+        Context::send_event(id, Event::Resized(events::Resized { size }));
+        m.draw(id, None);
+    }
+
+    /// Changes the DPI of the window as if it was moved to a monitor with another scale.
+    ///
+    /// The client area is scaled by the ratio between the new and the old DPI.
+    #[inline]
+    pub fn set_dpi(window: &Window, dpi: u32) {
+        MANAGER.lock().unwrap().set_dpi(window.id(), dpi);
+    }
+
+    /// Gives the keyboard focus to the window as if the user clicked it.
+    #[inline]
+    pub fn focus(window: &Window) {
+        let id = window.id();
+        let mut m = MANAGER.lock().unwrap();
+        if m.get(id).is_some_and(|w| w.is_drawable()) {
+            m.activate(id);
+        }
+    }
+
+    /// Replaces the monitors with one monitor of `rect` at the default DPI.
+    #[inline]
+    pub fn set_screen(rect: Rect<i32, Screen>) {
+        Self::set_monitors([Monitor::new(rect, rect, DEFAULT_DPI)]);
+    }
+
+    /// Replaces the monitor layout.
+    ///
+    /// Existing windows are not moved, and take the DPI of their monitor when they are moved or
+    /// resized next time. An empty layout is replaced with `Monitor::default()`.
+    #[inline]
+    pub fn set_monitors(monitors: impl IntoIterator<Item = Monitor>) {
+        let mut monitors = monitors.into_iter().collect::<Vec<_>>();
+        if monitors.is_empty() {
+            monitors.push(Monitor::default());
+        }
+        MANAGER.lock().unwrap().monitors = monitors;
+    }
+
+    #[inline]
+    pub fn monitors() -> Vec<Monitor> {
+        MANAGER.lock().unwrap().monitors.clone()
+    }
+
+    /// Returns the monitor which the window overlaps most.
+    #[inline]
+    pub fn monitor(window: &Window) -> Option<Monitor> {
+        let m = MANAGER.lock().unwrap();
+        let w = m.get(window.id())?;
+        Some(m.monitor_from_rect(w.screen_rect()))
+    }
+
+    #[inline]
+    pub fn title(window: &Window) -> Option<String> {
+        MANAGER
+            .lock()
+            .unwrap()
+            .get(window.id())
+            .map(|w| w.title.clone())
+    }
+
+    #[inline]
+    pub fn is_visible(window: &Window) -> bool {
+        Self::property(window, |w| w.visible)
+    }
+
+    #[inline]
+    pub fn is_minimized(window: &Window) -> bool {
+        Self::property(window, |w| w.minimized)
+    }
+
+    #[inline]
+    pub fn is_maximized(window: &Window) -> bool {
+        Self::property(window, |w| w.maximized && !w.minimized)
+    }
+
+    #[inline]
+    pub fn is_focused(window: &Window) -> bool {
+        MANAGER.lock().unwrap().focused == Some(window.id())
+    }
+
+    #[inline]
+    pub fn is_ime_enabled(window: &Window) -> bool {
+        Self::property(window, |w| w.enable_ime)
+    }
+
+    #[inline]
+    pub fn is_ime_candidate_window_visible(window: &Window) -> bool {
+        Self::property(window, |w| w.visible_ime_candidate_window)
+    }
+
+    #[inline]
+    pub fn accepts_drop_files(window: &Window) -> bool {
+        Self::property(window, |w| w.accept_drop_files)
+    }
+
+    #[inline]
+    pub fn cursor(window: &Window) -> Option<Cursor> {
+        MANAGER.lock().unwrap().get(window.id()).map(|w| w.cursor)
+    }
+
+    #[inline]
+    pub fn icon(window: &Window) -> Option<Icon> {
+        MANAGER
+            .lock()
+            .unwrap()
+            .get(window.id())
+            .and_then(|w| w.icon.clone())
+    }
+
+    fn property(window: &Window, f: impl FnOnce(&WindowState) -> bool) -> bool {
+        MANAGER.lock().unwrap().get(window.id()).is_some_and(f)
+    }
+}
+
+const VK_PACKET: u32 = 0xe7;
+const VK_PROCESSKEY: u32 = 0xe5;
+const DRAG_STEPS: i32 = 4;
+
+const LETTER_KEYS: [VirtualKey; 26] = [
+    VirtualKey::A,
+    VirtualKey::B,
+    VirtualKey::C,
+    VirtualKey::D,
+    VirtualKey::E,
+    VirtualKey::F,
+    VirtualKey::G,
+    VirtualKey::H,
+    VirtualKey::I,
+    VirtualKey::J,
+    VirtualKey::K,
+    VirtualKey::L,
+    VirtualKey::M,
+    VirtualKey::N,
+    VirtualKey::O,
+    VirtualKey::P,
+    VirtualKey::Q,
+    VirtualKey::R,
+    VirtualKey::S,
+    VirtualKey::T,
+    VirtualKey::U,
+    VirtualKey::V,
+    VirtualKey::W,
+    VirtualKey::X,
+    VirtualKey::Y,
+    VirtualKey::Z,
+];
+
+const DIGIT_KEYS: [VirtualKey; 10] = [
+    VirtualKey::Key0,
+    VirtualKey::Key1,
+    VirtualKey::Key2,
+    VirtualKey::Key3,
+    VirtualKey::Key4,
+    VirtualKey::Key5,
+    VirtualKey::Key6,
+    VirtualKey::Key7,
+    VirtualKey::Key8,
+    VirtualKey::Key9,
+];
+
+// Scan codes of the set 1 for a US keyboard layout.
+fn scan_code(vkey: VirtualKey) -> ScanCode {
+    const ROW_Q: &[VirtualKey] = &[
+        VirtualKey::Q,
+        VirtualKey::W,
+        VirtualKey::E,
+        VirtualKey::R,
+        VirtualKey::T,
+        VirtualKey::Y,
+        VirtualKey::U,
+        VirtualKey::I,
+        VirtualKey::O,
+        VirtualKey::P,
+    ];
+    const ROW_A: &[VirtualKey] = &[
+        VirtualKey::A,
+        VirtualKey::S,
+        VirtualKey::D,
+        VirtualKey::F,
+        VirtualKey::G,
+        VirtualKey::H,
+        VirtualKey::J,
+        VirtualKey::K,
+        VirtualKey::L,
+    ];
+    const ROW_Z: &[VirtualKey] = &[
+        VirtualKey::Z,
+        VirtualKey::X,
+        VirtualKey::C,
+        VirtualKey::V,
+        VirtualKey::B,
+        VirtualKey::N,
+        VirtualKey::M,
+    ];
+    let row = |row: &[VirtualKey], base: u32| {
+        row.iter()
+            .position(|k| *k == vkey)
+            .map(|i| ScanCode(base + i as u32))
+    };
+    if let Some(code) = row(ROW_Q, 0x10)
+        .or_else(|| row(ROW_A, 0x1e))
+        .or_else(|| row(ROW_Z, 0x2c))
+    {
+        return code;
+    }
+    let code = match vkey {
+        VirtualKey::Esc => 0x01,
+        VirtualKey::Key0 => 0x0b,
+        VirtualKey::Key1 => 0x02,
+        VirtualKey::Key2 => 0x03,
+        VirtualKey::Key3 => 0x04,
+        VirtualKey::Key4 => 0x05,
+        VirtualKey::Key5 => 0x06,
+        VirtualKey::Key6 => 0x07,
+        VirtualKey::Key7 => 0x08,
+        VirtualKey::Key8 => 0x09,
+        VirtualKey::Key9 => 0x0a,
+        VirtualKey::BackSpace => 0x0e,
+        VirtualKey::Tab => 0x0f,
+        VirtualKey::Enter => 0x1c,
+        VirtualKey::Ctrl => 0x1d,
+        VirtualKey::Shift => 0x2a,
+        VirtualKey::Alt => 0x38,
+        VirtualKey::Space => 0x39,
+        VirtualKey::CapsLock => 0x3a,
+        _ => 0,
+    };
+    ScanCode(code)
+}
+
+fn key_from_char(c: char) -> Option<(VirtualKey, bool)> {
+    match c {
+        'a'..='z' => Some((LETTER_KEYS[c as usize - 'a' as usize], false)),
+        'A'..='Z' => Some((LETTER_KEYS[c as usize - 'A' as usize], true)),
+        '0'..='9' => Some((DIGIT_KEYS[c as usize - '0' as usize], false)),
+        ' ' => Some((VirtualKey::Space, false)),
+        '\t' => Some((VirtualKey::Tab, false)),
+        '\r' => Some((VirtualKey::Enter, false)),
+        _ => None,
+    }
+}
+
+/// Generates mouse and keyboard input for windows of the headless backend.
+///
+/// Mouse input is sent to the window under the simulated cursor and keyboard input is sent to
+/// the focused window. The event sequences follow the Win32 backend: the first cursor movement
+/// over a window is reported as `CursorEntered`, and every key press which produces a character
+/// is followed by `CharInput`.
+#[derive(Debug, Default)]
+pub struct Simulator {
+    cursor: Option<(WindowId, PhysicalPosition<i32>)>,
+    buttons: MouseButtons,
+    keys: Vec<VirtualKey>,
+    ime: Option<ImeSession>,
+    ime_dictionary: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug)]
+struct ImeClause {
+    candidates: Vec<String>,
+    selected: usize,
+}
+
+impl ImeClause {
+    fn new(reading: String, mut candidates: Vec<String>) -> Self {
+        let katakana = to_katakana(&reading);
+        for c in [reading, katakana] {
+            if !candidates.contains(&c) {
+                candidates.push(c);
+            }
+        }
+        Self {
+            candidates,
+            selected: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ImeSession {
+    id: WindowId,
+    romaji: Romaji,
+    // Not empty while the composition is converted.
+    clauses: Vec<ImeClause>,
+    target: usize,
+    reply: reply::Pending<PhysicalPosition<i32>>,
+    candidate_position: Option<PhysicalPosition<i32>>,
+}
+
+impl ImeSession {
+    fn text(&self) -> String {
+        if self.clauses.is_empty() {
+            return self.romaji.text();
+        }
+        self.clauses
+            .iter()
+            .map(|c| c.candidates[c.selected].as_str())
+            .collect()
+    }
+
+    fn composition(&self) -> events::ImeComposition {
+        let chars = self.text().chars().collect::<Vec<_>>();
+        if self.clauses.is_empty() {
+            return events::ImeComposition {
+                clauses: vec![ime::Clause {
+                    range: 0..chars.len(),
+                    target: false,
+                }],
+                cursor_position: chars.len(),
+                chars,
+            };
+        }
+        let mut clauses = vec![];
+        let mut cursor_position = 0;
+        for (i, c) in self.clauses.iter().enumerate() {
+            let begin = clauses.last().map_or(0, |c: &ime::Clause| c.range.end);
+            let end = begin + c.candidates[c.selected].chars().count();
+            if i == self.target {
+                cursor_position = end;
+            }
+            clauses.push(ime::Clause {
+                range: begin..end,
+                target: i == self.target,
+            });
+        }
+        events::ImeComposition {
+            chars,
+            clauses,
+            cursor_position,
+        }
+    }
+
+    // Splits the reading into the longest readings in `dictionary` from the beginning.
+    fn convert(&mut self, dictionary: &HashMap<String, Vec<String>>) {
+        self.romaji.flush();
+        let chars = self.romaji.text().chars().collect::<Vec<_>>();
+        let mut unknown = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let found = (i + 1..=chars.len()).rev().find_map(|j| {
+                let reading = chars[i..j].iter().collect::<String>();
+                dictionary.get(&reading).map(|c| (j, reading, c.clone()))
+            });
+            let Some((j, reading, candidates)) = found else {
+                unknown.push(chars[i]);
+                i += 1;
+                continue;
+            };
+            if !unknown.is_empty() {
+                self.clauses
+                    .push(ImeClause::new(std::mem::take(&mut unknown), vec![]));
+            }
+            self.clauses.push(ImeClause::new(reading, candidates));
+            i = j;
+        }
+        if !unknown.is_empty() {
+            self.clauses.push(ImeClause::new(unknown, vec![]));
+        }
+        self.target = 0;
+    }
+}
+
+impl Simulator {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the cursor to `position` in the client area of `window`.
+    ///
+    /// When the cursor comes from another window, `CursorLeft` is sent to that window first.
+    pub fn move_cursor(&mut self, window: &Window, position: impl Into<PhysicalPosition<i32>>) {
+        let id = window.id();
+        let position = position.into();
+        let mouse_state = MouseState {
+            position,
+            buttons: self.buttons,
+        };
+        match self.cursor {
+            Some((current, _)) if current == id => {
+                Context::send_event(id, Event::CursorMoved(events::CursorMoved { mouse_state }));
+            }
+            _ => {
+                self.leave();
+                Context::send_event(
+                    id,
+                    Event::CursorEntered(events::CursorEntered { mouse_state }),
+                );
+            }
+        }
+        self.cursor = Some((id, position));
+    }
+
+    /// Moves the cursor out of the window under it.
+    pub fn leave(&mut self) {
+        let Some((id, position)) = self.cursor.take() else { return };
+        Context::send_event(
+            id,
+            Event::CursorLeft(events::CursorLeft {
+                mouse_state: MouseState {
+                    position,
+                    buttons: self.buttons,
+                },
+            }),
+        );
+    }
+
+    /// Presses `button` at the cursor position.
+    pub fn button_down(&mut self, button: MouseButton) {
+        let Some((id, _)) = self.cursor else { return };
+        {
+            let mut m = MANAGER.lock().unwrap();
+            if !m.get(id).is_some_and(|w| w.is_drawable()) {
+                return;
+            }
+            m.activate(id);
+        }
+        self.buttons.insert(button);
+        self.send_mouse_input(id, button, ButtonState::Pressed);
+    }
+
+    /// Releases `button` at the cursor position.
+    pub fn button_up(&mut self, button: MouseButton) {
+        let Some((id, _)) = self.cursor else { return };
+        if !self.buttons.contains(button) {
+            return;
+        }
+        self.buttons.remove(button);
+        self.send_mouse_input(id, button, ButtonState::Released);
+    }
+
+    /// Moves the cursor to `position` in `window`, then presses and releases `button`.
+    ///
+    /// The window is activated by the press when it is not focused.
+    pub fn click(
+        &mut self,
+        window: &Window,
+        position: impl Into<PhysicalPosition<i32>>,
+        button: MouseButton,
+    ) {
+        self.move_cursor(window, position);
+        self.button_down(button);
+        self.button_up(button);
+    }
+
+    /// Drags with the left button from `from` to `to` in the window under the cursor.
+    pub fn drag(
+        &mut self,
+        from: impl Into<PhysicalPosition<i32>>,
+        to: impl Into<PhysicalPosition<i32>>,
+    ) {
+        let Some((id, _)) = self.cursor else { return };
+        let from = from.into();
+        let to = to.into();
+        self.move_to(id, from);
+        self.button_down(MouseButton::Left);
+        for i in 1..=DRAG_STEPS {
+            let position = PhysicalPosition::new(
+                from.x + (to.x - from.x) * i / DRAG_STEPS,
+                from.y + (to.y - from.y) * i / DRAG_STEPS,
+            );
+            self.move_to(id, position);
+        }
+        self.button_up(MouseButton::Left);
+    }
+
+    /// Rotates the mouse wheel at the cursor position.
+    ///
+    /// `distance` is in the same unit as `MouseWheel::distance`, where 120 is one notch.
+    pub fn scroll(&mut self, axis: MouseWheelAxis, distance: i32) {
+        let Some((id, position)) = self.cursor else { return };
+        Context::send_event(
+            id,
+            Event::MouseWheel(events::MouseWheel {
+                axis,
+                distance,
+                mouse_state: MouseState {
+                    position,
+                    buttons: self.buttons,
+                },
+            }),
+        );
+    }
+
+    /// Presses `vkey` on the focused window.
+    ///
+    /// Pressing a key which is already down is reported as an auto-repeat with `prev_pressed`.
+    pub fn key_down(&mut self, vkey: VirtualKey) {
+        let c = self.char_from_key(vkey);
+        self.key_down_with(vkey, c);
+    }
+
+    /// Releases `vkey` on the focused window.
+    pub fn key_up(&mut self, vkey: VirtualKey) {
+        let Some(i) = self.keys.iter().position(|k| *k == vkey) else { return };
+        self.keys.remove(i);
+        send_key_input(vkey, KeyState::Released, true);
+    }
+
+    /// Presses and releases `vkey` on the focused window.
+    #[inline]
+    pub fn press_key(&mut self, vkey: VirtualKey) {
+        self.key_down(vkey);
+        self.key_up(vkey);
+    }
+
+    /// Types `text` on the focused window.
+    ///
+    /// Only ASCII letters, digits, `' '`, `'\t'` and `'\n'` are typed with their keys, with
+    /// `Shift` for upper case letters. `'\n'` is typed with `Enter`, which gives `'\r'` as
+    /// `press_key(VirtualKey::Enter)` does. Any other character, including the symbols of a US
+    /// keyboard, is sent as `VK_PACKET` like `SendInput` does: its `KeyInput` carries
+    /// `VirtualKey::Other(0xe7)` rather than the key of the character, and only `CharInput` gives
+    /// the character.
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            let c = if c == '\n' { '\r' } else { c };
+            match key_from_char(c) {
+                Some((vkey, shift)) => {
+                    let press_shift = shift && !self.keys.contains(&VirtualKey::Shift);
+                    if press_shift {
+                        self.key_down_with(VirtualKey::Shift, None);
+                    }
+                    self.key_down_with(vkey, Some(c));
+                    self.key_up(vkey);
+                    if press_shift {
+                        self.key_up(VirtualKey::Shift);
+                    }
+                }
+                None => {
+                    let vkey = VirtualKey::Other(VK_PACKET);
+                    self.key_down_with(vkey, Some(c));
+                    self.key_up(vkey);
+                }
+            }
+        }
+    }
+
+    /// Returns the mouse buttons which are currently pressed.
+    #[inline]
+    pub fn buttons(&self) -> MouseButtons {
+        self.buttons
+    }
+
+    /// Registers the candidates of `reading` in hiragana for the simulated IME.
+    ///
+    /// The reading itself and its katakana are always candidates after the registered ones.
+    #[inline]
+    pub fn add_ime_candidates<T>(&mut self, reading: &str, candidates: impl IntoIterator<Item = T>)
+    where
+        T: Into<String>,
+    {
+        self.ime_dictionary.insert(
+            reading.into(),
+            candidates.into_iter().map(|c| c.into()).collect(),
+        );
+    }
+
+    /// Types `script` on the focused window through the simulated IME.
+    ///
+    /// Other characters than the following are typed as romaji: `' '` converts the composition
+    /// or selects the next candidate, `'\n'` commits, `'\x1b'` cancels and `'\x08'` deletes the
+    /// last character. Outside a composition these four are typed as `Space`, `Enter`, `Esc` and
+    /// `BackSpace`. When the IME of the window is disabled, `script` is typed by `type_text`.
+    pub fn ime_type(&mut self, script: &str) {
+        for c in script.chars() {
+            match c {
+                ' ' | '\n' if self.ime.is_none() => self.type_text(&c.to_string()),
+                '\x1b' if self.ime.is_none() => self.press_key(VirtualKey::Esc),
+                '\x08' if self.ime.is_none() => self.press_key(VirtualKey::BackSpace),
+                ' ' => self.ime_convert(),
+                '\n' => self.ime_commit(),
+                '\x1b' => self.ime_cancel(),
+                '\x08' => self.ime_backspace(),
+                _ => self.ime_input(c),
+            }
+        }
+    }
+
+    /// Converts the composition, or selects the next candidate of the target clause when it is
+    /// already converted.
+    pub fn ime_convert(&mut self) {
+        let Some(session) = self.ime.as_mut() else { return };
+        send_process_key();
+        if session.clauses.is_empty() {
+            session.convert(&self.ime_dictionary);
+        } else {
+            let clause = &mut session.clauses[session.target];
+            clause.selected = (clause.selected + 1) % clause.candidates.len();
+        }
+        Context::send_event(session.id, Event::ImeComposition(session.composition()));
+    }
+
+    /// Selects the candidate at `index` of the target clause.
+    pub fn ime_select_candidate(&mut self, index: usize) {
+        let Some(session) = self.ime.as_mut() else { return };
+        let Some(clause) = session.clauses.get_mut(session.target) else { return };
+        if index >= clause.candidates.len() {
+            return;
+        }
+        clause.selected = index;
+        Context::send_event(session.id, Event::ImeComposition(session.composition()));
+    }
+
+    /// Moves the target to the next clause like the right arrow key.
+    #[inline]
+    pub fn ime_next_clause(&mut self) {
+        self.move_ime_target(1);
+    }
+
+    /// Moves the target to the previous clause like the left arrow key.
+    #[inline]
+    pub fn ime_prev_clause(&mut self) {
+        self.move_ime_target(-1);
+    }
+
+    /// Commits the composition and sends `ImeEndComposition` with the result.
+    pub fn ime_commit(&mut self) {
+        let Some(mut session) = self.ime.take() else { return };
+        send_process_key();
+        session.romaji.flush();
+        let result = session.text();
+        Context::send_event(
+            session.id,
+            Event::ImeEndComposition(events::ImeEndComposition {
+                result: Some(result),
+            }),
+        );
+    }
+
+    /// Cancels the composition and sends `ImeEndComposition` without a result.
+    pub fn ime_cancel(&mut self) {
+        let Some(session) = self.ime.take() else { return };
+        send_process_key();
+        Context::send_event(
+            session.id,
+            Event::ImeEndComposition(events::ImeEndComposition { result: None }),
+        );
+    }
+
+    /// Deletes the last character, or reverts the conversion when the composition is converted.
+    ///
+    /// The composition is canceled when it becomes empty.
+    pub fn ime_backspace(&mut self) {
+        let Some(session) = self.ime.as_mut() else { return };
+        if session.clauses.is_empty() {
+            session.romaji.pop();
+            if session.romaji.is_empty() {
+                self.ime_cancel();
+                return;
+            }
+        } else {
+            session.clauses.clear();
+        }
+        send_process_key();
+        Context::send_event(session.id, Event::ImeComposition(session.composition()));
+    }
+
+    /// Returns the candidates of the target clause while the composition is converted.
+    #[inline]
+    pub fn ime_candidates(&self) -> Option<&[String]> {
+        let session = self.ime.as_ref()?;
+        let clause = session.clauses.get(session.target)?;
+        Some(&clause.candidates)
+    }
+
+    /// Returns the position which the window set by `ImeStartComposition::set_position`.
+    #[inline]
+    pub fn ime_candidate_position(&mut self) -> Option<PhysicalPosition<i32>> {
+        let session = self.ime.as_mut()?;
+        if let Some(position) = session.reply.try_get() {
+            session.candidate_position = Some(position);
+        }
+        session.candidate_position
+    }
+
+    fn ime_input(&mut self, c: char) {
+        let Some(id) = MANAGER.lock().unwrap().focused else { return };
+        let enabled = MANAGER
+            .lock()
+            .unwrap()
+            .get(id)
+            .is_some_and(|w| w.enable_ime);
+        if !enabled {
+            self.type_text(&c.to_string());
+            return;
+        }
+        if self
+            .ime
+            .as_ref()
+            .is_some_and(|s| s.id != id || !s.clauses.is_empty())
+        {
+            self.ime_commit();
+        }
+        send_process_key();
+        let session = self.ime.get_or_insert_with(|| {
+            let (reply, pending) = Reply::channel(PhysicalPosition::new(0, 0));
+            Context::send_event(
+                id,
+                Event::ImeStartComposition(events::ImeStartComposition::new(reply)),
+            );
+            ImeSession {
+                id,
+                romaji: Romaji::default(),
+                clauses: vec![],
+                target: 0,
+                reply: pending,
+                candidate_position: None,
+            }
+        });
+        session.romaji.push(c);
+        Context::send_event(id, Event::ImeComposition(session.composition()));
+    }
+
+    fn move_ime_target(&mut self, d: isize) {
+        let Some(session) = self.ime.as_mut() else { return };
+        if session.clauses.is_empty() {
+            return;
+        }
+        let target = session.target as isize + d;
+        if target < 0 || target >= session.clauses.len() as isize {
+            return;
+        }
+        session.target = target as usize;
+        send_process_key();
+        Context::send_event(session.id, Event::ImeComposition(session.composition()));
+    }
+
+    fn move_to(&mut self, id: WindowId, position: PhysicalPosition<i32>) {
+        self.cursor = Some((id, position));
+        Context::send_event(
+            id,
+            Event::CursorMoved(events::CursorMoved {
+                mouse_state: MouseState {
+                    position,
+                    buttons: self.buttons,
+                },
+            }),
+        );
+    }
+
+    fn send_mouse_input(&self, id: WindowId, button: MouseButton, button_state: ButtonState) {
+        let Some((_, position)) = self.cursor else { return };
+        Context::send_event(
+            id,
+            Event::MouseInput(events::MouseInput {
+                button,
+                button_state,
+                mouse_state: MouseState {
+                    position,
+                    buttons: self.buttons,
+                },
+            }),
+        );
+    }
+
+    fn key_down_with(&mut self, vkey: VirtualKey, c: Option<char>) {
+        let prev_pressed = self.keys.contains(&vkey);
+        if !prev_pressed {
+            self.keys.push(vkey);
+        }
+        send_key_input(vkey, KeyState::Pressed, prev_pressed);
+        if let Some(c) = c {
+            send_focused(Event::CharInput(events::CharInput { c }));
+        }
+    }
+
+    fn char_from_key(&self, vkey: VirtualKey) -> Option<char> {
+        if self.keys.contains(&VirtualKey::Alt) {
+            return None;
+        }
+        let ctrl = self.keys.contains(&VirtualKey::Ctrl);
+        let shift = self.keys.contains(&VirtualKey::Shift);
+        if let Some(i) = LETTER_KEYS.iter().position(|k| *k == vkey) {
+            let c = if ctrl {
+                1 + i as u8
+            } else if shift {
+                b'A' + i as u8
+            } else {
+                b'a' + i as u8
+            };
+            return Some(c as char);
+        }
+        if ctrl {
+            return None;
+        }
+        if !shift {
+            if let Some(i) = DIGIT_KEYS.iter().position(|k| *k == vkey) {
+                return Some((b'0' + i as u8) as char);
+            }
+        }
+        match vkey {
+            VirtualKey::Space => Some(' '),
+            VirtualKey::Tab => Some('\t'),
+            VirtualKey::Enter => Some('\r'),
+            VirtualKey::BackSpace => Some('\x08'),
+            VirtualKey::Esc => Some('\x1b'),
+            _ => None,
+        }
+    }
+}
+
+fn send_focused(event: Event) {
+    let Some(id) = MANAGER.lock().unwrap().focused else { return };
+    Context::send_event(id, event);
+}
+
+// Keys which the IME processes are reported as `VK_PROCESSKEY` like the Win32 backend.
+fn send_process_key() {
+    let vkey = VirtualKey::Other(VK_PROCESSKEY);
+    send_key_input(vkey, KeyState::Pressed, false);
+    send_key_input(vkey, KeyState::Released, true);
+}
+
+fn send_key_input(vkey: VirtualKey, key_state: KeyState, prev_pressed: bool) {
+    send_focused(Event::KeyInput(events::KeyInput {
+        key_code: KeyCode::new(vkey, scan_code(vkey)),
+        key_state,
+        prev_pressed,
+    }));
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // The window manager is process global, so tests which create windows must not overlap.
+    pub(crate) static LOCK: Mutex<()> = Mutex::new(());
+
+    /// Also makes the headless backend the backend of the test process.
+    pub(crate) fn lock() -> std::sync::MutexGuard<'static, ()> {
+        crate::backend::set_backend(Headless).unwrap_or(());
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn create_window(title: &str) -> (Window, EventReceiver) {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(
+            Window::builder()
+                .title(title)
+                .position((100, 100))
+                .inner_size(PhysicalSize::new(640, 480))
+                .build(),
+        )
+        .unwrap()
+    }
+
+    pub(crate) fn drain(rx: &mut EventReceiver) -> Vec<Event> {
+        let mut events = vec![];
+        while let Ok(Some(event)) = rx.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn create_and_close() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("create_and_close");
+        let events = drain(&mut rx);
+        assert!(matches!(events[0], Event::Activated));
+        assert!(matches!(
+            events[1],
+            Event::Draw(events::Draw { invalid_rect }) if invalid_rect == PhysicalRect::new((0, 0), (640, 480))
+        ));
+        assert!(WindowManager::is_focused(&window));
+        assert_eq!(WindowManager::title(&window).unwrap(), "create_and_close");
+        window.destroy();
+        let events = drain(&mut rx);
+        assert!(matches!(events[0], Event::Inactivated));
+        assert!(matches!(events[1], Event::Closed));
+        assert!(matches!(events[2], Event::Quit));
+        assert!(window.is_closed());
+    }
+
+    #[test]
+    fn focus_follows_closed_window() {
+        let _lock = lock();
+        let (a, mut rx_a) = create_window("a");
+        let (b, mut rx_b) = create_window("b");
+        let events = drain(&mut rx_a);
+        assert!(matches!(events.last().unwrap(), Event::Inactivated));
+        assert!(WindowManager::is_focused(&b));
+        b.destroy();
+        let events = drain(&mut rx_b);
+        assert!(matches!(events[events.len() - 2], Event::Inactivated));
+        assert!(matches!(events[events.len() - 1], Event::Closed));
+        let events = drain(&mut rx_a);
+        assert!(matches!(events[..], [Event::Activated]));
+        a.destroy();
+        let events = drain(&mut rx_a);
+        assert!(matches!(events.last().unwrap(), Event::Quit));
+    }
+
+    #[test]
+    fn minimize_maximize_restore() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("minimize_maximize_restore");
+        drain(&mut rx);
+        window.maximize();
+        let events = drain(&mut rx);
+        assert!(
+            matches!(events[0], Event::Moved(events::Moved { position }) if position == ScreenPosition::new(0, 0))
+        );
+        assert!(
+            matches!(events[1], Event::Maximized(events::Maximized { size }) if size == PhysicalSize::new(1920, 1080))
+        );
+        assert!(WindowManager::is_maximized(&window));
+        window.minimize();
+        let events = drain(&mut rx);
+        assert!(matches!(events[1], Event::Minimized));
+        assert!(matches!(events[2], Event::Inactivated));
+        window.restore();
+        let events = drain(&mut rx);
+        assert!(matches!(events[0], Event::Activated));
+        assert!(matches!(events[2], Event::Maximized(_)));
+        window.restore();
+        let events = drain(&mut rx);
+        assert!(
+            matches!(events[0], Event::Moved(events::Moved { position }) if position == ScreenPosition::new(100, 100))
+        );
+        assert!(
+            matches!(events[1], Event::Restored(events::Restored { size }) if size == PhysicalSize::new(640, 480))
+        );
+        window.close();
+    }
+
+    #[test]
+    fn move_resize_and_dpi() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("move_resize_and_dpi");
+        drain(&mut rx);
+        WindowManager::move_window(&window, (200, 300));
+        WindowManager::resize(&window, PhysicalSize::new(800, 600));
+        WindowManager::set_dpi(&window, DEFAULT_DPI * 2);
+        let events = drain(&mut rx);
+        assert!(matches!(events[0], Event::Moved(_)));
+        assert!(
+            matches!(events[1], Event::Resized(events::Resized { size }) if size == PhysicalSize::new(640, 480))
+        );
+        assert!(
+            matches!(&events[2], Event::Resizing(e) if e.size() == PhysicalSize::new(800, 600))
+        );
+        assert!(matches!(events[3], Event::Resized(_)));
+        assert!(matches!(events[4], Event::Draw(_)));
+        assert!(matches!(
+            events[5],
+            Event::DpiChanged(events::DpiChanged { new_dpi: 192 })
+        ));
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            assert_eq!(
+                window.position().await.unwrap(),
+                ScreenPosition::new(200, 300)
+            );
+            assert_eq!(
+                window.inner_size().await.unwrap(),
+                PhysicalSize::new(1600, 1200)
+            );
+            assert_eq!(window.dpi().await.unwrap(), 192);
+        });
+        window.close();
+    }
+
+    #[test]
+    fn move_across_monitors() {
+        let _lock = lock();
+        let right = Rect::new((1920, 0), (2880, 1620));
+        let right_work_area = Rect::new((1920, 0), (2880, 1560));
+        WindowManager::set_monitors([
+            Monitor::default(),
+            Monitor::new(right, right_work_area, 144),
+        ]);
+        let (window, mut rx) = create_window("move_across_monitors");
+        drain(&mut rx);
+        WindowManager::move_window(&window, (1800, 100));
+        let events = drain(&mut rx);
+        assert!(matches!(events[0], Event::Moved(_)));
+        assert!(matches!(
+            events[1],
+            Event::DpiChanged(events::DpiChanged { new_dpi: 144 })
+        ));
+        assert!(
+            matches!(events[2], Event::Draw(events::Draw { invalid_rect }) if invalid_rect == PhysicalRect::new((0, 0), (960, 720)))
+        );
+        assert!(
+            matches!(events[3], Event::Resized(events::Resized { size }) if size == PhysicalSize::new(960, 720))
+        );
+        assert_eq!(WindowManager::monitor(&window).unwrap().rect, right);
+        window.maximize();
+        let events = drain(&mut rx);
+        assert!(
+            matches!(events[0], Event::Moved(events::Moved { position }) if position == ScreenPosition::new(1920, 0))
+        );
+        assert!(
+            matches!(events[1], Event::Maximized(events::Maximized { size }) if size == PhysicalSize::new(2880, 1560))
+        );
+        window.restore();
+        drain(&mut rx);
+        WindowManager::resize(&window, PhysicalSize::new(1200, 720));
+        let events = drain(&mut rx);
+        assert!(!events.iter().any(|e| matches!(e, Event::DpiChanged(_))));
+        WindowManager::move_window(&window, (100, 100));
+        let events = drain(&mut rx);
+        assert!(matches!(
+            events[1],
+            Event::DpiChanged(events::DpiChanged { new_dpi: 96 })
+        ));
+        assert!(
+            matches!(events[3], Event::Resized(events::Resized { size }) if size == PhysicalSize::new(800, 480))
+        );
+        window.close();
+        WindowManager::set_monitors([Monitor::default()]);
+    }
+
+    #[test]
+    fn present_and_capture() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("present_and_capture");
+        drain(&mut rx);
+        window.redraw(Some(PhysicalRect::new((100, 50), (200, 100))));
+        let Some(Event::Draw(draw)) = drain(&mut rx).pop() else {
+            panic!()
+        };
+        let mut image = Framebuffer::new(draw.invalid_rect.size.cast::<u32>().unwrap(), [0; 4]);
+        image.fill(PhysicalRect::new((0, 0), (100, 100)), [0xff, 0, 0, 0xff]);
+        window.present(draw.invalid_rect.position, &image);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let capture = rt.block_on(window.capture()).unwrap();
+        assert_eq!(capture.invalid_rect, Some(draw.invalid_rect));
+        let fb = &capture.framebuffer;
+        assert_eq!(fb.size(), PhysicalSize::new(640, 480));
+        assert_eq!(fb.pixel((99, 50)), Some([0xff; 4]));
+        assert_eq!(fb.pixel((100, 50)), Some([0xff, 0, 0, 0xff]));
+        assert_eq!(fb.pixel((200, 50)), Some([0; 4]));
+        #[cfg(feature = "png")]
+        {
+            // Set `WITAS_UPDATE_GOLDEN` to regenerate the golden image.
+            let path = concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/testdata/present_and_capture.png"
+            );
+            if std::env::var_os("WITAS_UPDATE_GOLDEN").is_some() {
+                fb.write_png(std::fs::File::create(path).unwrap()).unwrap();
+            }
+            let golden = Framebuffer::read_png(std::fs::File::open(path).unwrap()).unwrap();
+            assert!(fb == &golden);
+        }
+        WindowManager::resize(&window, PhysicalSize::new(320, 240));
+        let capture = rt.block_on(window.capture()).unwrap();
+        assert_eq!(capture.framebuffer.size(), PhysicalSize::new(320, 240));
+        assert_eq!(
+            capture.framebuffer.pixel((100, 50)),
+            Some([0xff, 0, 0, 0xff])
+        );
+        window.destroy();
+        assert!(rt.block_on(window.capture()).is_none());
+    }
+
+    fn ime_events(rx: &mut EventReceiver) -> Vec<Event> {
+        drain(rx)
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    Event::ImeStartComposition(_)
+                        | Event::ImeComposition(_)
+                        | Event::ImeEndComposition(_)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn simulated_ime() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("simulated_ime");
+        drain(&mut rx);
+        // An idle subscriber does not hold up the answer.
+        let _subscriber = rx.subscribe();
+        let mut sim = Simulator::new();
+        sim.add_ime_candidates("にほんご", ["日本語"]);
+        sim.add_ime_candidates("へんかん", ["変換"]);
+        sim.ime_type("nihongohenkan");
+        let mut events = ime_events(&mut rx);
+        let Event::ImeStartComposition(mut start) = events.remove(0) else {
+            panic!()
+        };
+        start.set_position((10, 20));
+        drop(start);
+        assert_eq!(
+            sim.ime_candidate_position(),
+            Some(PhysicalPosition::new(10, 20))
+        );
+        assert_eq!(events.len(), 13);
+        let Some(Event::ImeComposition(composition)) = events.pop() else {
+            panic!()
+        };
+        assert_eq!(
+            composition.chars.iter().collect::<String>(),
+            "にほんごへんかn"
+        );
+        assert_eq!(
+            composition.clauses,
+            [ime::Clause {
+                range: 0..8,
+                target: false
+            }]
+        );
+        assert_eq!(composition.cursor_position, 8);
+        sim.ime_type(" ");
+        let Some(Event::ImeComposition(composition)) = ime_events(&mut rx).pop() else {
+            panic!()
+        };
+        assert_eq!(composition.chars.iter().collect::<String>(), "日本語変換");
+        assert_eq!(
+            composition.clauses,
+            [
+                ime::Clause {
+                    range: 0..3,
+                    target: true
+                },
+                ime::Clause {
+                    range: 3..5,
+                    target: false
+                }
+            ]
+        );
+        assert_eq!(composition.cursor_position, 3);
+        assert_eq!(
+            sim.ime_candidates().unwrap(),
+            ["日本語", "にほんご", "ニホンゴ"]
+        );
+        sim.ime_type(" ");
+        sim.ime_next_clause();
+        sim.ime_select_candidate(2);
+        let Some(Event::ImeComposition(composition)) = ime_events(&mut rx).pop() else {
+            panic!()
+        };
+        assert_eq!(
+            composition.chars.iter().collect::<String>(),
+            "にほんごヘンカン"
+        );
+        assert!(composition.clauses[1].target);
+        assert_eq!(composition.cursor_position, 8);
+        sim.ime_type("\n");
+        let events = ime_events(&mut rx);
+        assert!(matches!(
+            &events[..],
+            [Event::ImeEndComposition(events::ImeEndComposition { result: Some(result) })] if result == "にほんごヘンカン"
+        ));
+        assert_eq!(sim.ime_candidate_position(), None);
+        sim.ime_type("ka\x08\x08");
+        let events = ime_events(&mut rx);
+        assert!(matches!(events[0], Event::ImeStartComposition(_)));
+        assert!(matches!(
+            events.last().unwrap(),
+            Event::ImeEndComposition(events::ImeEndComposition { result: None })
+        ));
+        sim.ime_type("a\n \n");
+        let events = drain(&mut rx);
+        assert!(events.iter().any(|e| matches!(e,
+            Event::ImeEndComposition(events::ImeEndComposition { result: Some(result) }) if result == "あ")));
+        let chars = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::CharInput(e) => Some(e.c),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(chars, " \r");
+        window.ime(false);
+        sim.ime_type("a");
+        let events = drain(&mut rx);
+        assert!(matches!(
+            events[1],
+            Event::CharInput(events::CharInput { c: 'a' })
+        ));
+        window.close();
+    }
+
+    #[test]
+    fn ime_without_polled_receiver() {
+        let _lock = lock();
+        let (window, _rx) = create_window("ime_without_polled_receiver");
+        let mut sim = Simulator::new();
+        sim.ime_type("a");
+        // The app reads no receiver, so the default position is answered without waiting.
+        assert_eq!(
+            sim.ime_candidate_position(),
+            Some(PhysicalPosition::new(0, 0))
+        );
+        sim.ime_cancel();
+        window.close();
+        assert!(window.is_closed());
+    }
+
+    #[test]
+    fn simulated_mouse_input() {
+        let _lock = lock();
+        let (a, mut rx_a) = create_window("simulated_mouse_input_a");
+        let (b, mut rx_b) = create_window("simulated_mouse_input_b");
+        drain(&mut rx_a);
+        drain(&mut rx_b);
+        let mut sim = Simulator::new();
+        sim.click(&a, (10, 20), MouseButton::Left);
+        let events = drain(&mut rx_a);
+        assert!(
+            matches!(&events[0], Event::CursorEntered(e) if e.mouse_state.position == PhysicalPosition::new(10, 20))
+        );
+        assert!(matches!(events[1], Event::Activated));
+        assert!(matches!(&events[2], Event::MouseInput(e)
+            if e.button_state == ButtonState::Pressed && e.mouse_state.buttons.contains(MouseButton::Left)));
+        assert!(matches!(&events[3], Event::MouseInput(e)
+            if e.button_state == ButtonState::Released && e.mouse_state.buttons.is_empty()));
+        assert!(matches!(drain(&mut rx_b)[..], [Event::Inactivated]));
+        sim.drag((0, 0), (40, 80));
+        let events = drain(&mut rx_a);
+        assert_eq!(events.len(), 7);
+        assert!(matches!(&events[1], Event::MouseInput(_)));
+        assert!(matches!(&events[5], Event::CursorMoved(e)
+            if e.mouse_state.position == PhysicalPosition::new(40, 80)
+                && e.mouse_state.buttons.contains(MouseButton::Left)));
+        sim.scroll(MouseWheelAxis::Vertical, -120);
+        assert!(matches!(&drain(&mut rx_a)[..], [Event::MouseWheel(e)] if e.distance == -120));
+        sim.move_cursor(&b, (5, 5));
+        assert!(matches!(drain(&mut rx_a)[..], [Event::CursorLeft(_)]));
+        assert!(matches!(drain(&mut rx_b)[..], [Event::CursorEntered(_)]));
+        a.close();
+        b.close();
+    }
+
+    #[test]
+    fn simulated_keyboard_input() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("simulated_keyboard_input");
+        drain(&mut rx);
+        let mut sim = Simulator::new();
+        sim.key_down(VirtualKey::A);
+        sim.key_down(VirtualKey::A);
+        sim.key_up(VirtualKey::A);
+        let events = drain(&mut rx);
+        assert!(
+            matches!(&events[0], Event::KeyInput(e) if e.key_code.vkey == VirtualKey::A && !e.prev_pressed)
+        );
+        assert!(matches!(&events[1], Event::CharInput(e) if e.c == 'a'));
+        assert!(
+            matches!(&events[2], Event::KeyInput(e) if e.key_state == KeyState::Pressed && e.prev_pressed)
+        );
+        assert!(matches!(&events[4], Event::KeyInput(e) if e.key_state == KeyState::Released));
+        sim.type_text("Hi あ");
+        let chars = drain(&mut rx)
+            .into_iter()
+            .filter_map(|e| match e {
+                Event::CharInput(e) => Some(e.c),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(chars, "Hi あ");
+        sim.type_text("\n");
+        sim.press_key(VirtualKey::Enter);
+        let chars = drain(&mut rx)
+            .into_iter()
+            .filter_map(|e| match e {
+                Event::CharInput(e) => Some(e.c),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(chars, "\r\r");
+        sim.type_text("!");
+        let events = drain(&mut rx);
+        assert!(
+            matches!(&events[0], Event::KeyInput(e) if e.key_code.vkey == VirtualKey::Other(VK_PACKET))
+        );
+        assert!(matches!(&events[1], Event::CharInput(e) if e.c == '!'));
+        window.close();
+    }
+
+    #[test]
+    fn event_mask() {
+        let _lock = lock();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (window, mut rx) = rt
+            .block_on(
+                Window::builder()
+                    .title("event_mask")
+                    .inner_size(PhysicalSize::new(640, 480))
+                    .events(EventMask::DRAW | EventMask::RESIZED)
+                    .build(),
+            )
+            .unwrap();
+        let mut sim = Simulator::new();
+        sim.click(&window, (10, 10), MouseButton::Left);
+        WindowManager::resize(&window, PhysicalSize::new(800, 600));
+        let events = drain(&mut rx);
+        assert!(!events.is_empty());
+        assert!(events
+            .iter()
+            .all(|e| matches!(e, Event::Draw(_) | Event::Resized(_))));
+        window.set_event_mask(EventMask::ALL);
+        sim.click(&window, (10, 10), MouseButton::Left);
+        let events = drain(&mut rx);
+        assert!(matches!(events[0], Event::CursorMoved(_)));
+        assert!(matches!(events[1], Event::MouseInput(_)));
+        window.set_event_mask(EventMask::empty());
+        window.close();
+        assert!(matches!(drain(&mut rx)[..], [Event::Closed, Event::Quit]));
+    }
+
+    #[test]
+    fn subscribe_and_stream() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("subscribe_and_stream");
+        drain(&mut rx);
+        let mut sub = rx.subscribe();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (mut rx, events) = rt.block_on(async {
+            // Both tasks wait at once, so each receiver must keep its own waker.
+            let a = tokio::spawn(async move {
+                let event = rx.recv().await;
+                (rx, format!("{:?}", event))
+            });
+            let b = tokio::spawn(async move { format!("{:?}", sub.recv().await) });
+            tokio::task::yield_now().await;
+            WindowManager::resize(&window, PhysicalSize::new(800, 600));
+            let (rx, a) = a.await.unwrap();
+            (rx, [a, b.await.unwrap()])
+        });
+        assert!(events[0].starts_with("Resiz"));
+        assert_eq!(events[0], events[1]);
+        drain(&mut rx);
+        rx.event_sender().send(1u32).unwrap();
+        let item = rt.block_on(std::future::poll_fn(|cx| {
+            futures_core::Stream::poll_next(std::pin::Pin::new(&mut rx.timestamped()), cx)
+        }));
+        let Some((Event::User(_), timestamp)) = item else { panic!() };
+        assert_eq!(rx.timestamp(), Some(timestamp));
+        window.destroy();
+        let events = rt.block_on(async {
+            let mut events = vec![];
+            while let Some(event) = std::future::poll_fn(|cx| {
+                futures_core::Stream::poll_next(std::pin::Pin::new(&mut rx), cx)
+            })
+            .await
+            {
+                events.push(event);
+            }
+            events
+        });
+        assert!(matches!(events[..], [.., Event::Closed, Event::Quit]));
+        assert!(matches!(
+            rx.subscribe().try_recv(),
+            Err(Error::UiThreadClosed)
+        ));
+    }
+
+    #[test]
+    fn app_events() {
+        let _lock = lock();
+        let mut app = AppEventReceiver::new();
+        let (a, _a_rx) = create_window("app_events_a");
+        let (b, _b_rx) = create_window("app_events_b");
+        let mut sim = Simulator::new();
+        sim.click(&a, (10, 10), MouseButton::Left);
+        sim.click(&b, (10, 10), MouseButton::Left);
+        a.destroy();
+        b.destroy();
+        let events = std::iter::from_fn(|| app.try_recv()).collect::<Vec<_>>();
+        let clicked = |id| {
+            events
+                .iter()
+                .any(|(i, e)| *i == id && matches!(e, Event::MouseInput(_)))
+        };
+        assert!(clicked(a.id()) && clicked(b.id()));
+        let quits = events
+            .iter()
+            .filter(|(_, e)| matches!(e, Event::Quit))
+            .collect::<Vec<_>>();
+        assert_eq!(quits.len(), 1);
+        assert_eq!(quits[0].0, b.id());
+        assert!(matches!(events.last(), Some((_, Event::Quit))));
+    }
+
+    #[test]
+    fn user_events() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("user_events");
+        drain(&mut rx);
+        let sender = rx.event_sender::<String>();
+        let mut sim = Simulator::new();
+        sim.click(&window, (10, 10), MouseButton::Left);
+        let worker = sender.clone();
+        std::thread::spawn(move || worker.send("loaded".to_string()).unwrap())
+            .join()
+            .unwrap();
+        sim.type_text("a");
+        let events = drain(&mut rx);
+        let user = events
+            .iter()
+            .position(
+                |e| matches!(e, Event::User(u) if u.downcast_ref::<String>().unwrap() == "loaded"),
+            )
+            .unwrap();
+        assert!(events[..user]
+            .iter()
+            .any(|e| matches!(e, Event::MouseInput(_))));
+        assert!(events[user..]
+            .iter()
+            .any(|e| matches!(e, Event::KeyInput(_))));
+        window.destroy();
+        assert!(matches!(
+            sender.send(String::new()),
+            Err(Error::WindowClosed)
+        ));
+    }
+
+    #[test]
+    fn close_requested() {
+        let _lock = lock();
+        let (window, mut rx) = create_window("close_requested");
+        drain(&mut rx);
+        window.close();
+        let mut events = drain(&mut rx);
+        let Some(Event::CloseRequested(req)) = events.pop() else {
+            panic!()
+        };
+        req.cancel();
+        drop(req);
+        assert!(!window.is_closed());
+        window.close();
+        let Some(Event::CloseRequested(req)) = drain(&mut rx).pop() else {
+            panic!()
+        };
+        assert!(!window.is_closed());
+        req.confirm();
+        assert!(window.is_closed());
+        assert!(matches!(
+            drain(&mut rx)[..],
+            [.., Event::Closed, Event::Quit]
+        ));
+
+        // Idle receivers other than the one returned with the window do not keep it open.
+        let (window, mut rx) = create_window("close_requested");
+        let _subscriber = rx.subscribe();
+        let _app = AppEventReceiver::new();
+        drain(&mut rx);
+        window.close();
+        let Some(Event::CloseRequested(req)) = drain(&mut rx).pop() else {
+            panic!()
+        };
+        assert!(!window.is_closed());
+        drop(req);
+        assert!(window.is_closed());
+
+        // An app which reads only `AppEventReceiver` answers instead of the idle receiver.
+        let mut app = AppEventReceiver::new();
+        let (window, _rx) = create_window("close_requested");
+        while app.try_recv().is_some() {}
+        window.close();
+        let req = std::iter::from_fn(|| app.try_recv())
+            .find_map(|(_, event)| match event {
+                Event::CloseRequested(req) => Some(req),
+                _ => None,
+            })
+            .unwrap();
+        req.cancel();
+        drop(req);
+        assert!(!window.is_closed());
+        window.close();
+        while app.try_recv().is_some() {}
+        assert!(window.is_closed());
+        drop(app);
+
+        // Nothing is polled, so the default answer is taken at once.
+        let (window, _rx) = create_window("close_requested");
+        window.close();
+        assert!(window.is_closed());
+
+        let (window, mut rx) = create_window("close_requested");
+        let mut mask = EventMask::ALL;
+        mask.remove(EventMask::CLOSE_REQUESTED);
+        window.set_event_mask(mask);
+        window.close();
+        assert!(window.is_closed());
+        assert!(!drain(&mut rx)
+            .iter()
+            .any(|e| matches!(e, Event::CloseRequested(_))));
+    }
+}
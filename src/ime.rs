@@ -1,16 +1,21 @@
+#[cfg(windows)]
 use crate::utility::*;
+#[cfg(windows)]
 use crate::*;
+#[cfg(windows)]
 use windows::Win32::{
     Foundation::{HWND, POINT, RECT},
     Globalization::*,
     UI::Input::Ime::*,
 };
 
+#[cfg(windows)]
 pub(crate) struct ImmContext {
     hwnd: HWND,
     himc: HIMC,
 }
 
+#[cfg(windows)]
 impl ImmContext {
     pub fn new(hwnd: HWND) -> Self {
         unsafe {
@@ -33,6 +38,7 @@ impl ImmContext {
     }
 }
 
+#[cfg(windows)]
 impl Drop for ImmContext {
     fn drop(&mut self) {
         unsafe {
@@ -43,16 +49,19 @@ impl Drop for ImmContext {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Clause {
     pub range: std::ops::Range<usize>,
     pub target: bool,
 }
 
+#[cfg(windows)]
 pub(crate) struct Imc {
     hwnd: HWND,
     himc: HIMC,
 }
 
+#[cfg(windows)]
 impl Imc {
     pub fn get(hwnd: HWND) -> Self {
         let himc = unsafe { ImmGetContext(hwnd) };
@@ -187,6 +196,7 @@ impl Imc {
     }
 }
 
+#[cfg(windows)]
 impl Drop for Imc {
     fn drop(&mut self) {
         unsafe {
@@ -0,0 +1,264 @@
+//! Streaming events to a debugging tool.
+//!
+//! [`Inspector`] writes a [`Record`] as a line of JSON for every event and raw input of every
+//! window to each client connected to a local socket. `witas-inspect` connects to it and prints
+//! the records.
+
+use crate::raw_input::{InputData, RawInputEvent};
+use crate::*;
+use std::future::Future;
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+/// How often the inspector accepts new clients while no events come.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A client which does not read for this long is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An event or a raw input.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub enum Item {
+    Event(Event),
+    RawInput(RawInputEvent),
+}
+
+impl Item {
+    /// Returns the kind of the event, prefixed by `Raw` for raw input.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Event(event) => event.kind(),
+            Self::RawInput(event) => match event {
+                RawInputEvent::Input(InputData::Keyboard(_)) => "RawKeyboard",
+                RawInputEvent::Input(InputData::Mouse(_)) => "RawMouse",
+                RawInputEvent::Input(InputData::GamePad(_)) => "RawGamePad",
+                RawInputEvent::DeviceChange(_) => "RawDeviceChange",
+                RawInputEvent::Quit => "RawQuit",
+            },
+        }
+    }
+}
+
+/// An item and its `Timestamp::instant` measured from when the inspector started.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    pub window: WindowId,
+    pub elapsed: Duration,
+    pub message_time: Option<u32>,
+    pub item: Item,
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    /// Returns `None` when no client is waiting.
+    fn accept(&self) -> Option<Box<dyn Write + Send>> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, _) = listener.accept().ok()?;
+                stream.set_nonblocking(false).ok()?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+                stream.set_nodelay(true).ok()?;
+                Some(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener, _) => {
+                let (stream, _) = listener.accept().ok()?;
+                stream.set_nonblocking(false).ok()?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+                Some(Box::new(stream))
+            }
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Self::Unix(_, path) = self {
+            std::fs::remove_file(path).unwrap_or(());
+        }
+    }
+}
+
+struct Unpark(std::thread::Thread);
+
+impl Wake for Unpark {
+    #[inline]
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn write_record(clients: &mut Vec<Box<dyn Write + Send>>, record: &Record) {
+    let Ok(mut line) = serde_json::to_vec(record) else { return };
+    line.push(b'\n');
+    clients.retain_mut(|client| client.write_all(&line).is_ok());
+}
+
+fn run(listener: Listener, stop: Arc<AtomicBool>) {
+    let mut events = AppEventReceiver::new();
+    let mut raw_input = Context::subscribe_app_raw_input();
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = std::task::Context::from_waker(&waker);
+    let start = Instant::now();
+    let mut clients: Vec<Box<dyn Write + Send>> = vec![];
+    while !stop.load(Ordering::Acquire) {
+        while let Some(client) = listener.accept() {
+            clients.push(client);
+        }
+        let mut idle = true;
+        while let Poll::Ready((window, event)) = std::pin::pin!(events.recv()).poll(&mut cx) {
+            idle = false;
+            if clients.is_empty() || matches!(event, Event::User(_)) {
+                continue;
+            }
+            let timestamp = events.timestamp().unwrap();
+            let record = Record {
+                window,
+                elapsed: timestamp.instant.saturating_duration_since(start),
+                message_time: timestamp.message_time,
+                item: Item::Event(event),
+            };
+            write_record(&mut clients, &record);
+        }
+        while let Poll::Ready(Some(((window, event), timestamp))) = raw_input.poll_recv(&mut cx) {
+            idle = false;
+            if clients.is_empty() {
+                continue;
+            }
+            let record = Record {
+                window,
+                elapsed: timestamp.instant.saturating_duration_since(start),
+                message_time: timestamp.message_time,
+                item: Item::RawInput(event),
+            };
+            write_record(&mut clients, &record);
+        }
+        if idle {
+            std::thread::park_timeout(ACCEPT_INTERVAL);
+        }
+    }
+}
+
+/// Streams the events and raw input of every window as JSON lines to clients of a local socket.
+///
+/// Events before the inspector was created and `Event::User` are not streamed. The inspector stops
+/// when dropped.
+pub struct Inspector {
+    th: Option<std::thread::JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl Inspector {
+    fn new(listener: Listener, local_addr: Option<SocketAddr>) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let th = std::thread::Builder::new()
+            .name("witas Inspector".into())
+            .spawn({
+                let stop = stop.clone();
+                move || run(listener, stop)
+            })?;
+        Ok(Self {
+            th: Some(th),
+            stop,
+            local_addr,
+        })
+    }
+
+    /// Listens on a TCP address such as `127.0.0.1:0`.
+    pub fn bind_tcp(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        Self::new(Listener::Tcp(listener), Some(local_addr))
+    }
+
+    /// Listens on a Unix domain socket. The socket file is removed when the inspector is dropped.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn bind_unix(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Self::new(Listener::Unix(listener, path.to_path_buf()), None)
+    }
+
+    /// Returns the address of the TCP listener.
+    #[inline]
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+}
+
+impl Drop for Inspector {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        let Some(th) = self.th.take() else { return };
+        th.thread().unpark();
+        th.join().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::tests::{create_window, drain, lock};
+    use std::io::{BufRead, ErrorKind};
+
+    fn read_record(reader: &mut impl BufRead, line: &mut String) -> Option<Record> {
+        match reader.read_line(line) {
+            Ok(_) => {
+                let record = serde_json::from_str(line).unwrap();
+                line.clear();
+                Some(record)
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => None,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn stream() {
+        let _lock = lock();
+        let inspector = Inspector::bind_tcp("127.0.0.1:0").unwrap();
+        let stream = std::net::TcpStream::connect(inspector.local_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let mut reader = std::io::BufReader::new(stream);
+        let mut line = String::new();
+        let (window, mut rx) = create_window("inspector");
+        drain(&mut rx);
+        // The client is accepted asynchronously, so the event is sent until it arrives. Windows
+        // of other tests may send events too.
+        let id = window.id();
+        let received = (0..100).any(|_| {
+            Context::send_event(id, Event::Minimized);
+            std::iter::from_fn(|| read_record(&mut reader, &mut line))
+                .any(|record| record.window == id && record.item.kind() == "Minimized")
+        });
+        assert!(received);
+        Context::send_raw_input_event(id, RawInputEvent::Quit);
+        let received = std::iter::repeat_with(|| read_record(&mut reader, &mut line))
+            .take(100)
+            .flatten()
+            .any(|record| record.window == id && record.item.kind() == "RawQuit");
+        assert!(received);
+        window.destroy();
+        drop(inspector);
+    }
+}
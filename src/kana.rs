@@ -0,0 +1,144 @@
+//! Romaji to kana conversion for the simulated IME of the headless backend.
+
+#[rustfmt::skip]
+const TABLE: &[(&str, &str)] = &[
+    ("a", "あ"), ("i", "い"), ("u", "う"), ("e", "え"), ("o", "お"),
+    ("ka", "か"), ("ki", "き"), ("ku", "く"), ("ke", "け"), ("ko", "こ"),
+    ("sa", "さ"), ("si", "し"), ("shi", "し"), ("su", "す"), ("se", "せ"), ("so", "そ"),
+    ("ta", "た"), ("ti", "ち"), ("chi", "ち"), ("tu", "つ"), ("tsu", "つ"), ("te", "て"), ("to", "と"),
+    ("na", "な"), ("ni", "に"), ("nu", "ぬ"), ("ne", "ね"), ("no", "の"),
+    ("ha", "は"), ("hi", "ひ"), ("hu", "ふ"), ("fu", "ふ"), ("he", "へ"), ("ho", "ほ"),
+    ("ma", "ま"), ("mi", "み"), ("mu", "む"), ("me", "め"), ("mo", "も"),
+    ("ya", "や"), ("yu", "ゆ"), ("yo", "よ"),
+    ("ra", "ら"), ("ri", "り"), ("ru", "る"), ("re", "れ"), ("ro", "ろ"),
+    ("wa", "わ"), ("wo", "を"), ("nn", "ん"), ("n'", "ん"),
+    ("ga", "が"), ("gi", "ぎ"), ("gu", "ぐ"), ("ge", "げ"), ("go", "ご"),
+    ("za", "ざ"), ("zi", "じ"), ("ji", "じ"), ("zu", "ず"), ("ze", "ぜ"), ("zo", "ぞ"),
+    ("da", "だ"), ("di", "ぢ"), ("du", "づ"), ("de", "で"), ("do", "ど"),
+    ("ba", "ば"), ("bi", "び"), ("bu", "ぶ"), ("be", "べ"), ("bo", "ぼ"),
+    ("pa", "ぱ"), ("pi", "ぴ"), ("pu", "ぷ"), ("pe", "ぺ"), ("po", "ぽ"),
+    ("kya", "きゃ"), ("kyu", "きゅ"), ("kyo", "きょ"),
+    ("sya", "しゃ"), ("syu", "しゅ"), ("syo", "しょ"),
+    ("sha", "しゃ"), ("shu", "しゅ"), ("she", "しぇ"), ("sho", "しょ"),
+    ("tya", "ちゃ"), ("tyu", "ちゅ"), ("tyo", "ちょ"),
+    ("cha", "ちゃ"), ("chu", "ちゅ"), ("che", "ちぇ"), ("cho", "ちょ"),
+    ("nya", "にゃ"), ("nyu", "にゅ"), ("nyo", "にょ"),
+    ("hya", "ひゃ"), ("hyu", "ひゅ"), ("hyo", "ひょ"),
+    ("mya", "みゃ"), ("myu", "みゅ"), ("myo", "みょ"),
+    ("rya", "りゃ"), ("ryu", "りゅ"), ("ryo", "りょ"),
+    ("gya", "ぎゃ"), ("gyu", "ぎゅ"), ("gyo", "ぎょ"),
+    ("zya", "じゃ"), ("zyu", "じゅ"), ("zyo", "じょ"),
+    ("ja", "じゃ"), ("ju", "じゅ"), ("je", "じぇ"), ("jo", "じょ"),
+    ("bya", "びゃ"), ("byu", "びゅ"), ("byo", "びょ"),
+    ("pya", "ぴゃ"), ("pyu", "ぴゅ"), ("pyo", "ぴょ"),
+    ("fa", "ふぁ"), ("fi", "ふぃ"), ("fe", "ふぇ"), ("fo", "ふぉ"),
+    ("xa", "ぁ"), ("xi", "ぃ"), ("xu", "ぅ"), ("xe", "ぇ"), ("xo", "ぉ"),
+    ("la", "ぁ"), ("li", "ぃ"), ("lu", "ぅ"), ("le", "ぇ"), ("lo", "ぉ"),
+    ("xya", "ゃ"), ("xyu", "ゅ"), ("xyo", "ょ"), ("xtu", "っ"), ("xtsu", "っ"),
+    ("lya", "ゃ"), ("lyu", "ゅ"), ("lyo", "ょ"), ("ltu", "っ"), ("ltsu", "っ"),
+    ("-", "ー"), (",", "、"), (".", "。"), ("[", "「"), ("]", "」"),
+];
+
+/// Romaji which is being converted to hiragana key by key.
+#[derive(Default, Debug)]
+pub(crate) struct Romaji {
+    kana: String,
+    pending: String,
+}
+
+impl Romaji {
+    /// Converts the romaji typed so far and `c` as far as possible.
+    pub fn push(&mut self, c: char) {
+        self.pending.push(c.to_ascii_lowercase());
+        while !self.pending.is_empty() {
+            if let Some((_, kana)) = TABLE.iter().find(|(romaji, _)| *romaji == self.pending) {
+                self.kana.push_str(kana);
+                self.pending.clear();
+                return;
+            }
+            if TABLE
+                .iter()
+                .any(|(romaji, _)| romaji.starts_with(&self.pending))
+            {
+                return;
+            }
+            let mut chars = self.pending.chars();
+            let first = chars.next().unwrap();
+            let second = chars.next();
+            if second == Some(first) && first.is_ascii_alphabetic() && !"aiueon".contains(first) {
+                self.kana.push('っ');
+            } else if first == 'n' {
+                self.kana.push('ん');
+            } else {
+                self.kana.push(first);
+            }
+            self.pending.remove(0);
+        }
+    }
+
+    /// Converts a trailing `n` to `ん` as the IME does when the composition is converted.
+    pub fn flush(&mut self) {
+        if self.pending == "n" {
+            self.kana.push('ん');
+            self.pending.clear();
+        }
+        self.kana.push_str(&self.pending);
+        self.pending.clear();
+    }
+
+    /// Deletes the last character of `text()`.
+    pub fn pop(&mut self) {
+        if self.pending.pop().is_none() {
+            self.kana.pop();
+        }
+    }
+
+    /// Returns the converted kana followed by the romaji which is not converted yet.
+    pub fn text(&self) -> String {
+        format!("{}{}", self.kana, self.pending)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kana.is_empty() && self.pending.is_empty()
+    }
+}
+
+/// Converts hiragana in `s` to katakana.
+pub(crate) fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3041}'..='\u{3096}' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(romaji: &str) -> String {
+        let mut r = Romaji::default();
+        romaji.chars().for_each(|c| r.push(c));
+        r.text()
+    }
+
+    #[test]
+    fn romaji_to_kana() {
+        assert_eq!(convert("nihongo"), "にほんご");
+        assert_eq!(convert("kanji"), "かんじ");
+        assert_eq!(convert("gakkou"), "がっこう");
+        assert_eq!(convert("konnnichiha"), "こんにちは");
+        assert_eq!(convert("shinbun"), "しんぶn");
+        assert_eq!(convert("kyo-"), "きょー");
+        assert_eq!(convert("Tokyo."), "ときょ。");
+        assert_eq!(convert("q"), "q");
+        let mut r = Romaji::default();
+        "shinbun".chars().for_each(|c| r.push(c));
+        r.flush();
+        assert_eq!(r.text(), "しんぶん");
+        r.pop();
+        assert_eq!(r.text(), "しんぶ");
+        assert_eq!(to_katakana("にほんご"), "ニホンゴ");
+    }
+}
@@ -0,0 +1,158 @@
+//! Translation of X keysyms which are shared by the X11 and Wayland backends.
+
+use crate::*;
+
+pub(crate) fn is_keypad(keysym: u32) -> bool {
+    (0xff80..=0xffbd).contains(&keysym)
+}
+
+pub(crate) fn as_virtual_key(keysym: u32) -> VirtualKey {
+    const LETTERS: [VirtualKey; 26] = [
+        VirtualKey::A,
+        VirtualKey::B,
+        VirtualKey::C,
+        VirtualKey::D,
+        VirtualKey::E,
+        VirtualKey::F,
+        VirtualKey::G,
+        VirtualKey::H,
+        VirtualKey::I,
+        VirtualKey::J,
+        VirtualKey::K,
+        VirtualKey::L,
+        VirtualKey::M,
+        VirtualKey::N,
+        VirtualKey::O,
+        VirtualKey::P,
+        VirtualKey::Q,
+        VirtualKey::R,
+        VirtualKey::S,
+        VirtualKey::T,
+        VirtualKey::U,
+        VirtualKey::V,
+        VirtualKey::W,
+        VirtualKey::X,
+        VirtualKey::Y,
+        VirtualKey::Z,
+    ];
+    const DIGITS: [VirtualKey; 10] = [
+        VirtualKey::Key0,
+        VirtualKey::Key1,
+        VirtualKey::Key2,
+        VirtualKey::Key3,
+        VirtualKey::Key4,
+        VirtualKey::Key5,
+        VirtualKey::Key6,
+        VirtualKey::Key7,
+        VirtualKey::Key8,
+        VirtualKey::Key9,
+    ];
+    const NUMPAD: [VirtualKey; 10] = [
+        VirtualKey::Num0,
+        VirtualKey::Num1,
+        VirtualKey::Num2,
+        VirtualKey::Num3,
+        VirtualKey::Num4,
+        VirtualKey::Num5,
+        VirtualKey::Num6,
+        VirtualKey::Num7,
+        VirtualKey::Num8,
+        VirtualKey::Num9,
+    ];
+    const FUNCTIONS: [VirtualKey; 24] = [
+        VirtualKey::F1,
+        VirtualKey::F2,
+        VirtualKey::F3,
+        VirtualKey::F4,
+        VirtualKey::F5,
+        VirtualKey::F6,
+        VirtualKey::F7,
+        VirtualKey::F8,
+        VirtualKey::F9,
+        VirtualKey::F10,
+        VirtualKey::F11,
+        VirtualKey::F12,
+        VirtualKey::F13,
+        VirtualKey::F14,
+        VirtualKey::F15,
+        VirtualKey::F16,
+        VirtualKey::F17,
+        VirtualKey::F18,
+        VirtualKey::F19,
+        VirtualKey::F20,
+        VirtualKey::F21,
+        VirtualKey::F22,
+        VirtualKey::F23,
+        VirtualKey::F24,
+    ];
+    match keysym {
+        0x0061..=0x007a => LETTERS[(keysym - 0x0061) as usize],
+        0x0041..=0x005a => LETTERS[(keysym - 0x0041) as usize],
+        0x0030..=0x0039 => DIGITS[(keysym - 0x0030) as usize],
+        0xffb0..=0xffb9 => NUMPAD[(keysym - 0xffb0) as usize],
+        0xffbe..=0xffd5 => FUNCTIONS[(keysym - 0xffbe) as usize],
+        0x0020 => VirtualKey::Space,
+        0xff1b => VirtualKey::Esc,
+        0xff09 => VirtualKey::Tab,
+        0xffe5 => VirtualKey::CapsLock,
+        0xffe1 | 0xffe2 => VirtualKey::Shift,
+        0xffe3 | 0xffe4 => VirtualKey::Ctrl,
+        0xffe9 | 0xffea => VirtualKey::Alt,
+        0xff08 => VirtualKey::BackSpace,
+        0xff0d | 0xff8d => VirtualKey::Enter,
+        0xff61 => VirtualKey::PrintScreen,
+        0xff14 => VirtualKey::ScrollLock,
+        0xff13 => VirtualKey::Pause,
+        0xff63 => VirtualKey::Insert,
+        0xffff => VirtualKey::Delete,
+        0xff50 => VirtualKey::Home,
+        0xff57 => VirtualKey::End,
+        0xff55 => VirtualKey::PageUp,
+        0xff56 => VirtualKey::PageDown,
+        0xff52 => VirtualKey::Up,
+        0xff54 => VirtualKey::Down,
+        0xff51 => VirtualKey::Left,
+        0xff53 => VirtualKey::Right,
+        0xff7f => VirtualKey::NumLock,
+        0xffab => VirtualKey::NumAdd,
+        0xffad => VirtualKey::NumSub,
+        0xffaa => VirtualKey::NumMul,
+        0xffaf => VirtualKey::NumDiv,
+        0xffae => VirtualKey::NumDecimal,
+        _ => VirtualKey::Other(keysym),
+    }
+}
+
+pub(crate) fn keysym_to_char(keysym: u32) -> Option<char> {
+    match keysym {
+        0x0020..=0x007e | 0x00a0..=0x00ff => char::from_u32(keysym),
+        0x0100_0000..=0x0110_ffff => char::from_u32(keysym - 0x0100_0000),
+        0xffb0..=0xffb9 => char::from_u32(keysym - 0xffb0 + '0' as u32),
+        0xff08 => Some('\x08'),
+        0xff09 => Some('\t'),
+        0xff0d | 0xff8d => Some('\r'),
+        0xff1b => Some('\x1b'),
+        0xffab => Some('+'),
+        0xffad => Some('-'),
+        0xffaa => Some('*'),
+        0xffaf => Some('/'),
+        0xffae => Some('.'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keysyms() {
+        assert_eq!(as_virtual_key(0x0061), VirtualKey::A);
+        assert_eq!(as_virtual_key(0xffbe), VirtualKey::F1);
+        assert_eq!(as_virtual_key(0xffb5), VirtualKey::Num5);
+        assert_eq!(keysym_to_char(0x0041), Some('A'));
+        assert_eq!(keysym_to_char(0x0100_3042), Some('あ'));
+        assert_eq!(keysym_to_char(0xff0d), Some('\r'));
+        assert_eq!(keysym_to_char(0xffff), None);
+    }
+}
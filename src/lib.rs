@@ -1,33 +1,67 @@
 //! An asynchronous window library in Rust for Windows
 
+pub mod backend;
 mod context;
+#[cfg(any(windows, test))]
+mod decode;
 mod device;
 mod error;
 pub mod events;
+mod framebuffer;
 mod geometry;
+mod kana;
+pub mod headless;
 pub mod ime;
+#[cfg(all(unix, any(feature = "x11", feature = "wayland")))]
+mod keysym;
+#[cfg(windows)]
 mod procedure;
+mod queue;
 pub mod raw_input;
+mod reply;
 mod resources;
+#[cfg(windows)]
 mod ui_thread;
+#[cfg(windows)]
 mod utility;
+#[cfg(all(unix, feature = "wayland"))]
+mod wayland;
+#[cfg(windows)]
+mod win32;
 pub mod window;
+#[cfg(all(unix, feature = "x11"))]
+mod x11;
 
 #[cfg(feature = "dialog")]
 #[cfg_attr(docsrs, doc(cfg(feature = "dialog")))]
 pub mod dialog;
 
+#[cfg(feature = "inspector")]
+#[cfg_attr(docsrs, doc(cfg(feature = "inspector")))]
+pub mod inspector;
+
+#[cfg(feature = "record")]
+#[cfg_attr(docsrs, doc(cfg(feature = "record")))]
+pub mod record;
+
 use context::Context;
 
 pub use device::*;
 pub use error::{Error, Result};
 #[doc(inline)]
-pub use events::{Event, ResizingEdge};
+pub use events::{Event, EventMask, ResizingEdge, Timestamp};
+pub use framebuffer::{Capture, Framebuffer};
 pub use geometry::*;
+pub use queue::{LatencyHistogram, QueuePolicy, QueueStats};
+pub use reply::{Reply, DEFAULT_REPLY_TIMEOUT};
 pub use resources::*;
+#[cfg(windows)]
 pub use ui_thread::UiThread;
+#[cfg(windows)]
+pub use window::MessageHook;
 pub use window::{
-    BorderlessStyle, EventReceiver, RawInputEventRecevier, Window, WindowBuilder, WindowStyle,
+    AppEventReceiver, BorderlessStyle, EventReceiver, EventSender, RawInputEventRecevier,
+    Timestamped, Window, WindowBuilder, WindowId, WindowStyle,
 };
 
 #[cfg(feature = "dialog")]
@@ -36,5 +70,7 @@ pub use dialog::{FileDialogOptions, FileOpenDialog, FileSaveDialog, FilterSpec};
 
 #[inline]
 pub fn init() {
+    #[cfg(windows)]
     ui_thread::UiThread::init();
+    backend::get();
 }
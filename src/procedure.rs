@@ -1,13 +1,12 @@
+use crate::decode::{decode, DecodedMessage, SizeKind};
 use crate::utility::*;
 use crate::*;
 use std::any::Any;
 use std::cell::RefCell;
 use std::path::PathBuf;
-use std::sync::mpsc;
 use windows::Win32::{
     Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, SIZE, WPARAM},
     Graphics::Gdi::{BeginPaint, EndPaint, GetUpdateRect, PAINTSTRUCT},
-    UI::Controls::WM_MOUSELEAVE,
     UI::HiDpi::{EnableNonClientDpiScaling, GetDpiForWindow},
     UI::Input::Ime::{ISC_SHOWUIALLCANDIDATEWINDOW, ISC_SHOWUICOMPOSITIONWINDOW},
     UI::Input::KeyboardAndMouse::{
@@ -17,48 +16,27 @@ use windows::Win32::{
     UI::WindowsAndMessaging::*,
 };
 
-fn loword(x: i32) -> i16 {
-    (x & 0xffff) as _
-}
-
-fn hiword(x: i32) -> i16 {
-    ((x >> 16) & 0xffff) as _
-}
-
-fn get_x_lparam(lp: LPARAM) -> i16 {
-    (lp.0 & 0xffff) as _
-}
-
-fn get_y_lparam(lp: LPARAM) -> i16 {
-    ((lp.0 >> 16) & 0xffff) as _
-}
-
-fn get_xbutton_wparam(wp: WPARAM) -> u16 {
-    ((wp.0 >> 16) & 0xffff) as _
-}
-
-fn lparam_to_point<C>(lparam: LPARAM) -> Position<i32, C> {
-    Position::new(get_x_lparam(lparam) as _, get_y_lparam(lparam) as _)
-}
-
-fn lparam_to_size(lparam: LPARAM) -> PhysicalSize<u32> {
-    Size::new(get_x_lparam(lparam) as _, get_y_lparam(lparam) as _)
-}
-
-fn wparam_to_button(wparam: WPARAM) -> MouseButton {
-    match get_xbutton_wparam(wparam) {
-        0x0001 => MouseButton::Ex(0),
-        0x0002 => MouseButton::Ex(1),
-        _ => unreachable!(),
-    }
-}
-
 thread_local! {
     static UNWIND: RefCell<Option<Box<dyn Any + Send>>> = RefCell::new(None);
     static ENTERED: RefCell<Option<HWND>> = RefCell::new(None);
 }
 
+/// Records a panic caught on the UI thread.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn record_panic(e: &(dyn Any + Send)) {
+    #[cfg(feature = "tracing")]
+    {
+        let message = e
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| e.downcast_ref::<String>().map(|s| s.as_str()))
+            .unwrap_or("Box<dyn Any>");
+        tracing::error!(panic = message, "panicked on the UI thread");
+    }
+}
+
 fn set_unwind(e: Box<dyn Any + Send>) {
+    record_panic(e.as_ref());
     UNWIND.with(|u| {
         *u.borrow_mut() = Some(e);
     });
@@ -83,9 +61,7 @@ unsafe fn on_paint(hwnd: HWND) -> LRESULT {
     LRESULT(0)
 }
 
-unsafe fn on_mouse_move(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
-    let position = lparam_to_point(lparam);
-    let buttons = MouseButtons::from(wparam);
+unsafe fn on_mouse_move(hwnd: HWND, mouse_state: MouseState) -> LRESULT {
     let entered = ENTERED.with(|entered| *entered.borrow());
     if entered.is_none() {
         TrackMouseEvent(&mut TRACKMOUSEEVENT {
@@ -99,37 +75,34 @@ unsafe fn on_mouse_move(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
         });
         Context::send_event(
             hwnd,
-            events::Event::CursorEntered(events::CursorEntered {
-                mouse_state: MouseState { position, buttons },
-            }),
+            events::Event::CursorEntered(events::CursorEntered { mouse_state }),
         );
     } else {
         Context::send_event(
             hwnd,
-            events::Event::CursorMoved(events::CursorMoved {
-                mouse_state: MouseState { position, buttons },
-            }),
+            events::Event::CursorMoved(events::CursorMoved { mouse_state }),
         );
     }
 
     LRESULT(0)
 }
 
-unsafe fn on_set_cursor(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
-    if loword(lparam.0 as _) != HTCLIENT as _ {
+unsafe fn on_set_cursor(hwnd: HWND, client: bool, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if !client {
         return DefWindowProcW(hwnd, WM_SETCURSOR, wparam, lparam);
     }
-    Context::get_window_property(hwnd, |props| props.cursor.set());
+    if let Some(cursor) = win32::get_window_property(hwnd, |props| props.cursor) {
+        cursor.set();
+    }
     LRESULT(0)
 }
 
-unsafe fn on_mouse_leave(hwnd: HWND, wparam: WPARAM, _lparam: LPARAM) -> LRESULT {
+unsafe fn on_mouse_leave(hwnd: HWND, buttons: MouseButtons) -> LRESULT {
     ENTERED.with(|entered| {
         *entered.borrow_mut() = None;
     });
     let mut position = POINT::default();
     GetCursorPos(&mut position);
-    let buttons = MouseButtons::from(wparam);
     Context::send_event(
         hwnd,
         Event::CursorLeft(events::CursorLeft {
@@ -146,8 +119,7 @@ unsafe fn on_mouse_input(
     hwnd: HWND,
     button: MouseButton,
     button_state: ButtonState,
-    wparam: WPARAM,
-    lparam: LPARAM,
+    mouse_state: MouseState,
 ) -> LRESULT {
     match button_state {
         ButtonState::Pressed => {
@@ -157,16 +129,12 @@ unsafe fn on_mouse_input(
             ReleaseCapture();
         }
     }
-    let position = lparam_to_point(lparam);
     Context::send_event(
         hwnd,
         Event::MouseInput(events::MouseInput {
             button,
             button_state,
-            mouse_state: MouseState {
-                position,
-                buttons: wparam.into(),
-            },
+            mouse_state,
         }),
     );
     LRESULT(0)
@@ -175,27 +143,28 @@ unsafe fn on_mouse_input(
 unsafe fn on_mouse_wheel(
     hwnd: HWND,
     axis: MouseWheelAxis,
-    wparam: WPARAM,
-    lparam: LPARAM,
+    distance: i32,
+    mouse_state: MouseState,
 ) -> LRESULT {
-    let delta = hiword(wparam.0 as _);
-    let buttons = MouseButtons::from(wparam);
-    let position = lparam_to_point(lparam);
     Context::send_event(
         hwnd,
         Event::MouseWheel(events::MouseWheel {
             axis,
-            distance: delta as i32,
-            mouse_state: MouseState { position, buttons },
+            distance,
+            mouse_state,
         }),
     );
     LRESULT(0)
 }
 
-unsafe fn on_key_input(hwnd: HWND, key_state: KeyState, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
-    let vkey = as_virtual_key(VIRTUAL_KEY(wparam.0 as _));
-    let scan_code = ScanCode(((lparam.0 >> 16) & 0x7f) as u32);
-    let prev_pressed = (lparam.0 >> 30) & 0x01 != 0;
+unsafe fn on_key_input(
+    hwnd: HWND,
+    vkey: u16,
+    scan_code: ScanCode,
+    key_state: KeyState,
+    prev_pressed: bool,
+) -> LRESULT {
+    let vkey = as_virtual_key(VIRTUAL_KEY(vkey));
     Context::send_event(
         hwnd,
         Event::KeyInput(events::KeyInput {
@@ -207,8 +176,8 @@ unsafe fn on_key_input(hwnd: HWND, key_state: KeyState, wparam: WPARAM, lparam:
     LRESULT(0)
 }
 
-unsafe fn on_char(hwnd: HWND, wparam: WPARAM, _lparam: LPARAM) -> LRESULT {
-    if let Some(c) = char::from_u32(wparam.0 as _) {
+unsafe fn on_char(hwnd: HWND, c: Option<char>) -> LRESULT {
+    if let Some(c) = c {
         Context::send_event(hwnd, Event::CharInput(events::CharInput { c }));
     }
     LRESULT(0)
@@ -219,7 +188,7 @@ unsafe fn on_ime_set_context(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRES
         let mut value = lparam.0 as u32;
         value &= !ISC_SHOWUICOMPOSITIONWINDOW;
         let candidate =
-            Context::get_window_property(hwnd, |prop| prop.visible_ime_candidate_window);
+            win32::get_window_property(hwnd, |prop| prop.visible_ime_candidate_window);
         if !candidate.unwrap_or(true) {
             value &= !ISC_SHOWUIALLCANDIDATEWINDOW;
         }
@@ -230,14 +199,15 @@ unsafe fn on_ime_set_context(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRES
 
 unsafe fn on_ime_start_composition(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     let imc = ime::Imc::get(hwnd);
-    let (tx, rx) = mpsc::channel();
+    let (reply, pending) = Reply::channel(PhysicalPosition::new(0, 0));
     Context::send_event(
         hwnd,
-        Event::ImeStartComposition(events::ImeStartComposition::new(tx)),
+        Event::ImeStartComposition(events::ImeStartComposition::new(reply)),
     );
-    if let Ok(position) = rx.recv() {
-        imc.set_candidate_window_position(position, false);
-    }
+    // The default comes at once when no receiver has been polled.
+    let timeout = win32::get_window_property(hwnd, |props| props.reply_timeout)
+        .unwrap_or(DEFAULT_REPLY_TIMEOUT);
+    imc.set_candidate_window_position(pending.wait(timeout), false);
     DefWindowProcW(hwnd, WM_IME_STARTCOMPOSITION, wparam, lparam)
 }
 
@@ -259,7 +229,7 @@ unsafe fn on_ime_end_composition(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) ->
     DefWindowProcW(hwnd, WM_IME_ENDCOMPOSITION, wparam, lparam)
 }
 
-unsafe fn on_sizing(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+unsafe fn on_sizing(hwnd: HWND, edge: ResizingEdge, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     let d = {
         let wrc = get_window_rect(hwnd);
         let crc = get_client_rect(hwnd);
@@ -269,49 +239,43 @@ unsafe fn on_sizing(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
         )
     };
     let rc = (lparam.0 as *mut RECT).as_mut().unwrap();
-    let size = PhysicalSize::new(
-        (rc.right - rc.left - d.width) as u32,
-        (rc.bottom - rc.left - d.height) as u32,
-    );
-    let edge = match wparam.0 as u32 {
-        WMSZ_LEFT => ResizingEdge::Left,
-        WMSZ_RIGHT => ResizingEdge::Right,
-        WMSZ_TOP => ResizingEdge::Top,
-        WMSZ_BOTTOM => ResizingEdge::Bottom,
-        WMSZ_TOPLEFT => ResizingEdge::TopLeft,
-        WMSZ_TOPRIGHT => ResizingEdge::TopRight,
-        WMSZ_BOTTOMLEFT => ResizingEdge::BottomLeft,
-        WMSZ_BOTTOMRIGHT => ResizingEdge::BottomLRight,
-        _ => unreachable!(),
-    };
-    Context::send_event(hwnd, Event::Resizing(events::Resizing { size, edge }));
+    let rect = (rc.left, rc.top, rc.right, rc.bottom);
+    let size = decode::sizing_client_size(rect, d);
+    let (reply, pending) = Reply::channel(size);
+    Context::send_event(hwnd, Event::Resizing(events::Resizing::new(edge, reply)));
+    // The default comes at once when no receiver has been polled.
+    let timeout = win32::get_window_property(hwnd, |props| props.reply_timeout)
+        .unwrap_or(DEFAULT_REPLY_TIMEOUT);
+    let answer = pending.wait(timeout);
+    if answer != size {
+        let (left, top, right, bottom) = decode::sizing_window_rect(rect, edge, answer, d);
+        *rc = RECT { left, top, right, bottom };
+    }
     DefWindowProcW(hwnd, WM_SIZING, wparam, lparam)
 }
 
-unsafe fn on_size(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
-    match wparam.0 as u32 {
-        SIZE_MINIMIZED => {
+unsafe fn on_size(hwnd: HWND, kind: SizeKind, size: PhysicalSize<u32>) -> LRESULT {
+    match kind {
+        SizeKind::Minimized => {
             Context::send_event(hwnd, Event::Minimized);
-            Context::set_window_property(hwnd, |props| props.minimized = true);
+            win32::set_window_property(hwnd, |props| props.minimized = true);
         }
-        SIZE_MAXIMIZED => {
-            let size = lparam_to_size(lparam);
+        SizeKind::Maximized => {
             Context::send_event(hwnd, Event::Maximized(events::Maximized { size }));
-            Context::set_window_property(hwnd, |props| props.maximized = true);
+            win32::set_window_property(hwnd, |props| props.maximized = true);
         }
-        SIZE_RESTORED => {
+        SizeKind::Restored => {
             let min_or_max =
-                Context::get_window_property(hwnd, |props| props.minimized | props.maximized);
+                win32::get_window_property(hwnd, |props| props.minimized | props.maximized);
             if min_or_max.unwrap_or(false) {
-                let size = lparam_to_size(lparam);
                 Context::send_event(hwnd, Event::Restored(events::Restored { size }));
-                Context::set_window_property(hwnd, |props| {
+                win32::set_window_property(hwnd, |props| {
                     props.minimized = false;
                     props.maximized = false;
                 });
             }
         }
-        _ => {}
+        SizeKind::Other => {}
     }
     LRESULT(0)
 }
@@ -340,8 +304,7 @@ unsafe fn on_exit_size_move(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESU
     DefWindowProcW(hwnd, WM_EXITSIZEMOVE, wparam, lparam)
 }
 
-unsafe fn on_activate(hwnd: HWND, wparam: WPARAM, _lparam: LPARAM) -> LRESULT {
-    let active = (wparam.0 as u32 & (WA_ACTIVE | WA_CLICKACTIVE)) != 0;
+unsafe fn on_activate(hwnd: HWND, active: bool) -> LRESULT {
     if active {
         Context::send_event(hwnd, Event::Activated);
     } else {
@@ -350,7 +313,7 @@ unsafe fn on_activate(hwnd: HWND, wparam: WPARAM, _lparam: LPARAM) -> LRESULT {
     LRESULT(0)
 }
 
-unsafe fn on_dpi_changed(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+unsafe fn on_dpi_changed(hwnd: HWND, new_dpi: u32, lparam: LPARAM) -> LRESULT {
     let rc = *(lparam.0 as *const RECT);
     SetWindowPos(
         hwnd,
@@ -361,14 +324,13 @@ unsafe fn on_dpi_changed(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT
         rc.bottom - rc.top,
         SWP_NOZORDER | SWP_NOACTIVATE,
     );
-    let new_dpi = hiword(wparam.0 as _) as u32;
     Context::send_event(hwnd, Event::DpiChanged(events::DpiChanged { new_dpi }));
     LRESULT(0)
 }
 
-unsafe fn on_get_dpi_scaled_size(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+unsafe fn on_get_dpi_scaled_size(hwnd: HWND, new_dpi: u32, lparam: LPARAM) -> LRESULT {
     let prev_dpi = GetDpiForWindow(hwnd) as i32;
-    let next_dpi = wparam.0 as i32;
+    let next_dpi = new_dpi as i32;
     let rc = get_client_rect(hwnd);
     let size = PhysicalSize::new(
         ((rc.right - rc.left) * next_dpi / prev_dpi) as u32,
@@ -387,8 +349,8 @@ unsafe fn on_get_dpi_scaled_size(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) ->
     LRESULT(1)
 }
 
-unsafe fn on_drop_files(hwnd: HWND, wparam: WPARAM, _lparam: LPARAM) -> LRESULT {
-    let hdrop = HDROP(wparam.0 as _);
+unsafe fn on_drop_files(hwnd: HWND, hdrop: usize) -> LRESULT {
+    let hdrop = HDROP(hdrop as _);
     let file_count = DragQueryFileW(hdrop, u32::MAX, None);
     let mut paths = Vec::with_capacity(file_count as _);
     let mut buffer = Vec::new();
@@ -418,27 +380,46 @@ unsafe fn on_nc_create(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     DefWindowProcW(hwnd, WM_NCCREATE, wparam, lparam)
 }
 
+fn on_close(hwnd: HWND) -> LRESULT {
+    Context::request_close(hwnd);
+    LRESULT(0)
+}
+
 unsafe fn on_destroy(hwnd: HWND) -> LRESULT {
-    Context::send_event(hwnd, Event::Closed);
-    let obj = Context::remove_window(hwnd);
-    if Context::is_empty() {
-        if let Some(obj) = obj {
-            obj.sender.send(Event::Quit).unwrap_or(());
-            if let Some(waker) = obj.waker {
-                waker.wake();
-            }
-            if let Some(sender) = obj.raw_input_sender {
-                sender.send(raw_input::RawInputEvent::Quit).unwrap_or(());
-            }
-            if let Some(waker) = obj.raw_input_waker {
-                waker.wake();
-            }
-        }
+    win32::remove_window_properties(hwnd);
+    if Context::close_window(hwnd) {
         PostQuitMessage(0);
     }
     LRESULT(0)
 }
 
+unsafe fn call_message_hook(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> Option<LRESULT> {
+    // The hook is taken out while it runs because it may send messages to the window.
+    let mut hook = win32::take_message_hook(hwnd)?;
+    let ret = hook(hwnd, msg, wparam, lparam);
+    win32::put_message_hook(hwnd, hook);
+    ret
+}
+
+unsafe fn on_other_message(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if win32::get_window_property(hwnd, |props| props.forward_messages).unwrap_or(false) {
+        Context::send_event(
+            hwnd,
+            Event::Message(events::Message {
+                msg,
+                wparam: wparam.0,
+                lparam: lparam.0,
+            }),
+        );
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
 pub(crate) extern "system" fn window_proc(
     hwnd: HWND,
     msg: u32,
@@ -446,89 +427,58 @@ pub(crate) extern "system" fn window_proc(
     lparam: LPARAM,
 ) -> LRESULT {
     let ret = std::panic::catch_unwind(|| unsafe {
-        match msg {
-            WM_INPUT => raw_input::on_input(hwnd, wparam, lparam),
-            WM_PAINT => on_paint(hwnd),
-            WM_MOUSEMOVE => on_mouse_move(hwnd, wparam, lparam),
-            WM_SETCURSOR => on_set_cursor(hwnd, wparam, lparam),
-            WM_MOUSELEAVE => on_mouse_leave(hwnd, wparam, lparam),
-            WM_LBUTTONDOWN => on_mouse_input(
-                hwnd,
-                MouseButton::Left,
-                ButtonState::Pressed,
-                wparam,
-                lparam,
-            ),
-            WM_RBUTTONDOWN => on_mouse_input(
-                hwnd,
-                MouseButton::Right,
-                ButtonState::Pressed,
-                wparam,
-                lparam,
-            ),
-            WM_MBUTTONDOWN => on_mouse_input(
-                hwnd,
-                MouseButton::Middle,
-                ButtonState::Pressed,
-                wparam,
-                lparam,
-            ),
-            WM_XBUTTONDOWN => on_mouse_input(
-                hwnd,
-                wparam_to_button(wparam),
-                ButtonState::Pressed,
-                wparam,
-                lparam,
-            ),
-            WM_LBUTTONUP => on_mouse_input(
-                hwnd,
-                MouseButton::Left,
-                ButtonState::Released,
-                wparam,
-                lparam,
-            ),
-            WM_RBUTTONUP => on_mouse_input(
-                hwnd,
-                MouseButton::Right,
-                ButtonState::Released,
-                wparam,
-                lparam,
-            ),
-            WM_MBUTTONUP => on_mouse_input(
-                hwnd,
-                MouseButton::Middle,
-                ButtonState::Released,
-                wparam,
-                lparam,
-            ),
-            WM_XBUTTONUP => on_mouse_input(
-                hwnd,
-                wparam_to_button(wparam),
-                ButtonState::Released,
-                wparam,
-                lparam,
-            ),
-            WM_MOUSEWHEEL => on_mouse_wheel(hwnd, MouseWheelAxis::Vertical, wparam, lparam),
-            WM_MOUSEHWHEEL => on_mouse_wheel(hwnd, MouseWheelAxis::Horizontal, wparam, lparam),
-            WM_KEYDOWN => on_key_input(hwnd, KeyState::Pressed, wparam, lparam),
-            WM_KEYUP => on_key_input(hwnd, KeyState::Released, wparam, lparam),
-            WM_CHAR => on_char(hwnd, wparam, lparam),
-            WM_IME_SETCONTEXT => on_ime_set_context(hwnd, wparam, lparam),
-            WM_IME_STARTCOMPOSITION => on_ime_start_composition(hwnd, wparam, lparam),
-            WM_IME_COMPOSITION => on_ime_composition(hwnd, wparam, lparam),
-            WM_IME_ENDCOMPOSITION => on_ime_end_composition(hwnd, wparam, lparam),
-            WM_SIZING => on_sizing(hwnd, wparam, lparam),
-            WM_SIZE => on_size(hwnd, wparam, lparam),
-            WM_WINDOWPOSCHANGED => on_window_pos_changed(hwnd, wparam, lparam),
-            WM_EXITSIZEMOVE => on_exit_size_move(hwnd, wparam, lparam),
-            WM_ACTIVATE => on_activate(hwnd, wparam, lparam),
-            WM_DPICHANGED => on_dpi_changed(hwnd, wparam, lparam),
-            WM_GETDPISCALEDSIZE => on_get_dpi_scaled_size(hwnd, wparam, lparam),
-            WM_DROPFILES => on_drop_files(hwnd, wparam, lparam),
-            WM_NCCREATE => on_nc_create(hwnd, wparam, lparam),
-            WM_DESTROY => on_destroy(hwnd),
-            WM_INPUT_DEVICE_CHANGE => raw_input::on_input_device_change(hwnd, wparam, lparam),
-            _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+        let _time = Context::set_message_time(Some(GetMessageTime() as u32));
+        let decoded = decode(msg, wparam.0, lparam.0);
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::trace_span!("window_proc", hwnd = hwnd.0, msg, kind = ?decoded).entered();
+        if let Some(ret) = call_message_hook(hwnd, msg, wparam, lparam) {
+            return ret;
+        }
+        match decoded {
+            DecodedMessage::Input => raw_input::on_input(hwnd, wparam, lparam),
+            DecodedMessage::Paint => on_paint(hwnd),
+            DecodedMessage::MouseMove(mouse_state) => on_mouse_move(hwnd, mouse_state),
+            DecodedMessage::SetCursor { client } => on_set_cursor(hwnd, client, wparam, lparam),
+            DecodedMessage::MouseLeave { buttons } => on_mouse_leave(hwnd, buttons),
+            DecodedMessage::MouseInput {
+                button,
+                button_state,
+                mouse_state,
+            } => on_mouse_input(hwnd, button, button_state, mouse_state),
+            DecodedMessage::MouseWheel {
+                axis,
+                distance,
+                mouse_state,
+            } => on_mouse_wheel(hwnd, axis, distance, mouse_state),
+            DecodedMessage::KeyInput {
+                vkey,
+                scan_code,
+                key_state,
+                prev_pressed,
+            } => on_key_input(hwnd, vkey, scan_code, key_state, prev_pressed),
+            DecodedMessage::CharInput(c) => on_char(hwnd, c),
+            DecodedMessage::ImeSetContext => on_ime_set_context(hwnd, wparam, lparam),
+            DecodedMessage::ImeStartComposition => on_ime_start_composition(hwnd, wparam, lparam),
+            DecodedMessage::ImeComposition => on_ime_composition(hwnd, wparam, lparam),
+            DecodedMessage::ImeEndComposition => on_ime_end_composition(hwnd, wparam, lparam),
+            DecodedMessage::Sizing(edge) => on_sizing(hwnd, edge, wparam, lparam),
+            DecodedMessage::Size { kind, size } => on_size(hwnd, kind, size),
+            DecodedMessage::WindowPosChanged => on_window_pos_changed(hwnd, wparam, lparam),
+            DecodedMessage::ExitSizeMove => on_exit_size_move(hwnd, wparam, lparam),
+            DecodedMessage::Activate { active } => on_activate(hwnd, active),
+            DecodedMessage::DpiChanged { new_dpi } => on_dpi_changed(hwnd, new_dpi, lparam),
+            DecodedMessage::GetDpiScaledSize { new_dpi } => {
+                on_get_dpi_scaled_size(hwnd, new_dpi, lparam)
+            }
+            DecodedMessage::DropFiles { hdrop } => on_drop_files(hwnd, hdrop),
+            DecodedMessage::NcCreate => on_nc_create(hwnd, wparam, lparam),
+            DecodedMessage::Close => on_close(hwnd),
+            DecodedMessage::Destroy => on_destroy(hwnd),
+            DecodedMessage::InputDeviceChange => {
+                raw_input::on_input_device_change(hwnd, wparam, lparam)
+            }
+            DecodedMessage::Other => on_other_message(hwnd, msg, wparam, lparam),
         }
     });
     ret.unwrap_or_else(|e| {
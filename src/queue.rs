@@ -0,0 +1,572 @@
+//! The queues between a backend and `EventReceiver`/`RawInputEventRecevier`.
+
+use crate::raw_input::{InputData, MousePosition, RawInputEvent};
+use crate::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+/// How events which are not received yet are queued.
+///
+/// The default is an unbounded queue without coalescing. Discrete input such as `MouseInput`
+/// and `KeyInput` is never dropped nor merged.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct QueuePolicy {
+    /// The maximum number of queued events, or unbounded when `None`.
+    ///
+    /// When the queue is full, the oldest `CursorMoved`, `Resizing` or relative raw mouse motion
+    /// is dropped to make room. The queue grows beyond the limit when it has none of them.
+    pub max_depth: Option<usize>,
+    /// Replaces the last queued `CursorMoved` with a new one.
+    pub coalesce_cursor_moved: bool,
+    /// Replaces the last queued `Resizing` with a new one.
+    pub coalesce_resizing: bool,
+    /// Merges a new `Draw` with the queued one into a `Draw` of the union of their rects.
+    pub union_draw: bool,
+    /// Adds relative raw mouse motion to the last queued one of the same device.
+    pub sum_raw_mouse_motion: bool,
+}
+
+impl QueuePolicy {
+    /// Bounded by `max_depth` with every kind of coalescing.
+    #[inline]
+    pub fn bounded(max_depth: usize) -> Self {
+        Self {
+            max_depth: Some(max_depth),
+            coalesce_cursor_moved: true,
+            coalesce_resizing: true,
+            union_draw: true,
+            sum_raw_mouse_motion: true,
+        }
+    }
+}
+
+const LATENCY_BUCKETS: usize = 24;
+
+/// Counts of latencies in buckets whose upper bounds are powers of two microseconds.
+///
+/// The last bucket has no upper bound.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros();
+        let i = (u128::BITS - micros.leading_zeros()) as usize;
+        self.counts[i.min(LATENCY_BUCKETS - 1)] += 1;
+    }
+
+    /// Returns the exclusive upper bound and the count of each bucket. The bound of the last
+    /// bucket is `Duration::MAX`.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts.iter().enumerate().map(|(i, &count)| {
+            let bound = if i == LATENCY_BUCKETS - 1 {
+                Duration::MAX
+            } else {
+                Duration::from_micros(1 << i)
+            };
+            (bound, count)
+        })
+    }
+
+    /// Returns the number of recorded latencies.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the upper bound of the bucket which contains the `q` quantile, or `None` when
+    /// nothing has been recorded.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut sum = 0;
+        self.buckets().find_map(|(bound, n)| {
+            sum += n;
+            (sum >= rank).then_some(bound)
+        })
+    }
+}
+
+/// Statistics of the queue of a receiver.
+#[derive(Clone, Debug, Default)]
+pub struct QueueStats {
+    /// The number of events sent to the queue per kind.
+    pub enqueued: BTreeMap<&'static str, u64>,
+    /// The number of events per kind which were dropped from the full queue or merged into
+    /// another event by `QueuePolicy`.
+    pub dropped: BTreeMap<&'static str, u64>,
+    /// The number of events which are not received yet.
+    pub depth: usize,
+    /// The time from the arrival of the message which caused an event until the event was
+    /// received. See `Timestamp::arrival`.
+    pub latency: LatencyHistogram,
+}
+
+pub(crate) trait Coalesce: Sized {
+    /// Merges `item` into `queue`, or returns it when it has to be pushed as is.
+    fn coalesce(
+        queue: &mut VecDeque<(Self, Timestamp)>,
+        item: (Self, Timestamp),
+        policy: &QueuePolicy,
+    ) -> Option<(Self, Timestamp)>;
+
+    /// Returns `true` when the event may be dropped from a full queue.
+    fn is_droppable(&self) -> bool;
+
+    /// Returns the key of `QueueStats`.
+    fn kind(&self) -> &'static str;
+}
+
+fn union(a: PhysicalRect<i32>, b: PhysicalRect<i32>) -> PhysicalRect<i32> {
+    let (a_end, b_end) = (a.endpoint(), b.endpoint());
+    PhysicalRect::from_positions(
+        (
+            a.position.x.min(b.position.x),
+            a.position.y.min(b.position.y),
+        ),
+        (a_end.x.max(b_end.x), a_end.y.max(b_end.y)),
+    )
+}
+
+impl Coalesce for Event {
+    fn coalesce(
+        queue: &mut VecDeque<(Self, Timestamp)>,
+        item: (Self, Timestamp),
+        policy: &QueuePolicy,
+    ) -> Option<(Self, Timestamp)> {
+        match (queue.back_mut(), &item.0) {
+            (Some(last @ (Event::CursorMoved(_), _)), Event::CursorMoved(_))
+                if policy.coalesce_cursor_moved =>
+            {
+                *last = item;
+                return None;
+            }
+            (Some(last @ (Event::Resizing(_), _)), Event::Resizing(_))
+                if policy.coalesce_resizing =>
+            {
+                *last = item;
+                return None;
+            }
+            _ => {}
+        }
+        let (Event::Draw(draw), timestamp) = item else { return Some(item) };
+        if !policy.union_draw {
+            return Some((Event::Draw(draw), timestamp));
+        }
+        // The merged `Draw` moves to the back so that it follows the events which caused it.
+        let prev = queue
+            .iter()
+            .position(|(event, _)| matches!(event, Event::Draw(_)))
+            .and_then(|i| queue.remove(i));
+        let invalid_rect = match prev {
+            Some((Event::Draw(prev), _)) => union(prev.invalid_rect, draw.invalid_rect),
+            _ => draw.invalid_rect,
+        };
+        Some((Event::Draw(events::Draw { invalid_rect }), timestamp))
+    }
+
+    #[inline]
+    fn is_droppable(&self) -> bool {
+        matches!(self, Event::CursorMoved(_) | Event::Resizing(_))
+    }
+
+    #[inline]
+    fn kind(&self) -> &'static str {
+        Event::kind(self)
+    }
+}
+
+fn relative_motion(event: &mut RawInputEvent) -> Option<&mut raw_input::MouseData> {
+    match event {
+        RawInputEvent::Input(InputData::Mouse(m))
+            if matches!(m.position, MousePosition::Relative(_))
+                && m.wheel.is_none()
+                && m.hwheel.is_none()
+                && m.buttons.is_empty() =>
+        {
+            Some(m)
+        }
+        _ => None,
+    }
+}
+
+impl Coalesce for RawInputEvent {
+    fn coalesce(
+        queue: &mut VecDeque<(Self, Timestamp)>,
+        mut item: (Self, Timestamp),
+        policy: &QueuePolicy,
+    ) -> Option<(Self, Timestamp)> {
+        if !policy.sum_raw_mouse_motion {
+            return Some(item);
+        }
+        let Some((last, last_timestamp)) = queue.back_mut() else { return Some(item) };
+        let (Some(last), Some(m)) = (relative_motion(last), relative_motion(&mut item.0)) else { return Some(item) };
+        if last.handle != m.handle {
+            return Some(item);
+        }
+        let (MousePosition::Relative(p), MousePosition::Relative(d)) =
+            (&mut last.position, m.position)
+        else { return Some(item) };
+        p.x += d.x;
+        p.y += d.y;
+        last.extra = m.extra;
+        *last_timestamp = item.1;
+        None
+    }
+
+    #[inline]
+    fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            RawInputEvent::Input(InputData::Mouse(m))
+                if matches!(m.position, MousePosition::Relative(_))
+                    && m.wheel.is_none()
+                    && m.hwheel.is_none()
+                    && m.buttons.is_empty()
+        )
+    }
+
+    #[inline]
+    fn kind(&self) -> &'static str {
+        RawInputEvent::kind(self)
+    }
+}
+
+impl Coalesce for (WindowId, Event) {
+    #[inline]
+    fn coalesce(
+        _queue: &mut VecDeque<(Self, Timestamp)>,
+        item: (Self, Timestamp),
+        _policy: &QueuePolicy,
+    ) -> Option<(Self, Timestamp)> {
+        Some(item)
+    }
+
+    #[inline]
+    fn is_droppable(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn kind(&self) -> &'static str {
+        self.1.kind()
+    }
+}
+
+#[cfg(feature = "inspector")]
+impl Coalesce for (WindowId, RawInputEvent) {
+    #[inline]
+    fn coalesce(
+        _queue: &mut VecDeque<(Self, Timestamp)>,
+        item: (Self, Timestamp),
+        _policy: &QueuePolicy,
+    ) -> Option<(Self, Timestamp)> {
+        Some(item)
+    }
+
+    #[inline]
+    fn is_droppable(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn kind(&self) -> &'static str {
+        self.1.kind()
+    }
+}
+
+struct Shared<T> {
+    events: VecDeque<(T, Timestamp)>,
+    policy: QueuePolicy,
+    closed: bool,
+    // Whether the receiver has been polled, which tells if the app reads this queue.
+    polled: bool,
+    waker: Option<Waker>,
+    stats: QueueStats,
+}
+
+impl<T> Shared<T> {
+    fn pop(&mut self) -> Option<(T, Timestamp)> {
+        let item = self.events.pop_front()?;
+        self.stats.latency.record(item.1.arrival.elapsed());
+        Some(item)
+    }
+}
+
+fn count(counts: &mut BTreeMap<&'static str, u64>, kind: &'static str) {
+    *counts.entry(kind).or_insert(0) += 1;
+}
+
+pub(crate) struct Sender<T>(Arc<Mutex<Shared<T>>>);
+
+impl<T: Coalesce> Sender<T> {
+    /// Queues `event`. It is discarded when the receiver has been dropped.
+    ///
+    /// Returns the depth of the queue after `event` was queued, coalesced or dropped.
+    pub fn send(&self, event: T, timestamp: Timestamp) -> usize {
+        if !self.is_connected() {
+            return 0;
+        }
+        let (depth, waker) = {
+            let mut shared = self.0.lock().unwrap();
+            let shared = &mut *shared;
+            let kind = event.kind();
+            count(&mut shared.stats.enqueued, kind);
+            let len = shared.events.len();
+            let item = T::coalesce(&mut shared.events, (event, timestamp), &shared.policy);
+            // A `Draw` merged into the new one is removed from the queue.
+            for _ in shared.events.len()..len {
+                count(&mut shared.stats.dropped, kind);
+            }
+            let Some(item) = item else {
+                count(&mut shared.stats.dropped, kind);
+                return shared.events.len();
+            };
+            if shared
+                .policy
+                .max_depth
+                .is_some_and(|max_depth| shared.events.len() >= max_depth)
+            {
+                if let Some(i) = shared.events.iter().position(|(e, _)| e.is_droppable()) {
+                    let (dropped, _) = shared.events.remove(i).unwrap();
+                    count(&mut shared.stats.dropped, dropped.kind());
+                } else if item.0.is_droppable() {
+                    count(&mut shared.stats.dropped, kind);
+                    return shared.events.len();
+                }
+            }
+            shared.events.push_back(item);
+            (shared.events.len(), shared.waker.take())
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        depth
+    }
+}
+
+impl<T> Sender<T> {
+    /// Returns `false` when the receiver has been dropped.
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        Arc::strong_count(&self.0) > 1
+    }
+
+    /// Returns `true` when the receiver is connected and has been polled.
+    #[inline]
+    pub fn is_polled(&self) -> bool {
+        self.is_connected() && self.0.lock().unwrap().polled
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut shared = self.0.lock().unwrap();
+            shared.closed = true;
+            shared.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) enum TryRecvError {
+    Empty,
+    Disconnected,
+}
+
+pub(crate) struct Receiver<T>(Arc<Mutex<Shared<T>>>);
+
+impl<T> Receiver<T> {
+    pub fn try_recv(&mut self) -> std::result::Result<(T, Timestamp), TryRecvError> {
+        let mut shared = self.0.lock().unwrap();
+        shared.polled = true;
+        match shared.pop() {
+            Some(item) => Ok(item),
+            None if shared.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Returns `Ready(None)` when the queue is empty and the sender has been dropped.
+    pub fn poll_recv(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Option<(T, Timestamp)>> {
+        let mut shared = self.0.lock().unwrap();
+        shared.polled = true;
+        match shared.pop() {
+            Some(item) => Poll::Ready(Some(item)),
+            None if shared.closed => Poll::Ready(None),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    #[inline]
+    pub fn policy(&self) -> QueuePolicy {
+        self.0.lock().unwrap().policy
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let shared = self.0.lock().unwrap();
+        QueueStats {
+            depth: shared.events.len(),
+            ..shared.stats.clone()
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // Events must not outlive the receiver. `ImeStartComposition` reports to the IME and
+        // `CloseRequested` destroys the window when they are dropped, so they are dropped after
+        // unlocking.
+        let events = std::mem::take(&mut self.0.lock().unwrap().events);
+        drop(events);
+    }
+}
+
+pub(crate) fn channel<T>(policy: QueuePolicy) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Mutex::new(Shared {
+        events: VecDeque::new(),
+        policy,
+        closed: false,
+        polled: false,
+        waker: None,
+        stats: QueueStats::default(),
+    }));
+    (Sender(shared.clone()), Receiver(shared))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor_moved(x: i32) -> Event {
+        Event::CursorMoved(events::CursorMoved {
+            mouse_state: MouseState {
+                position: PhysicalPosition::new(x, 0),
+                buttons: MouseButtons::new(),
+            },
+        })
+    }
+
+    fn key_input() -> Event {
+        Event::KeyInput(events::KeyInput {
+            key_code: KeyCode::new(VirtualKey::A, ScanCode(0x1e)),
+            key_state: KeyState::Pressed,
+            prev_pressed: false,
+        })
+    }
+
+    fn draw(x: i32, y: i32, width: i32, height: i32) -> Event {
+        Event::Draw(events::Draw {
+            invalid_rect: PhysicalRect::new((x, y), (width, height)),
+        })
+    }
+
+    fn drain<T>(rx: &mut Receiver<T>) -> Vec<T> {
+        std::iter::from_fn(|| rx.try_recv().ok().map(|(event, _)| event)).collect()
+    }
+
+    fn now() -> Timestamp {
+        let now = std::time::Instant::now();
+        Timestamp {
+            message_time: None,
+            instant: now,
+            arrival: now,
+        }
+    }
+
+    #[test]
+    fn coalescing() {
+        let (tx, mut rx) = channel(QueuePolicy::bounded(3));
+        tx.send(cursor_moved(1), now());
+        tx.send(cursor_moved(2), now());
+        tx.send(key_input(), now());
+        tx.send(draw(0, 0, 10, 10), now());
+        tx.send(cursor_moved(3), now());
+        tx.send(draw(20, 5, 10, 10), now());
+        let events = drain(&mut rx);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], Event::KeyInput(_)));
+        assert!(matches!(&events[1], Event::CursorMoved(m) if m.mouse_state.position.x == 3));
+        assert!(
+            matches!(&events[2], Event::Draw(d) if d.invalid_rect == PhysicalRect::new((0, 0), (30, 15)))
+        );
+        for _ in 0..5 {
+            tx.send(key_input(), now());
+        }
+        tx.send(cursor_moved(4), now());
+        let events = drain(&mut rx);
+        assert_eq!(events.len(), 5);
+        assert!(events.iter().all(|e| matches!(e, Event::KeyInput(_))));
+        drop(tx);
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+    }
+
+    #[test]
+    fn default_keeps_every_event() {
+        let (tx, mut rx) = channel(QueuePolicy::default());
+        tx.send(cursor_moved(1), now());
+        tx.send(cursor_moved(2), now());
+        tx.send(draw(0, 0, 1, 1), now());
+        tx.send(draw(0, 0, 1, 1), now());
+        assert_eq!(drain(&mut rx).len(), 4);
+    }
+
+    #[test]
+    fn stats() {
+        let (tx, mut rx) = channel(QueuePolicy::bounded(2));
+        let depths = [
+            tx.send(cursor_moved(1), now()),
+            tx.send(cursor_moved(2), now()),
+            tx.send(key_input(), now()),
+            tx.send(key_input(), now()),
+            tx.send(draw(0, 0, 1, 1), now()),
+            tx.send(draw(0, 0, 1, 1), now()),
+        ];
+        // `send` returns the depth after coalescing and dropping.
+        assert_eq!(depths, [1, 1, 2, 2, 3, 3]);
+        let stats = rx.stats();
+        assert_eq!(stats.enqueued["CursorMoved"], 2);
+        assert_eq!(stats.enqueued["KeyInput"], 2);
+        assert_eq!(stats.enqueued["Draw"], 2);
+        // The first `CursorMoved` is merged, and the second is dropped for the second `KeyInput`.
+        assert_eq!(stats.dropped["CursorMoved"], 2);
+        assert_eq!(stats.dropped["Draw"], 1);
+        assert!(!stats.dropped.contains_key("KeyInput"));
+        assert_eq!(stats.depth, 3);
+        assert_eq!(stats.latency.count(), 0);
+        drain(&mut rx);
+        let stats = rx.stats();
+        assert_eq!(stats.depth, 0);
+        assert_eq!(stats.latency.count(), 3);
+    }
+
+    #[test]
+    fn latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        histogram.record(Duration::ZERO);
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_secs(3600));
+        let buckets = histogram.buckets().collect::<Vec<_>>();
+        assert_eq!(buckets[0], (Duration::from_micros(1), 1));
+        assert_eq!(buckets[2], (Duration::from_micros(4), 2));
+        assert_eq!(buckets[LATENCY_BUCKETS - 1], (Duration::MAX, 1));
+        assert_eq!(histogram.quantile(0.0), Some(Duration::from_micros(1)));
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_micros(4)));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::MAX));
+    }
+}
@@ -1,6 +1,9 @@
 use crate::*;
+#[cfg(windows)]
 use std::cell::RefCell;
+#[cfg(windows)]
 use windows::core::HSTRING;
+#[cfg(windows)]
 use windows::Win32::{
     Devices::HumanInterfaceDevice::*,
     Foundation::{CloseHandle, E_FAIL, HANDLE, HWND, LPARAM, LRESULT, WPARAM},
@@ -12,13 +15,25 @@ use windows::Win32::{
     UI::Input::*,
     UI::WindowsAndMessaging::{
         DefWindowProcW, GIDC_ARRIVAL, GIDC_REMOVAL, RIM_INPUT, RIM_INPUTSINK, RI_KEY_BREAK,
-        RI_MOUSE_BUTTON_4_DOWN, RI_MOUSE_BUTTON_4_UP, RI_MOUSE_BUTTON_5_DOWN, RI_MOUSE_BUTTON_5_UP,
-        RI_MOUSE_HWHEEL, RI_MOUSE_LEFT_BUTTON_DOWN, RI_MOUSE_LEFT_BUTTON_UP,
-        RI_MOUSE_MIDDLE_BUTTON_DOWN, RI_MOUSE_MIDDLE_BUTTON_UP, RI_MOUSE_RIGHT_BUTTON_DOWN,
-        RI_MOUSE_RIGHT_BUTTON_UP, RI_MOUSE_WHEEL, WM_INPUT, WM_INPUT_DEVICE_CHANGE,
+        WM_INPUT, WM_INPUT_DEVICE_CHANGE,
     },
 };
 
+const RI_MOUSE_LEFT_BUTTON_DOWN: u32 = 0x0001;
+const RI_MOUSE_LEFT_BUTTON_UP: u32 = 0x0002;
+const RI_MOUSE_RIGHT_BUTTON_DOWN: u32 = 0x0004;
+const RI_MOUSE_RIGHT_BUTTON_UP: u32 = 0x0008;
+const RI_MOUSE_MIDDLE_BUTTON_DOWN: u32 = 0x0010;
+const RI_MOUSE_MIDDLE_BUTTON_UP: u32 = 0x0020;
+const RI_MOUSE_BUTTON_4_DOWN: u32 = 0x0040;
+const RI_MOUSE_BUTTON_4_UP: u32 = 0x0080;
+const RI_MOUSE_BUTTON_5_DOWN: u32 = 0x0100;
+const RI_MOUSE_BUTTON_5_UP: u32 = 0x0200;
+#[cfg(windows)]
+const RI_MOUSE_WHEEL: u32 = 0x0400;
+#[cfg(windows)]
+const RI_MOUSE_HWHEEL: u32 = 0x0800;
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
 pub struct Limit {
     pub min: i64,
@@ -31,6 +46,7 @@ pub enum WindowState {
     Background,
 }
 
+#[cfg(windows)]
 impl From<WPARAM> for WindowState {
     fn from(src: WPARAM) -> Self {
         match src.0 as _ {
@@ -50,7 +66,8 @@ pub enum DeviceType {
 }
 
 #[derive(Clone, Debug)]
-pub struct DeviceHandle(HANDLE);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceHandle(isize);
 
 impl PartialEq for DeviceHandle {
     #[inline]
@@ -79,14 +96,16 @@ impl Device {
         self.ty
     }
 
+    #[cfg(windows)]
     #[inline]
     pub fn raw_handle(&self) -> HANDLE {
-        self.handle.0
+        HANDLE(self.handle.0)
     }
 
+    #[cfg(windows)]
     #[inline]
     pub fn get_info(&self) -> Result<DeviceInfo> {
-        unsafe { get_device_info(self.handle.0).map_err(|e| e.into()) }
+        unsafe { get_device_info(HANDLE(self.handle.0)).map_err(|e| e.into()) }
     }
 }
 
@@ -152,7 +171,8 @@ pub enum DeviceInfo {
     GamePad(GamePadInfo),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyboardData {
     pub handle: DeviceHandle,
     pub key_code: KeyCode,
@@ -161,21 +181,30 @@ pub struct KeyboardData {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Relative;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Absolute;
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MousePosition {
     Relative(Position<i32, Relative>),
     Absolute(Position<i32, Absolute>),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouseButtonStates(u32);
 
 impl MouseButtonStates {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
     #[inline]
     pub fn contains(&self, button: MouseButton, state: ButtonState) -> bool {
         match state {
@@ -199,7 +228,8 @@ impl MouseButtonStates {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouseData {
     pub handle: DeviceHandle,
     pub position: MousePosition,
@@ -210,9 +240,11 @@ pub struct MouseData {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Buttons(u64);
 
 impl Buttons {
+    #[cfg(windows)]
     fn new() -> Self {
         Self(0)
     }
@@ -229,7 +261,8 @@ impl Buttons {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GamePadData {
     pub handle: DeviceHandle,
     pub x: i32,
@@ -242,10 +275,11 @@ pub struct GamePadData {
     pub buttons: Buttons,
 }
 
+#[cfg(windows)]
 impl GamePadData {
     fn new(handle: HANDLE) -> Self {
         Self {
-            handle: DeviceHandle(handle),
+            handle: DeviceHandle(handle.0),
             x: 0,
             y: 0,
             z: 0,
@@ -258,7 +292,8 @@ impl GamePadData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InputData {
     Keyboard(KeyboardData),
     Mouse(MouseData),
@@ -266,11 +301,13 @@ pub enum InputData {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DeviceChangeState {
     Arrival,
     Removal,
 }
 
+#[cfg(windows)]
 unsafe fn get_preparsed_data(handle: HANDLE, dest: &mut Vec<u8>) -> windows::core::Result<()> {
     let mut len = 0;
     let ret = GetRawInputDeviceInfoW(handle, RIDI_PREPARSEDDATA, None, &mut len);
@@ -291,6 +328,7 @@ unsafe fn get_preparsed_data(handle: HANDLE, dest: &mut Vec<u8>) -> windows::cor
     Ok(())
 }
 
+#[cfg(windows)]
 unsafe fn get_device_name(handle: HANDLE) -> windows::core::Result<HSTRING> {
     let mut len = 0;
     let ret = GetRawInputDeviceInfoW(handle, RIDI_DEVICENAME, None, &mut len);
@@ -310,6 +348,7 @@ unsafe fn get_device_name(handle: HANDLE) -> windows::core::Result<HSTRING> {
     Ok(HSTRING::from_wide(&buffer))
 }
 
+#[cfg(windows)]
 unsafe fn get_device_product_string(interface: &HSTRING) -> windows::core::Result<String> {
     let handle = CreateFileW(
         interface,
@@ -334,6 +373,7 @@ unsafe fn get_device_product_string(interface: &HSTRING) -> windows::core::Resul
     Ok(String::from_utf16_lossy(&buffer[..term]))
 }
 
+#[cfg(windows)]
 unsafe fn get_raw_input_device_info(handle: HANDLE) -> windows::core::Result<RID_DEVICE_INFO> {
     let mut len = std::mem::size_of::<RID_DEVICE_INFO>() as u32;
     let mut info = RID_DEVICE_INFO {
@@ -355,6 +395,7 @@ unsafe fn get_raw_input_device_info(handle: HANDLE) -> windows::core::Result<RID
     Ok(info)
 }
 
+#[cfg(windows)]
 unsafe fn get_device_type(handle: HANDLE) -> windows::core::Result<DeviceType> {
     let info = get_raw_input_device_info(handle)?;
     match info.dwType {
@@ -375,6 +416,7 @@ unsafe fn get_device_type(handle: HANDLE) -> windows::core::Result<DeviceType> {
     }
 }
 
+#[cfg(windows)]
 unsafe fn get_button_caps(
     preparsed: &[u8],
     mut len: u16,
@@ -390,6 +432,7 @@ unsafe fn get_button_caps(
     Ok(caps)
 }
 
+#[cfg(windows)]
 unsafe fn get_value_caps(
     preparsed: &[u8],
     mut len: u16,
@@ -405,6 +448,7 @@ unsafe fn get_value_caps(
     Ok(caps)
 }
 
+#[cfg(windows)]
 unsafe fn get_device_info(handle: HANDLE) -> windows::core::Result<DeviceInfo> {
     let info = get_raw_input_device_info(handle)?;
     let data = match info.dwType {
@@ -490,6 +534,7 @@ unsafe fn get_device_info(handle: HANDLE) -> windows::core::Result<DeviceInfo> {
     Ok(data)
 }
 
+#[cfg(windows)]
 pub fn get_device_list() -> Result<Vec<Device>> {
     unsafe {
         let mut len = 0;
@@ -514,7 +559,7 @@ pub fn get_device_list() -> Result<Vec<Device>> {
             .iter()
             .filter_map(|device| {
                 let device = Device {
-                    handle: DeviceHandle(device.hDevice),
+                    handle: DeviceHandle(device.hDevice.0),
                     ty: get_device_type(device.hDevice).ok()?,
                     name: get_device_name(device.hDevice)
                         .and_then(|name| get_device_product_string(&name))
@@ -527,6 +572,7 @@ pub fn get_device_list() -> Result<Vec<Device>> {
     }
 }
 
+#[cfg(windows)]
 struct GamePadObject {
     handle: DeviceHandle,
     button_caps: Vec<HIDP_BUTTON_CAPS>,
@@ -535,6 +581,7 @@ struct GamePadObject {
     preparsed_buffer: Vec<u8>,
 }
 
+#[cfg(windows)]
 impl GamePadObject {
     unsafe fn new(handle: HANDLE) -> windows::core::Result<Self> {
         let mut preparsed = vec![];
@@ -549,7 +596,7 @@ impl GamePadObject {
             preparsed.as_ptr() as _,
         );
         Ok(Self {
-            handle: DeviceHandle(handle),
+            handle: DeviceHandle(handle.0),
             button_caps,
             value_caps,
             usage: vec![0u16; usage_num as usize],
@@ -558,10 +605,12 @@ impl GamePadObject {
     }
 }
 
+#[cfg(windows)]
 thread_local! {
     static GAMEPADS: RefCell<Vec<GamePadObject>> = RefCell::new(vec![]);
 }
 
+#[cfg(windows)]
 pub(crate) fn register_devices(hwnd: HWND, state: WindowState) -> Result<()> {
     let flags = RIDEV_DEVNOTIFY
         | if state == WindowState::Background {
@@ -604,8 +653,8 @@ pub(crate) fn register_devices(hwnd: HWND, state: WindowState) -> Result<()> {
         GAMEPADS.with(|gamepads| {
             let mut gamepads = gamepads.borrow_mut();
             for device in &device_list {
-                if Ok(DeviceType::GamePad) == get_device_type(device.handle.0) {
-                    let Ok(obj) = GamePadObject::new(device.handle.0) else { continue };
+                if Ok(DeviceType::GamePad) == get_device_type(HANDLE(device.handle.0)) {
+                    let Ok(obj) = GamePadObject::new(HANDLE(device.handle.0)) else { continue };
                     gamepads.push(obj);
                 }
             }
@@ -614,6 +663,7 @@ pub(crate) fn register_devices(hwnd: HWND, state: WindowState) -> Result<()> {
     }
 }
 
+#[cfg(windows)]
 unsafe fn input_keyboard_data(input: &mut RAWINPUT) -> InputData {
     let keyboard = input.data.keyboard;
     let handle = input.header.hDevice;
@@ -628,13 +678,14 @@ unsafe fn input_keyboard_data(input: &mut RAWINPUT) -> InputData {
     };
     let extra = keyboard.ExtraInformation;
     InputData::Keyboard(KeyboardData {
-        handle: DeviceHandle(handle),
+        handle: DeviceHandle(handle.0),
         key_code,
         key_state,
         extra,
     })
 }
 
+#[cfg(windows)]
 unsafe fn input_mouse_data(input: &mut RAWINPUT) -> InputData {
     let mouse = input.data.mouse;
     let handle = input.header.hDevice;
@@ -651,7 +702,7 @@ unsafe fn input_mouse_data(input: &mut RAWINPUT) -> InputData {
     let buttons = MouseButtonStates(button_flags as _);
     let extra = mouse.ulExtraInformation;
     InputData::Mouse(MouseData {
-        handle: DeviceHandle(handle),
+        handle: DeviceHandle(handle.0),
         position,
         wheel,
         hwheel,
@@ -660,6 +711,7 @@ unsafe fn input_mouse_data(input: &mut RAWINPUT) -> InputData {
     })
 }
 
+#[cfg(windows)]
 unsafe fn input_gamepad_data(input: &mut RAWINPUT) -> windows::core::Result<InputData> {
     let hid = &mut input.data.hid;
     let handle = input.header.hDevice;
@@ -667,7 +719,7 @@ unsafe fn input_gamepad_data(input: &mut RAWINPUT) -> windows::core::Result<Inpu
         let mut gamepads = gamepads.borrow_mut();
         let Some(gamepad) = gamepads
             .iter_mut()
-            .find(|gamepad| gamepad.handle == DeviceHandle(handle)) else { return Err(E_FAIL.into()); };
+            .find(|gamepad| gamepad.handle == DeviceHandle(handle.0)) else { return Err(E_FAIL.into()); };
         let preparsed = &mut gamepad.preparsed_buffer;
         get_preparsed_data(handle, preparsed)?;
         let mut data = GamePadData::new(handle);
@@ -720,23 +772,40 @@ unsafe fn input_gamepad_data(input: &mut RAWINPUT) -> windows::core::Result<Inpu
     })
 }
 
+#[cfg(windows)]
 thread_local! {
     static RAW_INPUT_DATA_BUFFER: RefCell<Vec<u8>> = RefCell::new(vec![]);
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceChange {
     pub device: DeviceHandle,
     pub state: DeviceChangeState,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RawInputEvent {
     Input(InputData),
     DeviceChange(DeviceChange),
     Quit,
 }
 
+impl RawInputEvent {
+    /// Returns the name of the variant, or of the device for `Input`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Input(InputData::Keyboard(_)) => "Keyboard",
+            Self::Input(InputData::Mouse(_)) => "Mouse",
+            Self::Input(InputData::GamePad(_)) => "GamePad",
+            Self::DeviceChange(_) => "DeviceChange",
+            Self::Quit => "Quit",
+        }
+    }
+}
+
+#[cfg(windows)]
 pub(crate) unsafe fn on_input(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     let input_handle = HRAWINPUT(lparam.0);
     RAW_INPUT_DATA_BUFFER.with(|buffer| {
@@ -781,6 +850,7 @@ pub(crate) unsafe fn on_input(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRE
     })
 }
 
+#[cfg(windows)]
 pub(crate) unsafe fn on_input_device_change(hwnd: HWND, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     let def_window_proc = || DefWindowProcW(hwnd, WM_INPUT_DEVICE_CHANGE, wparam, lparam);
     let handle = HANDLE(lparam.0 as _);
@@ -796,7 +866,7 @@ pub(crate) unsafe fn on_input_device_change(hwnd: HWND, wparam: WPARAM, lparam:
             Context::send_raw_input_event(
                 hwnd,
                 RawInputEvent::DeviceChange(DeviceChange {
-                    device: DeviceHandle(handle),
+                    device: DeviceHandle(handle.0),
                     state: DeviceChangeState::Arrival,
                 }),
             );
@@ -806,7 +876,7 @@ pub(crate) unsafe fn on_input_device_change(hwnd: HWND, wparam: WPARAM, lparam:
                 let mut gamepads = gamepads.borrow_mut();
                 let index = gamepads
                     .iter()
-                    .position(|gamepad| gamepad.handle == DeviceHandle(handle));
+                    .position(|gamepad| gamepad.handle == DeviceHandle(handle.0));
                 if let Some(index) = index {
                     gamepads.remove(index);
                 }
@@ -0,0 +1,305 @@
+//! Recording events of a window and replaying them.
+//!
+//! [`Recorder`] wraps an `EventReceiver` and writes every received event with its timestamp,
+//! and [`ReplayReceiver`] reads the recording back through the same `recv`/`try_recv` API.
+
+use crate::*;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The first bytes of a recording in `Format::Binary`.
+const MAGIC: &[u8; 8] = b"WITASREC";
+
+/// The version of `Format::Binary`, which follows `MAGIC` as a little-endian `u32`.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// One JSON object per line.
+    JsonLines,
+    /// `MAGIC` and `VERSION` followed by records encoded by bincode.
+    Binary,
+}
+
+/// An event and its `Timestamp::instant` measured from when the `Recorder` was created.
+///
+/// Events enqueued before the `Recorder` was created have an `elapsed` of zero.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    pub elapsed: Duration,
+    #[serde(default)]
+    pub message_time: Option<u32>,
+    pub event: Event,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename = "Record")]
+struct RecordRef<'a> {
+    elapsed: Duration,
+    message_time: Option<u32>,
+    event: &'a Event,
+}
+
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e).into()
+}
+
+/// Writes every event received from an `EventReceiver`.
+///
+/// The writer is flushed after each event so that a recording survives a crash. `Event::User` is
+/// not written.
+pub struct Recorder<W: Write> {
+    rx: EventReceiver,
+    writer: W,
+    format: Format,
+    start: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(rx: EventReceiver, mut writer: W, format: Format) -> Result<Self> {
+        if format == Format::Binary {
+            writer.write_all(MAGIC)?;
+            writer.write_all(&VERSION.to_le_bytes())?;
+        }
+        Ok(Self {
+            rx,
+            writer,
+            format,
+            start: Instant::now(),
+        })
+    }
+
+    pub async fn recv(&mut self) -> Result<Event> {
+        let event = self.rx.recv().await;
+        self.write(&event)?;
+        Ok(event)
+    }
+
+    pub fn try_recv(&mut self) -> Result<Option<Event>> {
+        let Some(event) = self.rx.try_recv()? else { return Ok(None) };
+        self.write(&event)?;
+        Ok(Some(event))
+    }
+
+    /// Returns the wrapped receiver and writer.
+    #[inline]
+    pub fn into_inner(self) -> (EventReceiver, W) {
+        (self.rx, self.writer)
+    }
+
+    fn write(&mut self, event: &Event) -> Result<()> {
+        if matches!(event, Event::User(_)) {
+            return Ok(());
+        }
+        let timestamp = self.rx.timestamp();
+        let record = RecordRef {
+            elapsed: timestamp.map_or_else(
+                || self.start.elapsed(),
+                |t| t.instant.saturating_duration_since(self.start),
+            ),
+            message_time: timestamp.and_then(|t| t.message_time),
+            event,
+        };
+        match self.format {
+            Format::JsonLines => {
+                serde_json::to_writer(&mut self.writer, &record).map_err(std::io::Error::from)?;
+                self.writer.write_all(b"\n")?;
+            }
+            Format::Binary => {
+                bincode::serialize_into(&mut self.writer, &record).map_err(invalid_data)?;
+            }
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Recorder<std::io::BufWriter<std::fs::File>> {
+    /// Creates a file and records events to it.
+    #[inline]
+    pub fn create(rx: EventReceiver, path: impl AsRef<Path>, format: Format) -> Result<Self> {
+        let file = std::fs::File::create(path)?;
+        Self::new(rx, std::io::BufWriter::new(file), format)
+    }
+}
+
+/// How fast `ReplayReceiver` delivers events.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Pace {
+    /// Waits until each event reaches its recorded time since the first `recv`.
+    RealTime,
+    /// Delivers events without waiting.
+    #[default]
+    Immediate,
+}
+
+/// Delivers recorded events.
+///
+/// When the recording runs out, `recv` returns `Event::Quit` and `try_recv` returns
+/// `Error::UiThreadClosed` as `EventReceiver` does after the UI thread has closed.
+pub struct ReplayReceiver {
+    records: VecDeque<Record>,
+    pace: Pace,
+    start: Option<Instant>,
+    timestamp: Option<Timestamp>,
+}
+
+impl ReplayReceiver {
+    /// Reads a recording in either format.
+    ///
+    /// Returns `Error::UnsupportedRecordVersion` for a binary recording of another `VERSION`.
+    pub fn new(reader: impl Read) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut records = VecDeque::new();
+        if reader.fill_buf()?.starts_with(MAGIC) {
+            reader.consume(MAGIC.len());
+            let mut version = [0; 4];
+            reader.read_exact(&mut version)?;
+            let version = u32::from_le_bytes(version);
+            if version != VERSION {
+                return Err(Error::UnsupportedRecordVersion(version));
+            }
+            while !reader.fill_buf()?.is_empty() {
+                records.push_back(bincode::deserialize_from(&mut reader).map_err(invalid_data)?);
+            }
+        } else {
+            for line in reader.lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                records.push_back(serde_json::from_str(&line).map_err(std::io::Error::from)?);
+            }
+        }
+        Ok(Self {
+            records,
+            pace: Pace::default(),
+            start: None,
+            timestamp: None,
+        })
+    }
+
+    #[inline]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(std::fs::File::open(path)?)
+    }
+
+    #[inline]
+    pub fn pace(mut self, pace: Pace) -> Self {
+        self.pace = pace;
+        self
+    }
+
+    /// Returns the number of events which are not delivered yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub async fn recv(&mut self) -> Event {
+        let Some(elapsed) = self.records.front().map(|record| record.elapsed) else { return Event::Quit };
+        if self.pace == Pace::RealTime {
+            let start = *self.start.get_or_insert_with(Instant::now);
+            tokio::time::sleep_until((start + elapsed).into()).await;
+        }
+        self.pop().unwrap()
+    }
+
+    /// Returns `Ok(None)` while the next event is not due with `Pace::RealTime`.
+    pub fn try_recv(&mut self) -> Result<Option<Event>> {
+        let Some(record) = self.records.front() else { return Err(Error::UiThreadClosed) };
+        if self.pace == Pace::RealTime {
+            let start = *self.start.get_or_insert_with(Instant::now);
+            if start.elapsed() < record.elapsed {
+                return Ok(None);
+            }
+        }
+        Ok(self.pop())
+    }
+
+    /// Returns the timestamp of the event which was received last.
+    ///
+    /// `Timestamp::instant` and `Timestamp::arrival` are when the event was delivered.
+    #[inline]
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    fn pop(&mut self) -> Option<Event> {
+        let record = self.records.pop_front()?;
+        let now = Instant::now();
+        self.timestamp = Some(Timestamp {
+            message_time: record.message_time,
+            instant: now,
+            arrival: now,
+        });
+        Some(record.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::tests::{create_window, lock};
+    use crate::headless::Simulator;
+
+    fn record(format: Format) -> (Vec<u8>, Vec<String>) {
+        let (window, rx) = create_window("record");
+        let mut recorder = Recorder::new(rx, vec![], format).unwrap();
+        let mut sim = Simulator::new();
+        sim.click(&window, (10, 20), MouseButton::Left);
+        sim.type_text("a");
+        sim.leave();
+        window.close();
+        let mut events = vec![];
+        while let Ok(Some(event)) = recorder.try_recv() {
+            events.push(format!("{:?}", event));
+        }
+        (recorder.into_inner().1, events)
+    }
+
+    fn replay(data: &[u8], pace: Pace) -> Vec<String> {
+        let mut replay = ReplayReceiver::new(data).unwrap().pace(pace);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut events = vec![];
+        while !replay.is_empty() {
+            events.push(format!("{:?}", rt.block_on(replay.recv())));
+        }
+        assert!(matches!(rt.block_on(replay.recv()), Event::Quit));
+        assert!(matches!(replay.try_recv(), Err(Error::UiThreadClosed)));
+        events
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let _lock = lock();
+        for format in [Format::JsonLines, Format::Binary] {
+            let (data, events) = record(format);
+            assert!(events.len() > 5);
+            assert_eq!(data.starts_with(MAGIC), format == Format::Binary);
+            assert_eq!(replay(&data, Pace::Immediate), events);
+            assert_eq!(replay(&data, Pace::RealTime), events);
+        }
+    }
+
+    #[test]
+    fn reject_other_version() {
+        let _lock = lock();
+        let (mut data, _) = record(Format::Binary);
+        data[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            ReplayReceiver::new(&data[..]),
+            Err(Error::UnsupportedRecordVersion(v)) if v == VERSION + 1
+        ));
+    }
+}
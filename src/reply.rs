@@ -0,0 +1,134 @@
+//! Answers from the app to events which need them.
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long the UI thread waits for a `Reply` by default.
+pub const DEFAULT_REPLY_TIMEOUT: Duration = Duration::from_millis(200);
+
+struct Inner<T> {
+    value: Mutex<Option<T>>,
+    f: Option<Box<dyn FnOnce(T) + Send + Sync>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let (Some(f), Some(value)) = (self.f.take(), self.value.get_mut().unwrap().take()) else { return };
+        f(value);
+    }
+}
+
+/// An answer to an event.
+///
+/// Clones share the value, which is passed back when the last clone is dropped. The value starts
+/// as the default of the event.
+pub struct Reply<T>(Arc<Inner<T>>);
+
+impl<T: Send + 'static> Reply<T> {
+    /// Calls `f` with the answer.
+    pub(crate) fn new(default: T, f: impl FnOnce(T) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(Inner {
+            value: Mutex::new(Some(default)),
+            f: Some(Box::new(f)),
+        }))
+    }
+
+    /// Returns a reply and the receiver which waits for its answer.
+    pub(crate) fn channel(default: T) -> (Self, Pending<T>)
+    where
+        T: Clone,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let reply = Self::new(default.clone(), move |value| {
+            tx.send(value).unwrap_or(());
+        });
+        (reply, Pending { rx, default })
+    }
+
+    /// Returns a reply with the same value whose answer is not passed back.
+    pub(crate) fn detached(&self) -> Self
+    where
+        T: Clone,
+    {
+        Self::new(self.get(), |_| {})
+    }
+}
+
+impl<T> Reply<T> {
+    #[inline]
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.0.value.lock().unwrap().clone().unwrap()
+    }
+
+    #[inline]
+    pub fn set(&self, value: T) {
+        *self.0.value.lock().unwrap() = Some(value);
+    }
+}
+
+impl<T> Clone for Reply<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Reply<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Reply")
+            .field(self.0.value.lock().unwrap().as_ref().unwrap())
+            .finish()
+    }
+}
+
+/// Waits for the answer of a `Reply`.
+#[derive(Debug)]
+pub(crate) struct Pending<T> {
+    rx: mpsc::Receiver<T>,
+    // Only the UI thread of Windows waits for answers.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    default: T,
+}
+
+impl<T> Pending<T> {
+    /// Returns the default value when the answer does not come within `timeout`. A late answer
+    /// is discarded.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn wait(self, timeout: Duration) -> T {
+        self.rx.recv_timeout(timeout).unwrap_or(self.default)
+    }
+
+    /// Returns the answer if it has come.
+    pub fn try_get(&self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_and_default() {
+        let (reply, pending) = Reply::channel(1);
+        let held = reply.clone();
+        reply.set(2);
+        drop(reply);
+        // A clone is still held by a slow consumer.
+        let start = std::time::Instant::now();
+        assert_eq!(pending.wait(Duration::from_millis(20)), 1);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        drop(held);
+
+        let (reply, pending) = Reply::channel(1);
+        let t = std::thread::spawn(move || {
+            reply.set(3);
+        });
+        assert_eq!(pending.wait(Duration::from_secs(10)), 3);
+        t.join().unwrap();
+    }
+}
@@ -1,6 +1,9 @@
+#[cfg(windows)]
 use crate::*;
 use std::path::{Path, PathBuf};
+#[cfg(windows)]
 use windows::core::{HSTRING, PCWSTR};
+#[cfg(windows)]
 use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -16,6 +19,7 @@ impl Icon {
     }
 }
 
+#[cfg(windows)]
 fn load_icon_impl(hinst: HINSTANCE, icon: &Icon, cx: i32, cy: i32) -> Result<HICON> {
     let icon = unsafe {
         match icon {
@@ -38,6 +42,7 @@ fn load_icon_impl(hinst: HINSTANCE, icon: &Icon, cx: i32, cy: i32) -> Result<HIC
     Ok(HICON(icon.0))
 }
 
+#[cfg(windows)]
 pub(crate) fn load_icon(icon: &Icon, hinst: HINSTANCE) -> Result<HICON> {
     unsafe {
         load_icon_impl(
@@ -49,6 +54,7 @@ pub(crate) fn load_icon(icon: &Icon, hinst: HINSTANCE) -> Result<HICON> {
     }
 }
 
+#[cfg(windows)]
 pub(crate) fn load_small_icon(icon: &Icon, hinst: HINSTANCE) -> Result<HICON> {
     unsafe {
         load_icon_impl(
@@ -60,7 +66,7 @@ pub(crate) fn load_small_icon(icon: &Icon, hinst: HINSTANCE) -> Result<HICON> {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Cursor {
     AppStarting,
     Arrow,
@@ -78,6 +84,7 @@ pub enum Cursor {
     Wait,
 }
 
+#[cfg(windows)]
 impl Cursor {
     pub(crate) fn name(&self) -> PCWSTR {
         match self {
@@ -48,7 +48,7 @@ impl Thread {
                 let _coinit =
                     coinit::init(coinit::APARTMENTTHREADED | coinit::DISABLE_OLE1DDE).unwrap();
                 IsGUIThread(true);
-                window::register_class();
+                win32::register_class();
                 {
                     tmp_tx.send(()).unwrap_or(());
                 }
@@ -67,6 +67,7 @@ impl Thread {
                                     }
                                 }));
                             if let Err(e) = ret {
+                                procedure::record_panic(e.as_ref());
                                 Context::quit();
                                 std::panic::resume_unwind(e);
                             }
@@ -98,6 +99,12 @@ impl Thread {
     }
 
     fn send_task(&self, f: impl FnOnce() + Send + 'static) {
+        // The span is a child of the span of the caller.
+        #[cfg(feature = "tracing")]
+        let f = {
+            let span = tracing::trace_span!("task");
+            move || span.in_scope(f)
+        };
         self.tx_task.send(Task(Box::new(f))).unwrap_or(());
         self.post_message(WM_SEND_TASK);
     }
@@ -0,0 +1,1402 @@
+//! A backend for Wayland compositors.
+//!
+//! The backend is selected when the `wayland` feature is enabled and a compositor can be
+//! connected through `WAYLAND_DISPLAY`. Windows are xdg-shell toplevels and their configure
+//! events are translated into the same `Event` variants as the Win32 backend. Keyboard input is
+//! interpreted with xkbcommon, which is loaded at runtime, and `wp_fractional_scale_v1` is used
+//! for the DPI of windows when the compositor supports it.
+//!
+//! Wayland leaves key repeat to clients. A held key is repeated on a thread at the rate and the
+//! delay of `wl_keyboard.repeat_info` as `KeyInput` with `prev_pressed` and `CharInput`.
+//!
+//! Wayland does not let clients know or choose their position, so `Moved` is never sent and
+//! `Window::position` returns `None`. A minimized window can only be restored by the user.
+
+use crate::backend::{BoxFuture, WindowParams};
+use crate::keysym::{as_virtual_key, is_keypad, keysym_to_char};
+use crate::window::WindowId;
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::os::fd::AsFd;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{
+    wl_buffer, wl_compositor, wl_keyboard, wl_pointer, wl_registry, wl_seat, wl_shm, wl_shm_pool,
+    wl_surface,
+};
+use wayland_client::{delegate_noop, Connection, Dispatch, Proxy, QueueHandle, WEnum};
+use wayland_protocols::wp::cursor_shape::v1::client::{
+    wp_cursor_shape_device_v1::{self, Shape},
+    wp_cursor_shape_manager_v1,
+};
+use wayland_protocols::wp::fractional_scale::v1::client::{
+    wp_fractional_scale_manager_v1, wp_fractional_scale_v1,
+};
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
+use wayland_protocols::xdg::shell::client::{xdg_surface, xdg_toplevel, xdg_wm_base};
+use xkbcommon_dl as xkb;
+
+// xdg_toplevel states
+const STATE_MAXIMIZED: u32 = 1;
+const STATE_ACTIVATED: u32 = 4;
+const STATE_SUSPENDED: u32 = 9;
+
+// Linux input event codes of mouse buttons
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+
+// The denominator of `wp_fractional_scale_v1.preferred_scale`
+const SCALE_DENOMINATOR: u32 = 120;
+
+// The distance of one notch in `wl_pointer.axis`, which is 120 in `MouseWheel`
+const AXIS_STEP: f64 = 10.0;
+const WHEEL_DELTA: f64 = 120.0;
+
+// The key repeat until `wl_keyboard.repeat_info` comes
+const DEFAULT_REPEAT_RATE: u32 = 25;
+const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(600);
+
+fn cursor_shape(cursor: Cursor) -> Shape {
+    match cursor {
+        Cursor::AppStarting => Shape::Progress,
+        Cursor::Arrow => Shape::Default,
+        Cursor::Cross => Shape::Crosshair,
+        Cursor::Hand => Shape::Pointer,
+        Cursor::Help => Shape::Help,
+        Cursor::IBeam => Shape::Text,
+        Cursor::No => Shape::NotAllowed,
+        Cursor::SizeAll => Shape::Move,
+        Cursor::SizeNESW => Shape::NeswResize,
+        Cursor::SizeNS => Shape::NsResize,
+        Cursor::SizeNWSE => Shape::NwseResize,
+        Cursor::SizeWE => Shape::EwResize,
+        Cursor::SizeUpArrow => Shape::NResize,
+        Cursor::Wait => Shape::Wait,
+    }
+}
+
+fn as_mouse_button(button: u32) -> Option<MouseButton> {
+    match button {
+        BTN_LEFT => Some(MouseButton::Left),
+        BTN_RIGHT => Some(MouseButton::Right),
+        BTN_MIDDLE => Some(MouseButton::Middle),
+        0x113..=0x11f => Some(MouseButton::Ex(button - 0x113)),
+        _ => None,
+    }
+}
+
+struct Xkb {
+    keymap: *mut xkb::xkb_keymap,
+    state: *mut xkb::xkb_state,
+}
+
+// The keymap and the state are only used while `Shared::state` is locked.
+unsafe impl Send for Xkb {}
+
+impl Xkb {
+    fn new(text: &[u8]) -> Option<Self> {
+        let lib = xkb::xkbcommon_option()?;
+        let text = std::ffi::CString::new(text.split(|&b| b == 0).next()?).ok()?;
+        unsafe {
+            let context = (lib.xkb_context_new)(xkb::xkb_context_flags::XKB_CONTEXT_NO_FLAGS);
+            if context.is_null() {
+                return None;
+            }
+            let keymap = (lib.xkb_keymap_new_from_string)(
+                context,
+                text.as_ptr(),
+                xkb::xkb_keymap_format::XKB_KEYMAP_FORMAT_TEXT_V1,
+                xkb::xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            );
+            (lib.xkb_context_unref)(context);
+            Self::from_keymap(keymap)
+        }
+    }
+
+    // Takes the ownership of `keymap`.
+    unsafe fn from_keymap(keymap: *mut xkb::xkb_keymap) -> Option<Self> {
+        let lib = xkb::xkbcommon_handle();
+        if keymap.is_null() {
+            return None;
+        }
+        let state = (lib.xkb_state_new)(keymap);
+        if state.is_null() {
+            (lib.xkb_keymap_unref)(keymap);
+            return None;
+        }
+        Some(Self { keymap, state })
+    }
+
+    fn update_mask(&self, depressed: u32, latched: u32, locked: u32, group: u32) {
+        let lib = xkb::xkbcommon_handle();
+        unsafe {
+            (lib.xkb_state_update_mask)(self.state, depressed, latched, locked, 0, 0, group);
+        }
+    }
+
+    // Returns the keysym which identifies the key and the keysym which is typed.
+    fn lookup(&self, keycode: u32) -> (u32, u32) {
+        let lib = xkb::xkbcommon_handle();
+        unsafe {
+            let typed = (lib.xkb_state_key_get_one_sym)(self.state, keycode);
+            let layout = (lib.xkb_state_key_get_layout)(self.state, keycode);
+            let mut syms = std::ptr::null();
+            let len =
+                (lib.xkb_keymap_key_get_syms_by_level)(self.keymap, keycode, layout, 0, &mut syms);
+            let base = if len > 0 { *syms } else { typed };
+            if is_keypad(typed) {
+                (typed, typed)
+            } else {
+                (base, typed)
+            }
+        }
+    }
+
+    fn repeats(&self, keycode: u32) -> bool {
+        let lib = xkb::xkbcommon_handle();
+        unsafe { (lib.xkb_keymap_key_repeats)(self.keymap, keycode) > 0 }
+    }
+
+    fn is_ctrl_active(&self) -> bool {
+        let lib = xkb::xkbcommon_handle();
+        unsafe {
+            (lib.xkb_state_mod_name_is_active)(
+                self.state,
+                xkb::XKB_MOD_NAME_CTRL.as_ptr() as _,
+                xkb::xkb_state_component::XKB_STATE_MODS_EFFECTIVE,
+            ) > 0
+        }
+    }
+}
+
+impl Drop for Xkb {
+    fn drop(&mut self) {
+        let lib = xkb::xkbcommon_handle();
+        unsafe {
+            (lib.xkb_state_unref)(self.state);
+            (lib.xkb_keymap_unref)(self.keymap);
+        }
+    }
+}
+
+struct WindowState {
+    surface: wl_surface::WlSurface,
+    xdg_surface: xdg_surface::XdgSurface,
+    toplevel: xdg_toplevel::XdgToplevel,
+    fractional_scale: Option<wp_fractional_scale_v1::WpFractionalScaleV1>,
+    viewport: Option<wp_viewport::WpViewport>,
+    buffer: Option<wl_buffer::WlBuffer>,
+    // The size in surface coordinates
+    logical_size: PhysicalSize<u32>,
+    dpi: u32,
+    pending: Option<(PhysicalSize<u32>, Vec<u32>)>,
+    visible: bool,
+    configured: bool,
+    activated: bool,
+    maximized: bool,
+    minimized: bool,
+    cursor: Cursor,
+}
+
+impl WindowState {
+    fn physical_size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(
+            self.logical_size.width * self.dpi / DEFAULT_DPI,
+            self.logical_size.height * self.dpi / DEFAULT_DPI,
+        )
+    }
+
+    fn to_physical(&self, x: f64, y: f64) -> PhysicalPosition<i32> {
+        let scale = self.dpi as f64 / DEFAULT_DPI as f64;
+        PhysicalPosition::new((x * scale) as i32, (y * scale) as i32)
+    }
+}
+
+struct KeyRepeat {
+    // Keys per second, where 0 disables repeat
+    rate: u32,
+    delay: Duration,
+    // The held key, the window and when the key is repeated next
+    held: Option<(u32, u32, Instant)>,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self {
+            rate: DEFAULT_REPEAT_RATE,
+            delay: DEFAULT_REPEAT_DELAY,
+            held: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    windows: HashMap<u32, WindowState>,
+    pointer: Option<wl_pointer::WlPointer>,
+    cursor_shape_device: Option<wp_cursor_shape_device_v1::WpCursorShapeDeviceV1>,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    pointer_focus: Option<(u32, PhysicalPosition<i32>)>,
+    pointer_serial: u32,
+    buttons: MouseButtons,
+    keyboard_focus: Option<u32>,
+    pressed_keys: HashSet<u32>,
+    xkb: Option<Xkb>,
+    repeat: KeyRepeat,
+    disconnected: bool,
+}
+
+impl State {
+    // Sends `KeyInput` and `CharInput` of an evdev key code.
+    fn send_key(&self, id: u32, key: u32, key_state: KeyState, prev_pressed: bool) {
+        let Some(xkb) = self.xkb.as_ref() else { return };
+        // Keycodes of xkbcommon are evdev codes offset by 8.
+        let (base, typed) = xkb.lookup(key + 8);
+        Context::send_event(
+            id,
+            Event::KeyInput(events::KeyInput {
+                key_code: KeyCode::new(as_virtual_key(base), ScanCode(key)),
+                key_state,
+                prev_pressed,
+            }),
+        );
+        if key_state == KeyState::Released {
+            return;
+        }
+        let c = if xkb.is_ctrl_active() {
+            match base {
+                0x0061..=0x007a => char::from_u32(base - 0x0060),
+                _ => None,
+            }
+        } else {
+            keysym_to_char(typed)
+        };
+        if let Some(c) = c {
+            Context::send_event(id, Event::CharInput(events::CharInput { c }));
+        }
+    }
+}
+
+struct Shared {
+    conn: Connection,
+    qh: QueueHandle<Dispatcher>,
+    compositor: wl_compositor::WlCompositor,
+    shm: wl_shm::WlShm,
+    wm_base: xdg_wm_base::XdgWmBase,
+    fractional_scale_manager: Option<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1>,
+    viewporter: Option<wp_viewporter::WpViewporter>,
+    cursor_shape_manager: Option<wp_cursor_shape_manager_v1::WpCursorShapeManagerV1>,
+    state: Mutex<State>,
+    // Notified when `State::repeat` or `State::disconnected` changes
+    repeat_changed: Condvar,
+}
+
+impl Shared {
+    fn flush(&self) {
+        self.conn.flush().unwrap_or(());
+    }
+
+    // Attaches a white buffer of the physical size so that the compositor maps the surface.
+    fn attach_buffer(&self, w: &mut WindowState) {
+        let size = w.physical_size();
+        let stride = size.width * 4;
+        let len = (stride * size.height) as usize;
+        let Ok(file) = create_shm_file(len) else { return };
+        let pool = self.shm.create_pool(file.as_fd(), len as i32, &self.qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            size.width as i32,
+            size.height as i32,
+            stride as i32,
+            wl_shm::Format::Xrgb8888,
+            &self.qh,
+            (),
+        );
+        pool.destroy();
+        w.surface.attach(Some(&buffer), 0, 0);
+        match &w.viewport {
+            Some(viewport) => {
+                viewport.set_destination(w.logical_size.width as i32, w.logical_size.height as i32)
+            }
+            None => w
+                .surface
+                .set_buffer_scale((w.dpi / DEFAULT_DPI).max(1) as i32),
+        }
+        w.surface
+            .damage_buffer(0, 0, size.width as i32, size.height as i32);
+        w.surface.commit();
+        if let Some(old) = w.buffer.replace(buffer) {
+            old.destroy();
+        }
+    }
+
+    fn on_configure(&self, id: u32, serial: u32) {
+        let mut state = self.state.lock().unwrap();
+        let Some(w) = state.windows.get_mut(&id) else { return };
+        w.xdg_surface.ack_configure(serial);
+        let (size, states) = w
+            .pending
+            .take()
+            .unwrap_or((PhysicalSize::new(0, 0), vec![]));
+        let prev_size = w.physical_size();
+        if size.width > 0 && size.height > 0 {
+            w.logical_size = size;
+        }
+        let activated = states.contains(&STATE_ACTIVATED);
+        let maximized = states.contains(&STATE_MAXIMIZED);
+        let minimized = states.contains(&STATE_SUSPENDED);
+        let size = w.physical_size();
+        if activated != w.activated {
+            w.activated = activated;
+            let event = if activated {
+                Event::Activated
+            } else {
+                Event::Inactivated
+            };
+            Context::send_event(id, event);
+        }
+        if minimized && !w.minimized {
+            Context::send_event(id, Event::Minimized);
+        } else if maximized && (!w.maximized || w.minimized) {
+            Context::send_event(id, Event::Maximized(events::Maximized { size }));
+        } else if !minimized && !maximized && (w.maximized || w.minimized) {
+            Context::send_event(id, Event::Restored(events::Restored { size }));
+        }
+        w.minimized = minimized;
+        w.maximized = maximized;
+        if w.configured && size != prev_size {
+            Context::send_event(id, Event::Resized(events::Resized { size }));
+        }
+        if !w.configured || size != prev_size {
+            w.configured = true;
+            self.attach_buffer(w);
+            Context::send_event(
+                id,
+                Event::Draw(events::Draw {
+                    invalid_rect: PhysicalRect::new(
+                        (0, 0),
+                        (size.width as i32, size.height as i32),
+                    ),
+                }),
+            );
+        } else {
+            w.surface.commit();
+        }
+        drop(state);
+        self.flush();
+    }
+
+    fn on_scale_changed(&self, id: u32, dpi: u32) {
+        let mut state = self.state.lock().unwrap();
+        let Some(w) = state.windows.get_mut(&id) else { return };
+        if w.dpi == dpi {
+            return;
+        }
+        w.dpi = dpi;
+        Context::send_event(id, Event::DpiChanged(events::DpiChanged { new_dpi: dpi }));
+        if w.configured && w.visible {
+            let size = w.physical_size();
+            Context::send_event(id, Event::Resized(events::Resized { size }));
+            self.attach_buffer(w);
+            Context::send_event(
+                id,
+                Event::Draw(events::Draw {
+                    invalid_rect: PhysicalRect::new(
+                        (0, 0),
+                        (size.width as i32, size.height as i32),
+                    ),
+                }),
+            );
+        }
+        drop(state);
+        self.flush();
+    }
+
+    fn destroy_window(&self, id: u32) {
+        let w = {
+            let mut state = self.state.lock().unwrap();
+            if state.pointer_focus.is_some_and(|(focus, _)| focus == id) {
+                state.pointer_focus = None;
+            }
+            if state.keyboard_focus == Some(id) {
+                state.keyboard_focus = None;
+                state.repeat.held = None;
+            }
+            state.windows.remove(&id)
+        };
+        let Some(w) = w else { return };
+        if w.activated {
+            Context::send_event(id, Event::Inactivated);
+        }
+        if let Some(viewport) = w.viewport {
+            viewport.destroy();
+        }
+        if let Some(fractional_scale) = w.fractional_scale {
+            fractional_scale.destroy();
+        }
+        w.toplevel.destroy();
+        w.xdg_surface.destroy();
+        w.surface.destroy();
+        if let Some(buffer) = w.buffer {
+            buffer.destroy();
+        }
+        self.flush();
+        Context::close_window(id);
+    }
+
+    fn set_cursor_shape(&self, state: &State) {
+        let Some((id, _)) = state.pointer_focus else { return };
+        let Some(device) = state.cursor_shape_device.as_ref() else { return };
+        let Some(w) = state.windows.get(&id) else { return };
+        device.set_shape(state.pointer_serial, cursor_shape(w.cursor));
+    }
+
+    fn create_window(&self, params: WindowParams) -> Result<(WindowId, EventReceiver)> {
+        let surface = self.compositor.create_surface(&self.qh, ());
+        let id = surface.id().protocol_id();
+        let xdg_surface = self.wm_base.get_xdg_surface(&surface, &self.qh, id);
+        let toplevel = xdg_surface.get_toplevel(&self.qh, id);
+        toplevel.set_title(params.title);
+        toplevel.set_app_id("witas".into());
+        let fractional_scale = self
+            .fractional_scale_manager
+            .as_ref()
+            .map(|m| m.get_fractional_scale(&surface, &self.qh, id));
+        let viewport = self
+            .viewporter
+            .as_ref()
+            .map(|v| v.get_viewport(&surface, &self.qh, ()));
+        let dpi = DEFAULT_DPI;
+        let logical_size = (params.size)(dpi);
+        let rx = Context::register_window(id, false, params.queue_policy, params.event_mask);
+        self.state.lock().unwrap().windows.insert(
+            id,
+            WindowState {
+                surface: surface.clone(),
+                xdg_surface,
+                toplevel,
+                fractional_scale,
+                viewport,
+                buffer: None,
+                logical_size,
+                dpi,
+                pending: None,
+                visible: params.visibility,
+                configured: false,
+                activated: false,
+                maximized: false,
+                minimized: false,
+                cursor: Cursor::default(),
+            },
+        );
+        if params.visibility {
+            // The initial commit without a buffer asks the compositor for a configure event.
+            surface.commit();
+        }
+        self.flush();
+        Ok((id.into(), rx))
+    }
+}
+
+fn create_shm_file(len: usize) -> std::io::Result<std::fs::File> {
+    static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let path = dir.join(format!("witas-{}-{}", std::process::id(), n));
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+    file.write_all(&vec![0xff; len])?;
+    Ok(file)
+}
+
+struct Dispatcher {
+    shared: Arc<Shared>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Dispatcher {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(Dispatcher: ignore wl_compositor::WlCompositor);
+delegate_noop!(Dispatcher: ignore wl_surface::WlSurface);
+delegate_noop!(Dispatcher: ignore wl_shm::WlShm);
+delegate_noop!(Dispatcher: ignore wl_shm_pool::WlShmPool);
+delegate_noop!(Dispatcher: ignore wl_buffer::WlBuffer);
+delegate_noop!(Dispatcher: ignore wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1);
+delegate_noop!(Dispatcher: ignore wp_viewporter::WpViewporter);
+delegate_noop!(Dispatcher: ignore wp_viewport::WpViewport);
+delegate_noop!(Dispatcher: ignore wp_cursor_shape_manager_v1::WpCursorShapeManagerV1);
+delegate_noop!(Dispatcher: ignore wp_cursor_shape_device_v1::WpCursorShapeDeviceV1);
+
+impl Dispatch<xdg_wm_base::XdgWmBase, ()> for Dispatcher {
+    fn event(
+        _: &mut Self,
+        wm_base: &xdg_wm_base::XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<xdg_surface::XdgSurface, u32> for Dispatcher {
+    fn event(
+        this: &mut Self,
+        _: &xdg_surface::XdgSurface,
+        event: xdg_surface::Event,
+        id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            this.shared.on_configure(*id, serial);
+        }
+    }
+}
+
+impl Dispatch<xdg_toplevel::XdgToplevel, u32> for Dispatcher {
+    fn event(
+        this: &mut Self,
+        _: &xdg_toplevel::XdgToplevel,
+        event: xdg_toplevel::Event,
+        id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            xdg_toplevel::Event::Configure {
+                width,
+                height,
+                states,
+            } => {
+                let states = states
+                    .chunks_exact(4)
+                    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                let mut state = this.shared.state.lock().unwrap();
+                if let Some(w) = state.windows.get_mut(id) {
+                    w.pending = Some((PhysicalSize::new(width as u32, height as u32), states));
+                }
+            }
+            xdg_toplevel::Event::Close => Context::request_close(*id),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wp_fractional_scale_v1::WpFractionalScaleV1, u32> for Dispatcher {
+    fn event(
+        this: &mut Self,
+        _: &wp_fractional_scale_v1::WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        id: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wp_fractional_scale_v1::Event::PreferredScale { scale } = event {
+            this.shared
+                .on_scale_changed(*id, DEFAULT_DPI * scale / SCALE_DENOMINATOR);
+        }
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for Dispatcher {
+    fn event(
+        this: &mut Self,
+        seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        else { return };
+        let shared = &this.shared;
+        let mut state = shared.state.lock().unwrap();
+        if capabilities.contains(wl_seat::Capability::Pointer) {
+            if state.pointer.is_none() {
+                let pointer = seat.get_pointer(qh, ());
+                state.cursor_shape_device = shared
+                    .cursor_shape_manager
+                    .as_ref()
+                    .map(|m| m.get_pointer(&pointer, qh, ()));
+                state.pointer = Some(pointer);
+            }
+        } else if let Some(pointer) = state.pointer.take() {
+            if let Some(device) = state.cursor_shape_device.take() {
+                device.destroy();
+            }
+            pointer.release();
+        }
+        if capabilities.contains(wl_seat::Capability::Keyboard) {
+            if state.keyboard.is_none() {
+                state.keyboard = Some(seat.get_keyboard(qh, ()));
+            }
+        } else if let Some(keyboard) = state.keyboard.take() {
+            keyboard.release();
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for Dispatcher {
+    fn event(
+        this: &mut Self,
+        _: &wl_pointer::WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let shared = &this.shared;
+        let mut state = shared.state.lock().unwrap();
+        let _time = Context::set_message_time(match event {
+            wl_pointer::Event::Motion { time, .. }
+            | wl_pointer::Event::Button { time, .. }
+            | wl_pointer::Event::Axis { time, .. } => Some(time),
+            _ => None,
+        });
+        match event {
+            wl_pointer::Event::Enter {
+                serial,
+                surface,
+                surface_x,
+                surface_y,
+            } => {
+                let id = surface.id().protocol_id();
+                let Some(w) = state.windows.get(&id) else { return };
+                let position = w.to_physical(surface_x, surface_y);
+                state.pointer_focus = Some((id, position));
+                state.pointer_serial = serial;
+                shared.set_cursor_shape(&state);
+                Context::send_event(
+                    id,
+                    Event::CursorEntered(events::CursorEntered {
+                        mouse_state: MouseState {
+                            position,
+                            buttons: state.buttons,
+                        },
+                    }),
+                );
+            }
+            wl_pointer::Event::Leave { .. } => {
+                let Some((id, position)) = state.pointer_focus.take() else { return };
+                Context::send_event(
+                    id,
+                    Event::CursorLeft(events::CursorLeft {
+                        mouse_state: MouseState {
+                            position,
+                            buttons: state.buttons,
+                        },
+                    }),
+                );
+            }
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                let Some((id, _)) = state.pointer_focus else { return };
+                let Some(w) = state.windows.get(&id) else { return };
+                let position = w.to_physical(surface_x, surface_y);
+                state.pointer_focus = Some((id, position));
+                Context::send_event(
+                    id,
+                    Event::CursorMoved(events::CursorMoved {
+                        mouse_state: MouseState {
+                            position,
+                            buttons: state.buttons,
+                        },
+                    }),
+                );
+            }
+            wl_pointer::Event::Button {
+                button,
+                state: button_state,
+                ..
+            } => {
+                let Some((id, position)) = state.pointer_focus else { return };
+                let Some(button) = as_mouse_button(button) else { return };
+                let button_state = match button_state {
+                    WEnum::Value(wl_pointer::ButtonState::Pressed) => ButtonState::Pressed,
+                    _ => ButtonState::Released,
+                };
+                match button_state {
+                    ButtonState::Pressed => state.buttons.insert(button),
+                    ButtonState::Released => state.buttons.remove(button),
+                }
+                Context::send_event(
+                    id,
+                    Event::MouseInput(events::MouseInput {
+                        button,
+                        button_state,
+                        mouse_state: MouseState {
+                            position,
+                            buttons: state.buttons,
+                        },
+                    }),
+                );
+            }
+            wl_pointer::Event::Axis { axis, value, .. } => {
+                let Some((id, position)) = state.pointer_focus else { return };
+                // Positive values of the vertical axis scroll down, which is negative in `MouseWheel`.
+                let (axis, distance) = match axis {
+                    WEnum::Value(wl_pointer::Axis::VerticalScroll) => {
+                        (MouseWheelAxis::Vertical, -value / AXIS_STEP * WHEEL_DELTA)
+                    }
+                    WEnum::Value(wl_pointer::Axis::HorizontalScroll) => {
+                        (MouseWheelAxis::Horizontal, value / AXIS_STEP * WHEEL_DELTA)
+                    }
+                    _ => return,
+                };
+                Context::send_event(
+                    id,
+                    Event::MouseWheel(events::MouseWheel {
+                        axis,
+                        distance: distance as i32,
+                        mouse_state: MouseState {
+                            position,
+                            buttons: state.buttons,
+                        },
+                    }),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for Dispatcher {
+    fn event(
+        this: &mut Self,
+        _: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let mut state = this.shared.state.lock().unwrap();
+        let _time = Context::set_message_time(match event {
+            wl_keyboard::Event::Key { time, .. } => Some(time),
+            _ => None,
+        });
+        match event {
+            wl_keyboard::Event::Keymap { format, fd, .. } => {
+                if format != WEnum::Value(wl_keyboard::KeymapFormat::XkbV1) {
+                    return;
+                }
+                let mut text = vec![];
+                if std::fs::File::from(fd).read_to_end(&mut text).is_ok() {
+                    state.xkb = Xkb::new(&text);
+                }
+            }
+            wl_keyboard::Event::Enter { surface, .. } => {
+                state.keyboard_focus = Some(surface.id().protocol_id());
+            }
+            wl_keyboard::Event::Leave { .. } => {
+                state.keyboard_focus = None;
+                state.pressed_keys.clear();
+                state.repeat.held = None;
+            }
+            wl_keyboard::Event::RepeatInfo { rate, delay } => {
+                state.repeat.rate = rate.max(0) as u32;
+                state.repeat.delay = Duration::from_millis(delay.max(0) as u64);
+                if state.repeat.rate == 0 {
+                    state.repeat.held = None;
+                }
+            }
+            wl_keyboard::Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                if let Some(xkb) = state.xkb.as_ref() {
+                    xkb.update_mask(mods_depressed, mods_latched, mods_locked, group);
+                }
+            }
+            wl_keyboard::Event::Key {
+                key,
+                state: key_state,
+                ..
+            } => {
+                let Some(id) = state.keyboard_focus else { return };
+                let Some(xkb) = state.xkb.as_ref() else { return };
+                let repeats = xkb.repeats(key + 8);
+                let key_state = match key_state {
+                    WEnum::Value(wl_keyboard::KeyState::Released) => KeyState::Released,
+                    _ => KeyState::Pressed,
+                };
+                let prev_pressed = match key_state {
+                    KeyState::Pressed => !state.pressed_keys.insert(key),
+                    KeyState::Released => {
+                        state.pressed_keys.remove(&key);
+                        true
+                    }
+                };
+                state.send_key(id, key, key_state, prev_pressed);
+                // Only the last pressed key is repeated.
+                match key_state {
+                    KeyState::Pressed if repeats && state.repeat.rate > 0 => {
+                        let next = Instant::now() + state.repeat.delay;
+                        state.repeat.held = Some((key, id, next));
+                    }
+                    KeyState::Pressed => state.repeat.held = None,
+                    KeyState::Released => {
+                        if state.repeat.held.is_some_and(|(held, _, _)| held == key) {
+                            state.repeat.held = None;
+                        }
+                    }
+                }
+                this.shared.repeat_changed.notify_one();
+            }
+            _ => {}
+        }
+    }
+}
+
+// Repeats the held key until the connection is closed.
+fn repeat_keys(shared: &Shared) {
+    let mut state = shared.state.lock().unwrap();
+    while !state.disconnected {
+        let Some((key, id, next)) = state.repeat.held else {
+            state = shared.repeat_changed.wait(state).unwrap();
+            continue;
+        };
+        let now = Instant::now();
+        if now < next {
+            state = shared
+                .repeat_changed
+                .wait_timeout(state, next - now)
+                .unwrap()
+                .0;
+            continue;
+        }
+        let interval = Duration::from_secs(1) / state.repeat.rate;
+        state.repeat.held = Some((key, id, now + interval));
+        let _time = Context::set_message_time(None);
+        state.send_key(id, key, KeyState::Pressed, true);
+    }
+}
+
+pub(crate) struct Wayland {
+    shared: Arc<Shared>,
+}
+
+impl Wayland {
+    /// Connects to the compositor of `WAYLAND_DISPLAY` and starts the event thread.
+    pub(crate) fn connect() -> Option<Self> {
+        let conn = Connection::connect_to_env().ok()?;
+        let (globals, mut queue) = registry_queue_init::<Dispatcher>(&conn).ok()?;
+        let qh = queue.handle();
+        let compositor = globals.bind(&qh, 1..=4, ()).ok()?;
+        let shm = globals.bind(&qh, 1..=1, ()).ok()?;
+        let wm_base = globals.bind(&qh, 1..=6, ()).ok()?;
+        let shared = Arc::new(Shared {
+            conn: conn.clone(),
+            qh: qh.clone(),
+            compositor,
+            shm,
+            wm_base,
+            fractional_scale_manager: globals.bind(&qh, 1..=1, ()).ok(),
+            viewporter: globals.bind(&qh, 1..=1, ()).ok(),
+            cursor_shape_manager: globals.bind(&qh, 1..=1, ()).ok(),
+            state: Mutex::new(State::default()),
+            repeat_changed: Condvar::new(),
+        });
+        // The seat is kept alive by the connection and its events create the input devices.
+        let _: Option<wl_seat::WlSeat> = globals.bind(&qh, 1..=7, ()).ok();
+        let mut dispatcher = Dispatcher {
+            shared: shared.clone(),
+        };
+        queue.roundtrip(&mut dispatcher).ok()?;
+        std::thread::Builder::new()
+            .name("witas wayland key repeat".into())
+            .spawn({
+                let shared = shared.clone();
+                move || repeat_keys(&shared)
+            })
+            .ok()?;
+        std::thread::Builder::new()
+            .name("witas wayland events".into())
+            .spawn(move || {
+                while queue.blocking_dispatch(&mut dispatcher).is_ok() {}
+                let ids = {
+                    let mut state = dispatcher.shared.state.lock().unwrap();
+                    state.disconnected = true;
+                    dispatcher.shared.repeat_changed.notify_one();
+                    state.windows.keys().copied().collect::<Vec<_>>()
+                };
+                for id in ids {
+                    dispatcher.shared.destroy_window(id);
+                }
+            })
+            .ok()?;
+        Some(Self { shared })
+    }
+
+    fn with_window(&self, id: WindowId, f: impl FnOnce(&mut WindowState)) {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(w) = state.windows.get_mut(&id.into()) {
+            f(w);
+        }
+        drop(state);
+        self.shared.flush();
+    }
+}
+
+impl backend::Backend for Wayland {
+    fn create_window(&self, params: WindowParams) -> BoxFuture<Result<(WindowId, EventReceiver)>> {
+        let ret = self.shared.create_window(params);
+        Box::pin(async move { ret })
+    }
+
+    fn position(&self, _id: WindowId) -> BoxFuture<Option<ScreenPosition>> {
+        Box::pin(async move { None })
+    }
+
+    fn inner_size(&self, id: WindowId) -> BoxFuture<Option<PhysicalSize<u32>>> {
+        let state = self.shared.state.lock().unwrap();
+        let size = state.windows.get(&id.into()).map(|w| w.physical_size());
+        Box::pin(async move { size })
+    }
+
+    fn dpi(&self, id: WindowId) -> BoxFuture<Option<u32>> {
+        let state = self.shared.state.lock().unwrap();
+        let dpi = state.windows.get(&id.into()).map(|w| w.dpi);
+        Box::pin(async move { dpi })
+    }
+
+    fn show(&self, id: WindowId) {
+        self.with_window(id, |w| {
+            if !w.visible {
+                w.visible = true;
+                w.configured = false;
+                w.surface.commit();
+            }
+        });
+    }
+
+    fn hide(&self, id: WindowId) {
+        self.with_window(id, |w| {
+            if w.visible {
+                w.visible = false;
+                w.surface.attach(None, 0, 0);
+                w.surface.commit();
+            }
+        });
+    }
+
+    fn minimize(&self, id: WindowId) {
+        self.with_window(id, |w| w.toplevel.set_minimized());
+    }
+
+    fn maximize(&self, id: WindowId) {
+        self.show(id);
+        self.with_window(id, |w| w.toplevel.set_maximized());
+    }
+
+    fn restore(&self, id: WindowId) {
+        self.with_window(id, |w| w.toplevel.unset_maximized());
+    }
+
+    // Drag and drop (wl_data_device) is not supported.
+    fn accept_drop_files(&self, _id: WindowId, _accept: bool) {}
+
+    // Input methods (text-input) are not supported.
+    fn ime(&self, _id: WindowId, _enable: bool) {}
+
+    fn set_cursor(&self, id: WindowId, cursor: Cursor) {
+        let mut state = self.shared.state.lock().unwrap();
+        let Some(w) = state.windows.get_mut(&id.into()) else { return };
+        w.cursor = cursor;
+        self.shared.set_cursor_shape(&state);
+        drop(state);
+        self.shared.flush();
+    }
+
+    fn destroy(&self, id: WindowId) {
+        self.shared.destroy_window(id.into());
+    }
+
+    fn redraw(&self, id: WindowId, invalid_rect: Option<PhysicalRect<i32>>) {
+        let state = self.shared.state.lock().unwrap();
+        let Some(w) = state.windows.get(&id.into()) else { return };
+        if !w.configured || !w.visible {
+            return;
+        }
+        let size = w.physical_size();
+        Context::send_event(
+            id,
+            Event::Draw(events::Draw {
+                invalid_rect: invalid_rect.unwrap_or_else(|| {
+                    PhysicalRect::new((0, 0), (size.width as i32, size.height as i32))
+                }),
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+
+    // evdev key codes
+    const KEY_A: u32 = 30;
+    const KEY_LEFTSHIFT: u32 = 42;
+
+    fn params(title: &str) -> WindowParams {
+        WindowParams {
+            title: title.into(),
+            position: ScreenPosition::new(0, 0),
+            size: Box::new(|_| PhysicalSize::new(320, 240)),
+            style: Box::new(WindowStyle::default()),
+            visibility: true,
+            enable_ime: false,
+            visible_ime_candidate_window: false,
+            accept_drop_files: false,
+            enable_raw_input: false,
+            icon: None,
+            queue_policy: QueuePolicy::default(),
+            event_mask: EventMask::ALL,
+            reply_timeout: DEFAULT_REPLY_TIMEOUT,
+            forward_messages: false,
+        }
+    }
+
+    async fn wait_for(rx: &mut EventReceiver, f: impl Fn(&Event) -> bool) -> Event {
+        let wait = async {
+            loop {
+                let event = rx.recv().await;
+                if f(&event) {
+                    return event;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("the event did not come")
+    }
+
+    // Feeds input events to the dispatcher as if the compositor sent them to the window.
+    struct Input {
+        dispatcher: Dispatcher,
+        surface: wl_surface::WlSurface,
+    }
+
+    impl Input {
+        fn new(backend: &Wayland, id: WindowId) -> Self {
+            let state = backend.shared.state.lock().unwrap();
+            let surface = state.windows[&id.into()].surface.clone();
+            Self {
+                dispatcher: Dispatcher {
+                    shared: backend.shared.clone(),
+                },
+                surface,
+            }
+        }
+
+        fn keyboard(&mut self, events: impl IntoIterator<Item = wl_keyboard::Event>) {
+            let shared = self.dispatcher.shared.clone();
+            let keyboard = wl_keyboard::WlKeyboard::inert(shared.conn.backend().downgrade());
+            for event in events {
+                Dispatcher::event(
+                    &mut self.dispatcher,
+                    &keyboard,
+                    event,
+                    &(),
+                    &shared.conn,
+                    &shared.qh,
+                );
+            }
+        }
+
+        fn pointer(&mut self, events: impl IntoIterator<Item = wl_pointer::Event>) {
+            let shared = self.dispatcher.shared.clone();
+            let pointer = wl_pointer::WlPointer::inert(shared.conn.backend().downgrade());
+            for event in events {
+                Dispatcher::event(
+                    &mut self.dispatcher,
+                    &pointer,
+                    event,
+                    &(),
+                    &shared.conn,
+                    &shared.qh,
+                );
+            }
+        }
+    }
+
+    // The keymap of the default RMLVO names, which is US QWERTY unless overridden by `XKB_DEFAULT_*`.
+    fn default_xkb() -> Xkb {
+        let lib = xkb::xkbcommon_option().expect("cannot load xkbcommon");
+        unsafe {
+            let context = (lib.xkb_context_new)(xkb::xkb_context_flags::XKB_CONTEXT_NO_FLAGS);
+            let keymap = (lib.xkb_keymap_new_from_names)(
+                context,
+                std::ptr::null(),
+                xkb::xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            );
+            (lib.xkb_context_unref)(context);
+            Xkb::from_keymap(keymap).expect("cannot compile the keymap")
+        }
+    }
+
+    fn run(f: impl std::future::Future<Output = ()>) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(f);
+    }
+
+    // Run with `cargo test --features wayland -- --ignored` while a compositor such as
+    // `weston --backend=headless` is running.
+    #[test]
+    #[ignore = "requires a Wayland compositor"]
+    fn create_and_close_window() {
+        let backend = Wayland::connect().expect("cannot connect to the compositor");
+        run(async {
+            let (id, mut rx) = backend
+                .create_window(params("witas wayland test"))
+                .await
+                .unwrap();
+            wait_for(&mut rx, |e| matches!(e, Event::Draw(_))).await;
+            assert!(backend.inner_size(id).await.is_some());
+            backend.destroy(id);
+            wait_for(&mut rx, |e| matches!(e, Event::Closed)).await;
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a Wayland compositor"]
+    fn translate_keyboard() {
+        let backend = Wayland::connect().expect("cannot connect to the compositor");
+        run(async {
+            let (id, mut rx) = backend
+                .create_window(params("witas wayland keyboard"))
+                .await
+                .unwrap();
+            wait_for(&mut rx, |e| matches!(e, Event::Draw(_))).await;
+            backend.shared.state.lock().unwrap().xkb = Some(default_xkb());
+            let mut input = Input::new(&backend, id);
+            let key = |key, state| wl_keyboard::Event::Key {
+                serial: 0,
+                time: 0,
+                key,
+                state: WEnum::Value(state),
+            };
+            input.keyboard([
+                wl_keyboard::Event::Enter {
+                    serial: 0,
+                    surface: input.surface.clone(),
+                    keys: vec![],
+                },
+                wl_keyboard::Event::RepeatInfo { rate: 0, delay: 0 },
+                key(KEY_A, wl_keyboard::KeyState::Pressed),
+            ]);
+            let is_input = |e: &Event| matches!(e, Event::KeyInput(_) | Event::CharInput(_));
+            let Event::KeyInput(k) = wait_for(&mut rx, is_input).await else { panic!() };
+            assert_eq!(k.key_code.vkey, VirtualKey::A);
+            assert_eq!(k.key_state, KeyState::Pressed);
+            assert!(!k.prev_pressed);
+            let Event::CharInput(c) = wait_for(&mut rx, is_input).await else { panic!() };
+            assert_eq!(c.c, 'a');
+            input.keyboard([
+                key(KEY_A, wl_keyboard::KeyState::Released),
+                key(KEY_LEFTSHIFT, wl_keyboard::KeyState::Pressed),
+            ]);
+            // xkb gets the modifiers from the compositor, so Shift only changes the typed keysym
+            // after `Modifiers` comes.
+            input.keyboard([
+                wl_keyboard::Event::Modifiers {
+                    serial: 0,
+                    mods_depressed: 1,
+                    mods_latched: 0,
+                    mods_locked: 0,
+                    group: 0,
+                },
+                key(KEY_A, wl_keyboard::KeyState::Pressed),
+            ]);
+            let Event::KeyInput(k) = wait_for(&mut rx, is_input).await else { panic!() };
+            assert_eq!(k.key_state, KeyState::Released);
+            let Event::KeyInput(k) = wait_for(&mut rx, is_input).await else { panic!() };
+            assert_eq!(k.key_code.vkey, VirtualKey::Shift);
+            let Event::KeyInput(k) = wait_for(&mut rx, is_input).await else { panic!() };
+            assert_eq!(k.key_code.vkey, VirtualKey::A);
+            let Event::CharInput(c) = wait_for(&mut rx, is_input).await else { panic!() };
+            assert_eq!(c.c, 'A');
+            // The rate of 0 disables repeat.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            while let Some(e) = rx.try_recv().unwrap() {
+                assert!(!is_input(&e));
+            }
+            backend.destroy(id);
+            wait_for(&mut rx, |e| matches!(e, Event::Closed)).await;
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a Wayland compositor"]
+    fn repeat_keys() {
+        let backend = Wayland::connect().expect("cannot connect to the compositor");
+        run(async {
+            let (id, mut rx) = backend
+                .create_window(params("witas wayland key repeat"))
+                .await
+                .unwrap();
+            wait_for(&mut rx, |e| matches!(e, Event::Draw(_))).await;
+            backend.shared.state.lock().unwrap().xkb = Some(default_xkb());
+            let mut input = Input::new(&backend, id);
+            let key = |state| wl_keyboard::Event::Key {
+                serial: 0,
+                time: 0,
+                key: KEY_A,
+                state: WEnum::Value(state),
+            };
+            input.keyboard([
+                wl_keyboard::Event::Enter {
+                    serial: 0,
+                    surface: input.surface.clone(),
+                    keys: vec![],
+                },
+                wl_keyboard::Event::RepeatInfo {
+                    rate: 50,
+                    delay: 50,
+                },
+                key(wl_keyboard::KeyState::Pressed),
+            ]);
+            let is_key = |e: &Event| matches!(e, Event::KeyInput(_));
+            let Event::KeyInput(k) = wait_for(&mut rx, is_key).await else { panic!() };
+            assert!(!k.prev_pressed);
+            for _ in 0..3 {
+                let Event::KeyInput(k) = wait_for(&mut rx, is_key).await else { panic!() };
+                assert_eq!(k.key_code.vkey, VirtualKey::A);
+                assert_eq!(k.key_state, KeyState::Pressed);
+                assert!(k.prev_pressed);
+                let e = wait_for(&mut rx, |e| matches!(e, Event::CharInput(_))).await;
+                assert!(matches!(e, Event::CharInput(events::CharInput { c: 'a' })));
+            }
+            input.keyboard([key(wl_keyboard::KeyState::Released)]);
+            let Event::KeyInput(k) = wait_for(
+                &mut rx,
+                |e| matches!(e, Event::KeyInput(k) if k.key_state == KeyState::Released),
+            )
+            .await
+            else { panic!() };
+            assert_eq!(k.key_code.vkey, VirtualKey::A);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            while let Some(e) = rx.try_recv().unwrap() {
+                assert!(!is_key(&e));
+            }
+            backend.destroy(id);
+            wait_for(&mut rx, |e| matches!(e, Event::Closed)).await;
+        });
+    }
+
+    #[test]
+    #[ignore = "requires a Wayland compositor"]
+    fn translate_pointer() {
+        let backend = Wayland::connect().expect("cannot connect to the compositor");
+        run(async {
+            let (id, mut rx) = backend
+                .create_window(params("witas wayland pointer"))
+                .await
+                .unwrap();
+            wait_for(&mut rx, |e| matches!(e, Event::Draw(_))).await;
+            let scale = backend.shared.state.lock().unwrap().windows[&id.into()].dpi as f64
+                / DEFAULT_DPI as f64;
+            let position =
+                |x: f64, y: f64| PhysicalPosition::new((x * scale) as i32, (y * scale) as i32);
+            let mut input = Input::new(&backend, id);
+            input.pointer([wl_pointer::Event::Enter {
+                serial: 0,
+                surface: input.surface.clone(),
+                surface_x: 10.0,
+                surface_y: 20.0,
+            }]);
+            let Event::CursorEntered(e) =
+                wait_for(&mut rx, |e| matches!(e, Event::CursorEntered(_))).await
+            else { panic!() };
+            assert_eq!(e.mouse_state.position, position(10.0, 20.0));
+            input.pointer([
+                wl_pointer::Event::Motion {
+                    time: 0,
+                    surface_x: 50.0,
+                    surface_y: 60.0,
+                },
+                wl_pointer::Event::Button {
+                    serial: 0,
+                    time: 0,
+                    button: BTN_LEFT,
+                    state: WEnum::Value(wl_pointer::ButtonState::Pressed),
+                },
+                wl_pointer::Event::Axis {
+                    time: 0,
+                    axis: WEnum::Value(wl_pointer::Axis::VerticalScroll),
+                    value: AXIS_STEP,
+                },
+                wl_pointer::Event::Button {
+                    serial: 0,
+                    time: 0,
+                    button: BTN_LEFT,
+                    state: WEnum::Value(wl_pointer::ButtonState::Released),
+                },
+                wl_pointer::Event::Leave {
+                    serial: 0,
+                    surface: input.surface.clone(),
+                },
+            ]);
+            let Event::CursorMoved(e) =
+                wait_for(&mut rx, |e| matches!(e, Event::CursorMoved(_))).await
+            else { panic!() };
+            assert_eq!(e.mouse_state.position, position(50.0, 60.0));
+            let Event::MouseInput(e) =
+                wait_for(&mut rx, |e| matches!(e, Event::MouseInput(_))).await
+            else { panic!() };
+            assert_eq!(e.button, MouseButton::Left);
+            assert_eq!(e.button_state, ButtonState::Pressed);
+            assert!(e.mouse_state.buttons.contains(MouseButton::Left));
+            let Event::MouseWheel(e) =
+                wait_for(&mut rx, |e| matches!(e, Event::MouseWheel(_))).await
+            else { panic!() };
+            assert_eq!(e.axis, MouseWheelAxis::Vertical);
+            assert_eq!(e.distance, -(WHEEL_DELTA as i32));
+            let Event::MouseInput(e) =
+                wait_for(&mut rx, |e| matches!(e, Event::MouseInput(_))).await
+            else { panic!() };
+            assert_eq!(e.button_state, ButtonState::Released);
+            assert!(!e.mouse_state.buttons.contains(MouseButton::Left));
+            let Event::CursorLeft(e) =
+                wait_for(&mut rx, |e| matches!(e, Event::CursorLeft(_))).await
+            else { panic!() };
+            assert_eq!(e.mouse_state.position, position(50.0, 60.0));
+            backend.destroy(id);
+            wait_for(&mut rx, |e| matches!(e, Event::Closed)).await;
+        });
+    }
+}
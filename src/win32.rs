@@ -0,0 +1,316 @@
+use crate::backend::{BoxFuture, WindowParams};
+use crate::utility::adjust_window_rect;
+use crate::window::WindowId;
+use crate::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use tokio::sync::oneshot;
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, POINT, RECT, WPARAM},
+    Graphics::Gdi::{
+        GetStockObject, MonitorFromPoint, RedrawWindow, HBRUSH, MONITOR_DEFAULTTOPRIMARY,
+        RDW_INVALIDATE, WHITE_BRUSH,
+    },
+    System::LibraryLoader::GetModuleHandleW,
+    UI::HiDpi::{GetDpiForMonitor, GetDpiForWindow, MDT_DEFAULT},
+    UI::Shell::DragAcceptFiles,
+    UI::WindowsAndMessaging::{
+        CreateWindowExW, DestroyWindow, LoadCursorW, PostMessageW, RegisterClassExW, ShowWindow,
+        ShowWindowAsync, CS_HREDRAW, CS_VREDRAW, ICON_BIG, ICON_SMALL, IDC_ARROW, SW_HIDE,
+        SW_MINIMIZE, SW_RESTORE, SW_SHOW, SW_SHOWMAXIMIZED, WM_CLOSE, WM_SETICON, WNDCLASSEXW,
+    },
+};
+
+const WINDOW_CLASS_NAME: PCWSTR = windows::w!("witas_window_class");
+
+pub(crate) fn register_class() {
+    unsafe {
+        let wc = WNDCLASSEXW {
+            cbSize: std::mem::size_of::<WNDCLASSEXW>() as _,
+            style: CS_VREDRAW | CS_HREDRAW,
+            lpfnWndProc: Some(procedure::window_proc),
+            hInstance: GetModuleHandleW(None).unwrap(),
+            hCursor: LoadCursorW(None, IDC_ARROW).unwrap(),
+            lpszClassName: WINDOW_CLASS_NAME,
+            hbrBackground: HBRUSH(GetStockObject(WHITE_BRUSH).0),
+            ..Default::default()
+        };
+        if RegisterClassExW(&wc) == 0 {
+            panic!("RegisterClassExW failed");
+        }
+    }
+}
+
+fn get_dpi_from_point(pt: ScreenPosition) -> u32 {
+    let mut dpi_x = 0;
+    let mut dpi_y = 0;
+    unsafe {
+        GetDpiForMonitor(
+            MonitorFromPoint(POINT { x: pt.x, y: pt.y }, MONITOR_DEFAULTTOPRIMARY),
+            MDT_DEFAULT,
+            &mut dpi_x,
+            &mut dpi_y,
+        )
+        .unwrap_or(());
+    }
+    dpi_x
+}
+
+impl From<HWND> for WindowId {
+    #[inline]
+    fn from(src: HWND) -> Self {
+        Self(src.0)
+    }
+}
+
+impl From<WindowId> for HWND {
+    #[inline]
+    fn from(src: WindowId) -> Self {
+        HWND(src.0)
+    }
+}
+
+pub(crate) struct WindowProperties {
+    pub visible_ime_candidate_window: bool,
+    pub imm_context: Rc<ime::ImmContext>,
+    pub minimized: bool,
+    pub maximized: bool,
+    pub cursor: Cursor,
+    pub reply_timeout: std::time::Duration,
+    pub forward_messages: bool,
+}
+
+// Win32 APIs must not be called while the properties or the hooks are borrowed because they may
+// send messages to the window, which borrow them again.
+thread_local! {
+    static WINDOW_PROPERTIES: RefCell<HashMap<isize, WindowProperties>> =
+        RefCell::new(HashMap::new());
+    // Only windows with a hook have an entry, which is `None` while the hook runs.
+    static MESSAGE_HOOKS: RefCell<HashMap<isize, Option<MessageHook>>> =
+        RefCell::new(HashMap::new());
+}
+
+pub(crate) fn get_window_property<F, T>(hwnd: HWND, f: F) -> Option<T>
+where
+    F: FnOnce(&WindowProperties) -> T,
+{
+    WINDOW_PROPERTIES.with(|props| props.borrow().get(&hwnd.0).map(f))
+}
+
+pub(crate) fn set_window_property<F, T>(hwnd: HWND, f: F) -> Option<T>
+where
+    F: FnOnce(&mut WindowProperties) -> T,
+{
+    WINDOW_PROPERTIES.with(|props| props.borrow_mut().get_mut(&hwnd.0).map(f))
+}
+
+pub(crate) fn remove_window_properties(hwnd: HWND) {
+    // The properties and the hook are dropped after the borrows end.
+    let _props = WINDOW_PROPERTIES.with(|props| props.borrow_mut().remove(&hwnd.0));
+    let _hook = MESSAGE_HOOKS.with(|hooks| hooks.borrow_mut().remove(&hwnd.0));
+}
+
+/// Takes the hook out of the window until `put_message_hook` is called.
+pub(crate) fn take_message_hook(hwnd: HWND) -> Option<MessageHook> {
+    MESSAGE_HOOKS.with(|hooks| hooks.borrow_mut().get_mut(&hwnd.0)?.take())
+}
+
+/// Puts the hook back unless the window has been destroyed while it ran.
+pub(crate) fn put_message_hook(hwnd: HWND, hook: MessageHook) {
+    let hook = MESSAGE_HOOKS.with(|hooks| match hooks.borrow_mut().get_mut(&hwnd.0) {
+        Some(slot) => slot.replace(hook),
+        None => Some(hook),
+    });
+    drop(hook);
+}
+
+pub(crate) struct Win32;
+
+impl backend::Backend for Win32 {
+    fn create_window(&self, params: WindowParams) -> BoxFuture<Result<(WindowId, EventReceiver)>> {
+        let (tx, rx) = oneshot::channel();
+        UiThread::send_task(move || unsafe {
+            let title: HSTRING = params.title.into();
+            let dpi = get_dpi_from_point(params.position);
+            let size = (params.size)(dpi);
+            let style = params.style.style();
+            let ex_style = params.style.ex_style();
+            let rc = adjust_window_rect(size, style, false, ex_style, dpi);
+            let hinst = GetModuleHandleW(None).unwrap();
+            let hwnd = CreateWindowExW(
+                ex_style,
+                WINDOW_CLASS_NAME,
+                &title,
+                style,
+                params.position.x,
+                params.position.y,
+                rc.right - rc.left,
+                rc.bottom - rc.top,
+                None,
+                None,
+                hinst,
+                None,
+            );
+            if hwnd == HWND(0) {
+                tx.send(Err(Error::from_win32())).unwrap_or(());
+                return;
+            }
+            DragAcceptFiles(hwnd, params.accept_drop_files);
+            let props = WindowProperties {
+                visible_ime_candidate_window: params.visible_ime_candidate_window,
+                imm_context: Rc::new(ime::ImmContext::new(hwnd)),
+                minimized: false,
+                maximized: false,
+                cursor: Cursor::default(),
+                reply_timeout: params.reply_timeout,
+                forward_messages: params.forward_messages,
+            };
+            if params.enable_ime {
+                props.imm_context.enable();
+            } else {
+                props.imm_context.disable();
+            }
+            WINDOW_PROPERTIES.with(|map| {
+                map.borrow_mut().insert(hwnd.0, props);
+            });
+            if let Some(hook) = params.message_hook {
+                MESSAGE_HOOKS.with(|hooks| {
+                    hooks.borrow_mut().insert(hwnd.0, Some(hook));
+                });
+            }
+            if let Some(icon) = params.icon {
+                if let Ok(big) = load_icon(&icon, hinst) {
+                    PostMessageW(hwnd, WM_SETICON, WPARAM(ICON_BIG as _), LPARAM(big.0 as _));
+                }
+                if let Ok(small) = load_small_icon(&icon, hinst) {
+                    PostMessageW(
+                        hwnd,
+                        WM_SETICON,
+                        WPARAM(ICON_SMALL as _),
+                        LPARAM(small.0 as _),
+                    );
+                }
+            }
+            let enable_raw_input = params.enable_raw_input
+                && raw_input::register_devices(hwnd, raw_input::WindowState::Foreground).is_ok();
+            let rx = Context::register_window(
+                hwnd,
+                enable_raw_input,
+                params.queue_policy,
+                params.event_mask,
+            );
+            if params.visibility {
+                ShowWindow(hwnd, SW_SHOW);
+            }
+            tx.send(Ok((hwnd.into(), rx))).unwrap_or(());
+        });
+        Box::pin(async move { rx.await.unwrap_or(Err(Error::UiThreadClosed)) })
+    }
+
+    fn position(&self, id: WindowId) -> BoxFuture<Option<ScreenPosition>> {
+        let (tx, rx) = oneshot::channel();
+        UiThread::send_task(move || {
+            let rc = utility::get_window_rect(HWND::from(id));
+            tx.send((rc.left, rc.top).into()).unwrap_or(());
+        });
+        Box::pin(async move { rx.await.ok() })
+    }
+
+    fn inner_size(&self, id: WindowId) -> BoxFuture<Option<PhysicalSize<u32>>> {
+        let (tx, rx) = oneshot::channel();
+        UiThread::send_task(move || {
+            let rc = utility::get_client_rect(HWND::from(id));
+            tx.send(((rc.right - rc.left) as u32, (rc.bottom - rc.top) as u32).into())
+                .unwrap_or(());
+        });
+        Box::pin(async move { rx.await.ok() })
+    }
+
+    fn dpi(&self, id: WindowId) -> BoxFuture<Option<u32>> {
+        let (tx, rx) = oneshot::channel();
+        UiThread::send_task(move || unsafe {
+            let dpi = GetDpiForWindow(HWND::from(id));
+            tx.send(dpi).unwrap_or(());
+        });
+        Box::pin(async move { rx.await.ok() })
+    }
+
+    fn show(&self, id: WindowId) {
+        unsafe {
+            ShowWindowAsync(HWND::from(id), SW_SHOW);
+        }
+    }
+
+    fn hide(&self, id: WindowId) {
+        unsafe {
+            ShowWindowAsync(HWND::from(id), SW_HIDE);
+        }
+    }
+
+    fn minimize(&self, id: WindowId) {
+        unsafe {
+            ShowWindowAsync(HWND::from(id), SW_MINIMIZE);
+        }
+    }
+
+    fn maximize(&self, id: WindowId) {
+        unsafe {
+            ShowWindowAsync(HWND::from(id), SW_SHOWMAXIMIZED);
+        }
+    }
+
+    fn restore(&self, id: WindowId) {
+        unsafe {
+            ShowWindowAsync(HWND::from(id), SW_RESTORE);
+        }
+    }
+
+    fn accept_drop_files(&self, id: WindowId, accept: bool) {
+        UiThread::send_task(move || unsafe {
+            DragAcceptFiles(HWND::from(id), accept);
+        });
+    }
+
+    fn ime(&self, id: WindowId, enable: bool) {
+        UiThread::send_task(move || {
+            let imm_context =
+                get_window_property(HWND::from(id), |props| props.imm_context.clone());
+            let Some(imm_context) = imm_context else { return };
+            if enable {
+                imm_context.enable();
+            } else {
+                imm_context.disable();
+            }
+        });
+    }
+
+    fn set_cursor(&self, id: WindowId, cursor: Cursor) {
+        UiThread::send_task(move || {
+            if set_window_property(HWND::from(id), |props| props.cursor = cursor).is_some() {
+                cursor.set();
+            }
+        });
+    }
+
+    fn close(&self, id: WindowId) {
+        unsafe {
+            PostMessageW(HWND::from(id), WM_CLOSE, WPARAM(0), LPARAM(0));
+        }
+    }
+
+    fn destroy(&self, id: WindowId) {
+        UiThread::send_task(move || unsafe {
+            DestroyWindow(HWND::from(id));
+        });
+    }
+
+    fn redraw(&self, id: WindowId, invalid_rect: Option<PhysicalRect<i32>>) {
+        UiThread::send_task(move || unsafe {
+            let rc: Option<RECT> = invalid_rect.map(|rc| rc.into());
+            let p = rc.as_ref().map(|p| p as *const _);
+            RedrawWindow(HWND::from(id), p, None, RDW_INVALIDATE);
+        });
+    }
+}
@@ -1,70 +1,53 @@
-use crate::utility::adjust_window_rect;
 use crate::*;
-use tokio::sync::{mpsc, oneshot};
-use windows::core::{HSTRING, PCWSTR};
-use windows::Win32::{
-    Foundation::{HWND, LPARAM, POINT, RECT, WPARAM},
-    Graphics::Gdi::{
-        GetStockObject, MonitorFromPoint, RedrawWindow, HBRUSH, MONITOR_DEFAULTTOPRIMARY,
-        RDW_INVALIDATE, WHITE_BRUSH,
-    },
-    System::LibraryLoader::GetModuleHandleW,
-    UI::HiDpi::{GetDpiForMonitor, GetDpiForWindow, MDT_DEFAULT},
-    UI::Shell::DragAcceptFiles,
-    UI::WindowsAndMessaging::{
-        CreateWindowExW, LoadCursorW, PostMessageW, RegisterClassExW, ShowWindow, ShowWindowAsync,
-        CS_HREDRAW, CS_VREDRAW, ICON_BIG, ICON_SMALL, IDC_ARROW, SW_HIDE, SW_MINIMIZE, SW_RESTORE,
-        SW_SHOW, SW_SHOWMAXIMIZED, WINDOW_EX_STYLE, WINDOW_STYLE, WM_CLOSE, WM_SETICON,
-        WNDCLASSEXW, WS_CAPTION, WS_MAXIMIZEBOX, WS_MINIMIZEBOX, WS_OVERLAPPED,
-        WS_OVERLAPPEDWINDOW, WS_POPUP, WS_SYSMENU, WS_THICKFRAME,
-    },
+use std::any::Any;
+use std::marker::PhantomData;
+use std::time::Duration;
+#[cfg(windows)]
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    WINDOW_EX_STYLE, WINDOW_STYLE, WS_CAPTION, WS_MAXIMIZEBOX, WS_MINIMIZEBOX, WS_OVERLAPPED,
+    WS_POPUP, WS_SYSMENU, WS_THICKFRAME,
 };
 
-const WINDOW_CLASS_NAME: PCWSTR = windows::w!("witas_window_class");
-
-pub(crate) fn register_class() {
-    unsafe {
-        let wc = WNDCLASSEXW {
-            cbSize: std::mem::size_of::<WNDCLASSEXW>() as _,
-            style: CS_VREDRAW | CS_HREDRAW,
-            lpfnWndProc: Some(procedure::window_proc),
-            hInstance: GetModuleHandleW(None).unwrap(),
-            hCursor: LoadCursorW(None, IDC_ARROW).unwrap(),
-            lpszClassName: WINDOW_CLASS_NAME,
-            hbrBackground: HBRUSH(GetStockObject(WHITE_BRUSH).0),
-            ..Default::default()
-        };
-        if RegisterClassExW(&wc) == 0 {
-            panic!("RegisterClassExW failed");
-        }
+/// Identifies a window of a backend.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowId(pub(crate) isize);
+
+impl WindowId {
+    #[inline]
+    pub const fn from_raw(raw: isize) -> Self {
+        Self(raw)
+    }
+
+    #[inline]
+    pub const fn raw(&self) -> isize {
+        self.0
     }
 }
 
-fn get_dpi_from_point(pt: ScreenPosition) -> u32 {
-    let mut dpi_x = 0;
-    let mut dpi_y = 0;
-    unsafe {
-        GetDpiForMonitor(
-            MonitorFromPoint(POINT { x: pt.x, y: pt.y }, MONITOR_DEFAULTTOPRIMARY),
-            MDT_DEFAULT,
-            &mut dpi_x,
-            &mut dpi_y,
-        )
-        .unwrap_or(());
+// X11 windows and Wayland surfaces are identified by `u32`.
+#[cfg(all(unix, any(feature = "x11", feature = "wayland")))]
+impl From<u32> for WindowId {
+    #[inline]
+    fn from(src: u32) -> Self {
+        Self(src as isize)
     }
-    dpi_x
 }
 
-pub(crate) struct WindowProperties {
-    pub visible_ime_candidate_window: bool,
-    pub imm_context: ime::ImmContext,
-    pub minimized: bool,
-    pub maximized: bool,
-    pub cursor: Cursor,
+#[cfg(all(unix, any(feature = "x11", feature = "wayland")))]
+impl From<WindowId> for u32 {
+    #[inline]
+    fn from(src: WindowId) -> Self {
+        src.0 as _
+    }
 }
 
 pub trait Style {
+    #[cfg(windows)]
     fn style(&self) -> WINDOW_STYLE;
+    #[cfg(windows)]
     fn ex_style(&self) -> WINDOW_EX_STYLE;
 }
 
@@ -72,11 +55,13 @@ pub trait Style {
 pub struct BorderlessStyle;
 
 impl Style for BorderlessStyle {
+    #[cfg(windows)]
     #[inline]
     fn style(&self) -> WINDOW_STYLE {
         WS_POPUP
     }
 
+    #[cfg(windows)]
     #[inline]
     fn ex_style(&self) -> WINDOW_EX_STYLE {
         WINDOW_EX_STYLE::default()
@@ -85,16 +70,18 @@ impl Style for BorderlessStyle {
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct WindowStyle {
-    style: WINDOW_STYLE,
-    ex_style: WINDOW_EX_STYLE,
+    resizable: bool,
+    has_minimize_box: bool,
+    has_maximize_box: bool,
 }
 
 impl WindowStyle {
     #[inline]
     pub fn dialog() -> Self {
         Self {
-            style: WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU,
-            ex_style: WINDOW_EX_STYLE::default(),
+            resizable: false,
+            has_minimize_box: false,
+            has_maximize_box: false,
         }
     }
 
@@ -105,31 +92,19 @@ impl WindowStyle {
 
     #[inline]
     pub fn resizable(mut self, resizable: bool) -> Self {
-        if resizable {
-            self.style |= WS_THICKFRAME;
-        } else {
-            self.style &= !WS_THICKFRAME;
-        }
+        self.resizable = resizable;
         self
     }
 
     #[inline]
     pub fn has_minimize_box(mut self, has_box: bool) -> Self {
-        if has_box {
-            self.style |= WS_MINIMIZEBOX;
-        } else {
-            self.style &= !WS_MINIMIZEBOX;
-        }
+        self.has_minimize_box = has_box;
         self
     }
 
     #[inline]
     pub fn has_maximize_box(mut self, has_box: bool) -> Self {
-        if has_box {
-            self.style |= WS_MAXIMIZEBOX;
-        } else {
-            self.style &= !WS_MAXIMIZEBOX;
-        }
+        self.has_maximize_box = has_box;
         self
     }
 }
@@ -138,24 +113,42 @@ impl Default for WindowStyle {
     #[inline]
     fn default() -> Self {
         Self {
-            style: WS_OVERLAPPEDWINDOW,
-            ex_style: WINDOW_EX_STYLE::default(),
+            resizable: true,
+            has_minimize_box: true,
+            has_maximize_box: true,
         }
     }
 }
 
 impl Style for WindowStyle {
+    #[cfg(windows)]
     #[inline]
     fn style(&self) -> WINDOW_STYLE {
-        self.style
+        let mut style = WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU;
+        if self.resizable {
+            style |= WS_THICKFRAME;
+        }
+        if self.has_minimize_box {
+            style |= WS_MINIMIZEBOX;
+        }
+        if self.has_maximize_box {
+            style |= WS_MAXIMIZEBOX;
+        }
+        style
     }
 
+    #[cfg(windows)]
     #[inline]
     fn ex_style(&self) -> WINDOW_EX_STYLE {
-        self.ex_style
+        WINDOW_EX_STYLE::default()
     }
 }
 
+/// Runs on the UI thread before witas handles a message, and consumes the message by returning
+/// `Some`.
+#[cfg(windows)]
+pub type MessageHook = Box<dyn FnMut(HWND, u32, WPARAM, LPARAM) -> Option<LRESULT> + Send>;
+
 pub struct WindowBuilder<Title = (), Sz = ()> {
     title: Title,
     position: ScreenPosition,
@@ -167,6 +160,12 @@ pub struct WindowBuilder<Title = (), Sz = ()> {
     accept_drop_files: bool,
     enable_raw_input: bool,
     icon: Option<Icon>,
+    queue_policy: QueuePolicy,
+    event_mask: EventMask,
+    reply_timeout: Duration,
+    #[cfg(windows)]
+    message_hook: Option<MessageHook>,
+    forward_messages: bool,
 }
 
 impl WindowBuilder<(), ()> {
@@ -182,6 +181,12 @@ impl WindowBuilder<(), ()> {
             accept_drop_files: false,
             enable_raw_input: false,
             icon: None,
+            queue_policy: QueuePolicy::default(),
+            event_mask: EventMask::ALL,
+            reply_timeout: DEFAULT_REPLY_TIMEOUT,
+            #[cfg(windows)]
+            message_hook: None,
+            forward_messages: false,
         }
     }
 }
@@ -200,6 +205,12 @@ impl<Title, Sz> WindowBuilder<Title, Sz> {
             accept_drop_files: self.accept_drop_files,
             enable_raw_input: self.enable_raw_input,
             icon: self.icon,
+            queue_policy: self.queue_policy,
+            event_mask: self.event_mask,
+            reply_timeout: self.reply_timeout,
+            #[cfg(windows)]
+            message_hook: self.message_hook,
+            forward_messages: self.forward_messages,
         }
     }
 
@@ -225,6 +236,12 @@ impl<Title, Sz> WindowBuilder<Title, Sz> {
             accept_drop_files: self.accept_drop_files,
             enable_raw_input: self.enable_raw_input,
             icon: self.icon,
+            queue_policy: self.queue_policy,
+            event_mask: self.event_mask,
+            reply_timeout: self.reply_timeout,
+            #[cfg(windows)]
+            message_hook: self.message_hook,
+            forward_messages: self.forward_messages,
         }
     }
 
@@ -269,6 +286,50 @@ impl<Title, Sz> WindowBuilder<Title, Sz> {
         self.icon = Some(icon);
         self
     }
+
+    /// Sets how events are queued until they are received. See `QueuePolicy`.
+    #[inline]
+    pub fn queue_policy(mut self, policy: QueuePolicy) -> Self {
+        self.queue_policy = policy;
+        self
+    }
+
+    /// Discards events outside `mask` before they are queued.
+    #[inline]
+    pub fn events(mut self, mask: EventMask) -> Self {
+        self.event_mask = mask;
+        self
+    }
+
+    /// Sets how long the UI thread waits for a `Reply` such as the position of
+    /// `Event::ImeStartComposition`. The default is `DEFAULT_REPLY_TIMEOUT`.
+    #[inline]
+    pub fn reply_timeout(mut self, timeout: Duration) -> Self {
+        self.reply_timeout = timeout;
+        self
+    }
+
+    /// Sets a hook which sees every message of the window before witas.
+    ///
+    /// Messages sent before the window is created, such as `WM_CREATE`, and messages sent while
+    /// the hook runs do not reach it.
+    #[cfg(windows)]
+    #[inline]
+    pub fn message_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(HWND, u32, WPARAM, LPARAM) -> Option<LRESULT> + Send + 'static,
+    {
+        self.message_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Sends `Event::Message` of the messages which witas does not handle. They are still passed
+    /// to `DefWindowProcW`.
+    #[inline]
+    pub fn forward_messages(mut self, forward: bool) -> Self {
+        self.forward_messages = forward;
+        self
+    }
 }
 
 impl<Sz> WindowBuilder<String, Sz>
@@ -279,7 +340,7 @@ where
     pub fn build(self) -> Build<Sz> {
         Build {
             builder: Some(self),
-            rx: None,
+            future: None,
         }
     }
 }
@@ -298,8 +359,8 @@ where
 }
 
 pub struct Recv<'a, T> {
-    hwnd: HWND,
-    rx: &'a mut mpsc::UnboundedReceiver<T>,
+    rx: &'a mut queue::Receiver<T>,
+    timestamp: &'a mut Option<Timestamp>,
 }
 
 impl<'a> std::future::Future for Recv<'a, Event> {
@@ -310,14 +371,32 @@ impl<'a> std::future::Future for Recv<'a, Event> {
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
         let this = self.get_mut();
-        match this.rx.try_recv() {
-            Ok(event) => std::task::Poll::Ready(event),
-            Err(mpsc::error::TryRecvError::Empty) => {
-                Context::set_waker(this.hwnd, cx.waker().clone());
-                std::task::Poll::Pending
+        this.rx.poll_recv(cx).map(|item| match item {
+            Some((event, timestamp)) => {
+                *this.timestamp = Some(timestamp);
+                event
             }
-            Err(mpsc::error::TryRecvError::Disconnected) => std::task::Poll::Ready(Event::Quit),
-        }
+            None => Event::Quit,
+        })
+    }
+}
+
+impl<'a> std::future::Future for Recv<'a, (WindowId, Event)> {
+    type Output = (WindowId, Event);
+
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let this = self.get_mut();
+        this.rx.poll_recv(cx).map(|item| match item {
+            Some((item, timestamp)) => {
+                *this.timestamp = Some(timestamp);
+                item
+            }
+            // The sender is owned by the static `Context` and is only dropped after the receiver.
+            None => unreachable!("the sender of AppEventReceiver was dropped"),
+        })
     }
 }
 
@@ -329,85 +408,281 @@ impl<'a> std::future::Future for Recv<'a, raw_input::RawInputEvent> {
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
         let this = self.get_mut();
-        match this.rx.try_recv() {
-            Ok(event) => std::task::Poll::Ready(event),
-            Err(mpsc::error::TryRecvError::Empty) => {
-                Context::set_raw_input_waker(this.hwnd, cx.waker().clone());
-                std::task::Poll::Pending
-            }
-            Err(mpsc::error::TryRecvError::Disconnected) => {
-                std::task::Poll::Ready(raw_input::RawInputEvent::Quit)
+        this.rx.poll_recv(cx).map(|item| match item {
+            Some((event, timestamp)) => {
+                *this.timestamp = Some(timestamp);
+                event
             }
+            None => raw_input::RawInputEvent::Quit,
+        })
+    }
+}
+
+/// Posts `Event::User` to a window from any thread.
+///
+/// Posted events are queued in order with the events from the OS.
+pub struct EventSender<T> {
+    id: WindowId,
+    _t: PhantomData<fn(T)>,
+}
+
+impl<T: Any + Send + Sync> EventSender<T> {
+    #[inline]
+    fn new(id: WindowId) -> Self {
+        Self {
+            id,
+            _t: PhantomData,
+        }
+    }
+
+    /// Returns `Err(Error::WindowClosed)` when the window has been closed.
+    pub fn send(&self, value: T) -> Result<()> {
+        if !Context::send_event(self.id, Event::User(events::User::new(value))) {
+            return Err(Error::WindowClosed);
+        }
+        Ok(())
+    }
+}
+
+impl<T> Clone for EventSender<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            _t: PhantomData,
         }
     }
 }
 
 pub struct EventReceiver {
-    hwnd: HWND,
-    rx: mpsc::UnboundedReceiver<Event>,
-    raw_input_rx: Option<mpsc::UnboundedReceiver<raw_input::RawInputEvent>>,
+    id: WindowId,
+    rx: queue::Receiver<Event>,
+    raw_input_rx: Option<queue::Receiver<raw_input::RawInputEvent>>,
+    timestamp: Option<Timestamp>,
 }
 
 impl EventReceiver {
+    pub(crate) fn new(
+        id: WindowId,
+        rx: queue::Receiver<Event>,
+        raw_input_rx: Option<queue::Receiver<raw_input::RawInputEvent>>,
+    ) -> Self {
+        Self {
+            id,
+            rx,
+            raw_input_rx,
+            timestamp: None,
+        }
+    }
+
     #[inline]
-    pub fn recv(&mut self) -> Recv<Event> {
+    pub fn recv(&mut self) -> Recv<'_, Event> {
         Recv {
-            hwnd: self.hwnd,
             rx: &mut self.rx,
+            timestamp: &mut self.timestamp,
         }
     }
 
     #[inline]
     pub fn try_recv(&mut self) -> Result<Option<Event>> {
         match self.rx.try_recv() {
-            Ok(event) => Ok(Some(event)),
-            Err(mpsc::error::TryRecvError::Empty) => Ok(None),
-            Err(mpsc::error::TryRecvError::Disconnected) => Err(Error::UiThreadClosed),
+            Ok((event, timestamp)) => {
+                self.timestamp = Some(timestamp);
+                Ok(Some(event))
+            }
+            Err(queue::TryRecvError::Empty) => Ok(None),
+            Err(queue::TryRecvError::Disconnected) => Err(Error::UiThreadClosed),
         }
     }
 
+    /// Returns the timestamp of the event which was returned last by `recv`, `try_recv`, the
+    /// `Stream` impl or `timestamped`.
+    #[inline]
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    /// Returns a stream which yields each event with its timestamp.
+    #[inline]
+    pub fn timestamped(&mut self) -> Timestamped<'_> {
+        Timestamped { rx: self }
+    }
+
+    /// Creates another receiver which gets a clone of every event sent after this call.
+    ///
+    /// Each receiver has its own queue with the same `QueuePolicy`, and raw input events are
+    /// delivered only to the receiver returned by `WindowBuilder::build`.
+    ///
+    /// The answers of `Event::Resizing`, `Event::ImeStartComposition` and `Event::CloseRequested`
+    /// are taken from one receiver which has been polled by `recv`, `try_recv` or the stream: the
+    /// receiver returned by `WindowBuilder::build`, or else the first subscriber, or else the
+    /// first `AppEventReceiver`. The others get copies whose answers are ignored. When no receiver
+    /// has been polled, the default answers are taken without waiting.
+    #[inline]
+    pub fn subscribe(&self) -> EventReceiver {
+        Context::subscribe(self.id, self.rx.policy())
+    }
+
+    #[inline]
+    pub fn event_sender<T: Any + Send + Sync>(&self) -> EventSender<T> {
+        EventSender::new(self.id)
+    }
+
+    /// Returns the statistics of the queue of this receiver.
+    #[inline]
+    pub fn stats(&self) -> QueueStats {
+        self.rx.stats()
+    }
+
     #[inline]
     pub fn take_raw_input_receiver(&mut self) -> Option<RawInputEventRecevier> {
         self.raw_input_rx.take().map(|rx| RawInputEventRecevier {
-            hwnd: self.hwnd,
             rx,
+            timestamp: None,
+        })
+    }
+}
+
+/// Yields events until the window is closed. `Event::Quit` is the last item when the last window
+/// is closed.
+///
+/// The items do not carry their timestamps. `EventReceiver::timestamp` returns the timestamp of
+/// the item yielded last, and `EventReceiver::timestamped` yields the timestamps with the events.
+impl futures_core::Stream for EventReceiver {
+    type Item = Event;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.rx.poll_recv(cx).map(|item| {
+            let (event, timestamp) = item?;
+            this.timestamp = Some(timestamp);
+            Some(event)
         })
     }
 }
 
+/// Yields events with their timestamps. Returned by `EventReceiver::timestamped`.
+pub struct Timestamped<'a> {
+    rx: &'a mut EventReceiver,
+}
+
+impl futures_core::Stream for Timestamped<'_> {
+    type Item = (Event, Timestamp);
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.rx.rx.poll_recv(cx).map(|item| {
+            let (event, timestamp) = item?;
+            this.rx.timestamp = Some(timestamp);
+            Some((event, timestamp))
+        })
+    }
+}
+
+/// Receives the events of every window.
+///
+/// Events sent before `AppEventReceiver::new` are not delivered. `Event::Quit` is delivered once
+/// with the window which was closed last, or with one of the remaining windows when the UI thread
+/// stops by a panic.
+///
+/// The answers of `Event::Resizing`, `Event::ImeStartComposition` and `Event::CloseRequested` are
+/// taken from the first polled `AppEventReceiver` only when no `EventReceiver` of the window has
+/// been polled. See `EventReceiver::subscribe`.
+pub struct AppEventReceiver {
+    rx: queue::Receiver<(WindowId, Event)>,
+    timestamp: Option<Timestamp>,
+}
+
+impl AppEventReceiver {
+    #[inline]
+    pub fn new() -> Self {
+        Context::subscribe_app()
+    }
+
+    pub(crate) fn from_queue(rx: queue::Receiver<(WindowId, Event)>) -> Self {
+        Self {
+            rx,
+            timestamp: None,
+        }
+    }
+
+    #[inline]
+    pub fn recv(&mut self) -> Recv<'_, (WindowId, Event)> {
+        Recv {
+            rx: &mut self.rx,
+            timestamp: &mut self.timestamp,
+        }
+    }
+
+    #[inline]
+    pub fn try_recv(&mut self) -> Option<(WindowId, Event)> {
+        let (item, timestamp) = self.rx.try_recv().ok()?;
+        self.timestamp = Some(timestamp);
+        Some(item)
+    }
+
+    /// Returns the timestamp of the event which was returned last by `recv` or `try_recv`.
+    #[inline]
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+}
+
+impl Default for AppEventReceiver {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct RawInputEventRecevier {
-    hwnd: HWND,
-    rx: mpsc::UnboundedReceiver<raw_input::RawInputEvent>,
+    rx: queue::Receiver<raw_input::RawInputEvent>,
+    timestamp: Option<Timestamp>,
 }
 
 impl RawInputEventRecevier {
     #[inline]
-    pub fn recv(&mut self) -> Recv<raw_input::RawInputEvent> {
+    pub fn recv(&mut self) -> Recv<'_, raw_input::RawInputEvent> {
         Recv {
-            hwnd: self.hwnd,
             rx: &mut self.rx,
+            timestamp: &mut self.timestamp,
         }
     }
 
     #[inline]
     pub fn try_recv(&mut self) -> Result<Option<raw_input::RawInputEvent>> {
         match self.rx.try_recv() {
-            Ok(event) => Ok(Some(event)),
-            Err(mpsc::error::TryRecvError::Empty) => Ok(None),
-            Err(mpsc::error::TryRecvError::Disconnected) => Err(Error::UiThreadClosed),
+            Ok((event, timestamp)) => {
+                self.timestamp = Some(timestamp);
+                Ok(Some(event))
+            }
+            Err(queue::TryRecvError::Empty) => Ok(None),
+            Err(queue::TryRecvError::Disconnected) => Err(Error::UiThreadClosed),
         }
     }
-}
 
-type BuildResult = Result<(
-    HWND,
-    mpsc::UnboundedReceiver<Event>,
-    Option<mpsc::UnboundedReceiver<raw_input::RawInputEvent>>,
-)>;
+    /// Returns the timestamp of the event which was returned last by `recv` or `try_recv`.
+    #[inline]
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    /// Returns the statistics of the queue of this receiver.
+    #[inline]
+    pub fn stats(&self) -> QueueStats {
+        self.rx.stats()
+    }
+}
 
 pub struct Build<Sz> {
     builder: Option<WindowBuilder<String, Sz>>,
-    rx: Option<mpsc::UnboundedReceiver<BuildResult>>,
+    future: Option<backend::BoxFuture<Result<(WindowId, EventReceiver)>>>,
 }
 
 impl<Sz> std::future::Future for Build<Sz>
@@ -422,115 +697,38 @@ where
     ) -> std::task::Poll<Self::Output> {
         let this = self.get_mut();
         if let Some(builder) = this.builder.take() {
-            let (tx, rx) = mpsc::unbounded_channel::<BuildResult>();
-            let waker = cx.waker().clone();
-            let create_window = move || unsafe {
-                let title: HSTRING = builder.title.into();
-                let dpi = get_dpi_from_point(builder.position);
-                let size = builder.size.to_physical(dpi);
-                let style = builder.style.style();
-                let ex_style = builder.style.ex_style();
-                let rc = adjust_window_rect(size, style, false, ex_style, dpi);
-                let hinst = GetModuleHandleW(None).unwrap();
-                let hwnd = CreateWindowExW(
-                    ex_style,
-                    WINDOW_CLASS_NAME,
-                    &title,
-                    style,
-                    builder.position.x,
-                    builder.position.y,
-                    rc.right - rc.left,
-                    rc.bottom - rc.top,
-                    None,
-                    None,
-                    hinst,
-                    None,
-                );
-                if hwnd == HWND(0) {
-                    tx.send(Err(Error::from_win32())).unwrap_or(());
-                    waker.wake();
-                    return;
-                }
-                DragAcceptFiles(hwnd, builder.accept_drop_files);
-                let props = WindowProperties {
-                    visible_ime_candidate_window: builder.visible_ime_candidate_window,
-                    imm_context: ime::ImmContext::new(hwnd),
-                    minimized: false,
-                    maximized: false,
-                    cursor: Cursor::default(),
-                };
-                if builder.enable_ime {
-                    props.imm_context.enable();
-                } else {
-                    props.imm_context.disable();
-                }
-                if let Some(icon) = builder.icon {
-                    if let Ok(big) = load_icon(&icon, hinst) {
-                        PostMessageW(hwnd, WM_SETICON, WPARAM(ICON_BIG as _), LPARAM(big.0 as _));
-                    }
-                    if let Ok(small) = load_small_icon(&icon, hinst) {
-                        PostMessageW(
-                            hwnd,
-                            WM_SETICON,
-                            WPARAM(ICON_SMALL as _),
-                            LPARAM(small.0 as _),
-                        );
-                    }
-                }
-                let (event_rx, raw_input_event_rx) = {
-                    let (event_tx, event_rx) = mpsc::unbounded_channel();
-                    let raw_input_event_rx = if builder.enable_raw_input {
-                        if raw_input::register_devices(hwnd, raw_input::WindowState::Foreground)
-                            .is_ok()
-                        {
-                            let (raw_input_event_tx, raw_input_event_rx) =
-                                mpsc::unbounded_channel();
-                            Context::register_window(
-                                hwnd,
-                                props,
-                                event_tx,
-                                Some(raw_input_event_tx),
-                            );
-                            Some(raw_input_event_rx)
-                        } else {
-                            None
-                        }
-                    } else {
-                        Context::register_window(hwnd, props, event_tx, None);
-                        None
-                    };
-                    (event_rx, raw_input_event_rx)
-                };
-                if builder.visibility {
-                    ShowWindow(hwnd, SW_SHOW);
-                }
-                tx.send(Ok((hwnd, event_rx, raw_input_event_rx)))
-                    .unwrap_or(());
-                waker.wake();
+            let size = builder.size;
+            let params = backend::WindowParams {
+                title: builder.title,
+                position: builder.position,
+                size: Box::new(move |dpi| size.to_physical(dpi)),
+                style: builder.style,
+                visibility: builder.visibility,
+                enable_ime: builder.enable_ime,
+                visible_ime_candidate_window: builder.visible_ime_candidate_window,
+                accept_drop_files: builder.accept_drop_files,
+                enable_raw_input: builder.enable_raw_input,
+                icon: builder.icon,
+                queue_policy: builder.queue_policy,
+                event_mask: builder.event_mask,
+                reply_timeout: builder.reply_timeout,
+                #[cfg(windows)]
+                message_hook: builder.message_hook,
+                forward_messages: builder.forward_messages,
             };
-            UiThread::send_task(create_window);
-            this.rx = Some(rx);
-            return std::task::Poll::Pending;
-        }
-        match this.rx.as_mut().unwrap().try_recv() {
-            Ok(ret) => std::task::Poll::Ready(ret.map(|(hwnd, rx, raw_input_rx)| {
-                let rx = EventReceiver {
-                    hwnd,
-                    rx,
-                    raw_input_rx,
-                };
-                (Window { hwnd }, rx)
-            })),
-            Err(mpsc::error::TryRecvError::Empty) => std::task::Poll::Pending,
-            Err(mpsc::error::TryRecvError::Disconnected) => {
-                std::task::Poll::Ready(Err(Error::UiThreadClosed))
-            }
+            this.future = Some(backend::get().create_window(params));
         }
+        this.future
+            .as_mut()
+            .unwrap()
+            .as_mut()
+            .poll(cx)
+            .map(|ret| ret.map(|(id, rx)| (Window { id }, rx)))
     }
 }
 
 pub struct Window {
-    hwnd: HWND,
+    id: WindowId,
 }
 
 impl Window {
@@ -545,13 +743,7 @@ impl Window {
         if self.is_closed() {
             return None;
         }
-        let hwnd = self.hwnd;
-        let (tx, rx) = oneshot::channel();
-        UiThread::send_task(move || {
-            let rc = utility::get_window_rect(hwnd);
-            tx.send((rc.left, rc.top).into()).unwrap_or(());
-        });
-        rx.await.ok()
+        backend::get().position(self.id).await
     }
 
     #[inline]
@@ -559,14 +751,7 @@ impl Window {
         if self.is_closed() {
             return None;
         }
-        let hwnd = self.hwnd;
-        let (tx, rx) = oneshot::channel();
-        UiThread::send_task(move || {
-            let rc = utility::get_client_rect(hwnd);
-            tx.send(((rc.right - rc.left) as u32, (rc.bottom - rc.top) as u32).into())
-                .unwrap_or(());
-        });
-        rx.await.ok()
+        backend::get().inner_size(self.id).await
     }
 
     #[inline]
@@ -574,13 +759,7 @@ impl Window {
         if self.is_closed() {
             return None;
         }
-        let hwnd = self.hwnd;
-        let (tx, rx) = oneshot::channel();
-        UiThread::send_task(move || unsafe {
-            let dpi = GetDpiForWindow(hwnd);
-            tx.send(dpi).unwrap_or(());
-        });
-        rx.await.ok()
+        backend::get().dpi(self.id).await
     }
 
     #[inline]
@@ -590,98 +769,105 @@ impl Window {
 
     #[inline]
     pub fn show(&self) {
-        unsafe {
-            ShowWindowAsync(self.hwnd, SW_SHOW);
-        }
+        backend::get().show(self.id);
     }
 
     #[inline]
     pub fn hide(&self) {
-        unsafe {
-            ShowWindowAsync(self.hwnd, SW_HIDE);
-        }
+        backend::get().hide(self.id);
     }
 
     #[inline]
     pub fn minimize(&self) {
-        unsafe {
-            ShowWindowAsync(self.hwnd, SW_MINIMIZE);
-        }
+        backend::get().minimize(self.id);
     }
 
     #[inline]
     pub fn maximize(&self) {
-        unsafe {
-            ShowWindowAsync(self.hwnd, SW_SHOWMAXIMIZED);
-        }
+        backend::get().maximize(self.id);
     }
 
     #[inline]
     pub fn restore(&self) {
-        unsafe {
-            ShowWindowAsync(self.hwnd, SW_RESTORE);
-        }
+        backend::get().restore(self.id);
     }
 
     #[inline]
     pub fn accept_drop_files(&self, accept: bool) {
-        let hwnd = self.hwnd;
-        UiThread::send_task(move || unsafe {
-            DragAcceptFiles(hwnd, accept);
-        });
+        backend::get().accept_drop_files(self.id, accept);
     }
 
     #[inline]
     pub fn ime(&self, enable: bool) {
-        let hwnd = self.hwnd;
-        UiThread::send_task(move || {
-            Context::set_window_property(hwnd, |props| {
-                if enable {
-                    props.imm_context.enable();
-                } else {
-                    props.imm_context.disable();
-                }
-            });
-        });
+        backend::get().ime(self.id, enable);
     }
 
     #[inline]
     pub fn set_cursor(&self, cursor: Cursor) {
-        let hwnd = self.hwnd;
-        UiThread::send_task(move || {
-            Context::set_window_property(hwnd, |props| {
-                props.cursor = cursor;
-                props.cursor.set();
-            })
-        });
+        backend::get().set_cursor(self.id, cursor);
     }
 
+    /// Discards events outside `mask` before they are queued.
+    #[inline]
+    pub fn set_event_mask(&self, mask: EventMask) {
+        Context::set_event_mask(self.id, mask);
+    }
+
+    /// Sends `Event::CloseRequested`, and closes the window unless the request is cancelled.
     #[inline]
     pub fn close(&self) {
-        unsafe {
-            if !self.is_closed() {
-                PostMessageW(self.hwnd, WM_CLOSE, WPARAM(0), LPARAM(0));
-            }
+        if !self.is_closed() {
+            backend::get().close(self.id);
+        }
+    }
+
+    /// Closes the window without `Event::CloseRequested`.
+    #[inline]
+    pub fn destroy(&self) {
+        if !self.is_closed() {
+            backend::get().destroy(self.id);
         }
     }
 
     #[inline]
     pub fn is_closed(&self) -> bool {
-        Context::window_is_closed(self.hwnd)
+        Context::window_is_closed(self.id)
     }
 
     #[inline]
     pub fn redraw(&self, invalid_rect: Option<PhysicalRect<i32>>) {
-        let hwnd = self.hwnd;
-        UiThread::send_task(move || unsafe {
-            let rc: Option<RECT> = invalid_rect.map(|rc| rc.into());
-            let p = rc.as_ref().map(|p| p as *const _);
-            RedrawWindow(hwnd, p, None, RDW_INVALIDATE);
-        });
+        backend::get().redraw(self.id, invalid_rect);
+    }
+
+    /// Copies `image` to the client area at `position`.
+    ///
+    /// Only the headless backend keeps the pixels, other backends ignore them.
+    #[inline]
+    pub fn present(&self, position: impl Into<PhysicalPosition<i32>>, image: &Framebuffer) {
+        backend::get().present(self.id, position.into(), image);
+    }
+
+    /// Returns the pixels presented by `present` and the rect of the last `Event::Draw`.
+    #[inline]
+    pub async fn capture(&self) -> Option<Capture> {
+        if self.is_closed() {
+            return None;
+        }
+        backend::get().capture(self.id).await
     }
 
     #[inline]
     pub fn raw_handle(&self) -> *const std::ffi::c_void {
-        self.hwnd.0 as _
+        self.id.0 as _
+    }
+
+    #[inline]
+    pub fn id(&self) -> WindowId {
+        self.id
+    }
+
+    #[inline]
+    pub fn event_sender<T: Any + Send + Sync>(&self) -> EventSender<T> {
+        EventSender::new(self.id)
     }
 }
@@ -0,0 +1,1047 @@
+//! A backend for X11 servers.
+//!
+//! The backend is selected when the `x11` feature is enabled and an X server can be connected
+//! through `DISPLAY`. Events are read by a dedicated thread and translated into the same `Event`
+//! variants as the Win32 backend. `Xft.dpi` in `RESOURCE_MANAGER` is used as the DPI of windows.
+
+use crate::backend::{BoxFuture, WindowParams};
+use crate::keysym::{as_virtual_key, is_keypad, keysym_to_char};
+use crate::window::WindowId;
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use x11rb::connection::Connection;
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{self, ConnectionExt as _};
+use x11rb::protocol::Event as XEvent;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        UTF8_STRING,
+        WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
+        WM_CHANGE_STATE,
+        RESOURCE_MANAGER,
+        _NET_WM_NAME,
+        _NET_WM_STATE,
+        _NET_WM_STATE_HIDDEN,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+    }
+}
+
+fn event_mask() -> xproto::EventMask {
+    xproto::EventMask::EXPOSURE
+        | xproto::EventMask::STRUCTURE_NOTIFY
+        | xproto::EventMask::FOCUS_CHANGE
+        | xproto::EventMask::PROPERTY_CHANGE
+        | xproto::EventMask::ENTER_WINDOW
+        | xproto::EventMask::LEAVE_WINDOW
+        | xproto::EventMask::POINTER_MOTION
+        | xproto::EventMask::BUTTON_PRESS
+        | xproto::EventMask::BUTTON_RELEASE
+        | xproto::EventMask::KEY_PRESS
+        | xproto::EventMask::KEY_RELEASE
+}
+
+// ICCCM WM_STATE
+const ICONIC_STATE: u32 = 3;
+
+// _NET_WM_STATE actions
+const NET_WM_STATE_REMOVE: u32 = 0;
+const NET_WM_STATE_ADD: u32 = 1;
+
+const WHEEL_DELTA: i32 = 120;
+
+impl From<x11rb::errors::ConnectionError> for Error {
+    fn from(src: x11rb::errors::ConnectionError) -> Self {
+        Self::X11(src.into())
+    }
+}
+
+impl From<x11rb::errors::ReplyOrIdError> for Error {
+    fn from(src: x11rb::errors::ReplyOrIdError) -> Self {
+        Self::X11(src)
+    }
+}
+
+fn cursor_name(cursor: Cursor) -> &'static str {
+    match cursor {
+        Cursor::AppStarting => "watch",
+        Cursor::Arrow => "left_ptr",
+        Cursor::Cross => "crosshair",
+        Cursor::Hand => "hand2",
+        Cursor::Help => "question_arrow",
+        Cursor::IBeam => "xterm",
+        Cursor::No => "X_cursor",
+        Cursor::SizeAll => "fleur",
+        Cursor::SizeNESW => "bottom_left_corner",
+        Cursor::SizeNS => "sb_v_double_arrow",
+        Cursor::SizeNWSE => "bottom_right_corner",
+        Cursor::SizeWE => "sb_h_double_arrow",
+        Cursor::SizeUpArrow => "sb_up_arrow",
+        Cursor::Wait => "watch",
+    }
+}
+
+struct Keymap {
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: Vec<u32>,
+}
+
+impl Keymap {
+    fn new(conn: &RustConnection) -> Option<Self> {
+        let setup = conn.setup();
+        let count = setup.max_keycode - setup.min_keycode + 1;
+        let reply = conn
+            .get_keyboard_mapping(setup.min_keycode, count)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(Self {
+            min_keycode: setup.min_keycode,
+            keysyms_per_keycode: reply.keysyms_per_keycode,
+            keysyms: reply.keysyms,
+        })
+    }
+
+    fn get(&self, keycode: u8, index: usize) -> u32 {
+        let per = self.keysyms_per_keycode as usize;
+        if keycode < self.min_keycode || index >= per {
+            return 0;
+        }
+        let i = (keycode - self.min_keycode) as usize * per + index;
+        self.keysyms.get(i).copied().unwrap_or(0)
+    }
+
+    // Returns the keysym which identifies the key and the keysym which is typed in `state`.
+    fn lookup(&self, keycode: u8, state: xproto::KeyButMask) -> (u32, u32) {
+        let base = self.get(keycode, 0);
+        let shifted = self.get(keycode, 1);
+        if is_keypad(shifted) && state.contains(xproto::KeyButMask::MOD2) {
+            return (shifted, shifted);
+        }
+        let shift = state.contains(xproto::KeyButMask::SHIFT);
+        let lock = state.contains(xproto::KeyButMask::LOCK);
+        let mut typed = if shift && shifted != 0 { shifted } else { base };
+        if lock && !shift && (0x0061..=0x007a).contains(&typed) {
+            typed -= 0x20;
+        }
+        (base, typed)
+    }
+}
+
+fn mouse_buttons(state: xproto::KeyButMask) -> MouseButtons {
+    let mut buttons = MouseButtons::new();
+    if state.contains(xproto::KeyButMask::BUTTON1) {
+        buttons.insert(MouseButton::Left);
+    }
+    if state.contains(xproto::KeyButMask::BUTTON2) {
+        buttons.insert(MouseButton::Middle);
+    }
+    if state.contains(xproto::KeyButMask::BUTTON3) {
+        buttons.insert(MouseButton::Right);
+    }
+    buttons
+}
+
+fn as_mouse_button(button: u8) -> Option<MouseButton> {
+    match button {
+        1 => Some(MouseButton::Left),
+        2 => Some(MouseButton::Middle),
+        3 => Some(MouseButton::Right),
+        8.. => Some(MouseButton::Ex((button - 8) as u32)),
+        _ => None,
+    }
+}
+
+/// Returns the server time of input events.
+fn event_time(event: &XEvent) -> Option<u32> {
+    match event {
+        XEvent::EnterNotify(e) | XEvent::LeaveNotify(e) => Some(e.time),
+        XEvent::MotionNotify(e) => Some(e.time),
+        XEvent::ButtonPress(e) | XEvent::ButtonRelease(e) => Some(e.time),
+        XEvent::KeyPress(e) | XEvent::KeyRelease(e) => Some(e.time),
+        _ => None,
+    }
+}
+
+fn read_dpi(conn: &RustConnection) -> u32 {
+    x11rb::resource_manager::new_from_resource_manager(conn)
+        .ok()
+        .flatten()
+        .and_then(|db| {
+            db.get_string("Xft.dpi", "")
+                .and_then(|s| s.parse::<f32>().ok())
+        })
+        .map_or(DEFAULT_DPI, |dpi| dpi.round() as u32)
+}
+
+struct WindowState {
+    position: ScreenPosition,
+    size: PhysicalSize<u32>,
+    minimized: bool,
+    maximized: bool,
+}
+
+struct State {
+    windows: HashMap<xproto::Window, WindowState>,
+    focused: Option<xproto::Window>,
+    pressed_keys: HashSet<u8>,
+    keymap: Option<Keymap>,
+    dpi: u32,
+    cursors: HashMap<Cursor, xproto::Cursor>,
+}
+
+struct Shared {
+    conn: RustConnection,
+    root: xproto::Window,
+    white_pixel: u32,
+    atoms: Atoms,
+    cursor_handle: Option<x11rb::cursor::Handle>,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn run(&self) {
+        while let Ok(event) = self.conn.wait_for_event() {
+            self.handle_event(event);
+        }
+        let windows = self
+            .state
+            .lock()
+            .unwrap()
+            .windows
+            .drain()
+            .map(|(window, _)| window)
+            .collect::<Vec<_>>();
+        for window in windows {
+            Context::close_window(window);
+        }
+    }
+
+    fn handle_event(&self, event: XEvent) {
+        let _time = Context::set_message_time(event_time(&event));
+        match event {
+            XEvent::Expose(e) => {
+                Context::send_event(
+                    e.window,
+                    Event::Draw(events::Draw {
+                        invalid_rect: PhysicalRect::new(
+                            (e.x as i32, e.y as i32),
+                            (e.width as i32, e.height as i32),
+                        ),
+                    }),
+                );
+            }
+            XEvent::ConfigureNotify(e) => self.on_configure(e),
+            XEvent::FocusIn(e) => {
+                if e.mode == xproto::NotifyMode::GRAB || e.detail == xproto::NotifyDetail::POINTER {
+                    return;
+                }
+                let prev = self.state.lock().unwrap().focused.replace(e.event);
+                if prev != Some(e.event) {
+                    Context::send_event(e.event, Event::Activated);
+                }
+            }
+            XEvent::FocusOut(e) => {
+                if e.mode == xproto::NotifyMode::GRAB || e.detail == xproto::NotifyDetail::POINTER {
+                    return;
+                }
+                let mut state = self.state.lock().unwrap();
+                if state.focused == Some(e.event) {
+                    state.focused = None;
+                    state.pressed_keys.clear();
+                    Context::send_event(e.event, Event::Inactivated);
+                }
+            }
+            XEvent::EnterNotify(e) => {
+                Context::send_event(
+                    e.event,
+                    Event::CursorEntered(events::CursorEntered {
+                        mouse_state: MouseState {
+                            position: (e.event_x as i32, e.event_y as i32).into(),
+                            buttons: mouse_buttons(e.state),
+                        },
+                    }),
+                );
+            }
+            XEvent::LeaveNotify(e) => {
+                Context::send_event(
+                    e.event,
+                    Event::CursorLeft(events::CursorLeft {
+                        mouse_state: MouseState {
+                            position: (e.root_x as i32, e.root_y as i32).into(),
+                            buttons: mouse_buttons(e.state),
+                        },
+                    }),
+                );
+            }
+            XEvent::MotionNotify(e) => {
+                Context::send_event(
+                    e.event,
+                    Event::CursorMoved(events::CursorMoved {
+                        mouse_state: MouseState {
+                            position: (e.event_x as i32, e.event_y as i32).into(),
+                            buttons: mouse_buttons(e.state),
+                        },
+                    }),
+                );
+            }
+            XEvent::ButtonPress(e) => self.on_button(e, ButtonState::Pressed),
+            XEvent::ButtonRelease(e) => self.on_button(e, ButtonState::Released),
+            XEvent::KeyPress(e) => self.on_key(e, KeyState::Pressed),
+            XEvent::KeyRelease(e) => self.on_key(e, KeyState::Released),
+            XEvent::ClientMessage(e)
+                if e.format == 32
+                    && e.type_ == self.atoms.WM_PROTOCOLS
+                    && e.data.as_data32()[0] == self.atoms.WM_DELETE_WINDOW =>
+            {
+                Context::request_close(e.window);
+            }
+            XEvent::DestroyNotify(e) => {
+                if e.event != e.window {
+                    return;
+                }
+                {
+                    let mut state = self.state.lock().unwrap();
+                    state.windows.remove(&e.window);
+                    if state.focused == Some(e.window) {
+                        state.focused = None;
+                        Context::send_event(e.window, Event::Inactivated);
+                    }
+                }
+                Context::close_window(e.window);
+            }
+            XEvent::PropertyNotify(e) => {
+                if e.window == self.root && e.atom == self.atoms.RESOURCE_MANAGER {
+                    self.on_resource_manager_changed();
+                } else if e.atom == self.atoms._NET_WM_STATE {
+                    self.on_net_wm_state_changed(e.window);
+                }
+            }
+            XEvent::MappingNotify(_) => {
+                self.state.lock().unwrap().keymap = Keymap::new(&self.conn);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_configure(&self, e: xproto::ConfigureNotifyEvent) {
+        // Real events from a reparenting window manager are relative to the frame.
+        let synthetic = e.response_type & 0x80 != 0;
+        let position = if synthetic {
+            Some(ScreenPosition::new(e.x as i32, e.y as i32))
+        } else {
+            self.conn
+                .translate_coordinates(e.window, self.root, 0, 0)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .map(|reply| ScreenPosition::new(reply.dst_x as i32, reply.dst_y as i32))
+        };
+        let size = PhysicalSize::new(e.width as u32, e.height as u32);
+        let mut state = self.state.lock().unwrap();
+        let Some(w) = state.windows.get_mut(&e.window) else { return };
+        if let Some(position) = position {
+            if w.position != position {
+                w.position = position;
+                Context::send_event(e.window, Event::Moved(events::Moved { position }));
+            }
+        }
+        if !synthetic && w.size != size {
+            w.size = size;
+            Context::send_event(e.window, Event::Resized(events::Resized { size }));
+        }
+    }
+
+    fn on_button(&self, e: xproto::ButtonPressEvent, button_state: ButtonState) {
+        let position = PhysicalPosition::new(e.event_x as i32, e.event_y as i32);
+        let mut buttons = mouse_buttons(e.state);
+        let wheel = match e.detail {
+            4 => Some((MouseWheelAxis::Vertical, WHEEL_DELTA)),
+            5 => Some((MouseWheelAxis::Vertical, -WHEEL_DELTA)),
+            6 => Some((MouseWheelAxis::Horizontal, -WHEEL_DELTA)),
+            7 => Some((MouseWheelAxis::Horizontal, WHEEL_DELTA)),
+            _ => None,
+        };
+        if let Some((axis, distance)) = wheel {
+            if button_state == ButtonState::Pressed {
+                Context::send_event(
+                    e.event,
+                    Event::MouseWheel(events::MouseWheel {
+                        axis,
+                        distance,
+                        mouse_state: MouseState { position, buttons },
+                    }),
+                );
+            }
+            return;
+        }
+        let Some(button) = as_mouse_button(e.detail) else { return };
+        // The state of the event is the state just before the button was pressed or released.
+        match button_state {
+            ButtonState::Pressed => buttons.insert(button),
+            ButtonState::Released => buttons.remove(button),
+        }
+        Context::send_event(
+            e.event,
+            Event::MouseInput(events::MouseInput {
+                button,
+                button_state,
+                mouse_state: MouseState { position, buttons },
+            }),
+        );
+    }
+
+    fn on_key(&self, e: xproto::KeyPressEvent, key_state: KeyState) {
+        let mut state = self.state.lock().unwrap();
+        let Some((base, typed)) = state.keymap.as_ref().map(|k| k.lookup(e.detail, e.state)) else { return };
+        let prev_pressed = match key_state {
+            KeyState::Pressed => !state.pressed_keys.insert(e.detail),
+            KeyState::Released => {
+                state.pressed_keys.remove(&e.detail);
+                true
+            }
+        };
+        drop(state);
+        Context::send_event(
+            e.event,
+            Event::KeyInput(events::KeyInput {
+                key_code: KeyCode::new(
+                    as_virtual_key(base),
+                    ScanCode(e.detail.saturating_sub(8) as u32),
+                ),
+                key_state,
+                prev_pressed,
+            }),
+        );
+        if key_state == KeyState::Released {
+            return;
+        }
+        let c = if e.state.contains(xproto::KeyButMask::CONTROL) {
+            match base {
+                0x0061..=0x007a => char::from_u32(base - 0x0060),
+                _ => None,
+            }
+        } else {
+            keysym_to_char(typed)
+        };
+        if let Some(c) = c {
+            Context::send_event(e.event, Event::CharInput(events::CharInput { c }));
+        }
+    }
+
+    fn on_resource_manager_changed(&self) {
+        let dpi = read_dpi(&self.conn);
+        let mut state = self.state.lock().unwrap();
+        if state.dpi == dpi {
+            return;
+        }
+        state.dpi = dpi;
+        for &window in state.windows.keys() {
+            Context::send_event(
+                window,
+                Event::DpiChanged(events::DpiChanged { new_dpi: dpi }),
+            );
+        }
+    }
+
+    fn on_net_wm_state_changed(&self, window: xproto::Window) {
+        let Ok(cookie) = self.conn.get_property(
+            false,
+            window,
+            self.atoms._NET_WM_STATE,
+            xproto::AtomEnum::ATOM,
+            0,
+            u32::MAX,
+        ) else { return };
+        let Ok(reply) = cookie.reply() else { return };
+        let atoms = reply
+            .value32()
+            .map(|v| v.collect::<Vec<_>>())
+            .unwrap_or_default();
+        let minimized = atoms.contains(&self.atoms._NET_WM_STATE_HIDDEN);
+        let maximized = atoms.contains(&self.atoms._NET_WM_STATE_MAXIMIZED_VERT)
+            && atoms.contains(&self.atoms._NET_WM_STATE_MAXIMIZED_HORZ);
+        let mut state = self.state.lock().unwrap();
+        let Some(w) = state.windows.get_mut(&window) else { return };
+        let size = w.size;
+        if minimized && !w.minimized {
+            Context::send_event(window, Event::Minimized);
+        } else if maximized && (!w.maximized || w.minimized) {
+            Context::send_event(window, Event::Maximized(events::Maximized { size }));
+        } else if !minimized && !maximized && (w.minimized || w.maximized) {
+            Context::send_event(window, Event::Restored(events::Restored { size }));
+        }
+        w.minimized = minimized;
+        w.maximized = maximized;
+    }
+
+    fn send_to_root(&self, window: xproto::Window, type_: xproto::Atom, data: [u32; 5]) {
+        let event = xproto::ClientMessageEvent::new(32, window, type_, data);
+        self.conn
+            .send_event(
+                false,
+                self.root,
+                xproto::EventMask::SUBSTRUCTURE_REDIRECT | xproto::EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )
+            .ok();
+    }
+
+    fn set_maximized(&self, window: xproto::Window, maximized: bool) {
+        let action = if maximized {
+            NET_WM_STATE_ADD
+        } else {
+            NET_WM_STATE_REMOVE
+        };
+        self.send_to_root(
+            window,
+            self.atoms._NET_WM_STATE,
+            [
+                action,
+                self.atoms._NET_WM_STATE_MAXIMIZED_VERT,
+                self.atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+                1,
+                0,
+            ],
+        );
+    }
+
+    fn load_cursor(&self, cursor: Cursor) -> Option<xproto::Cursor> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(&c) = state.cursors.get(&cursor) {
+            return Some(c);
+        }
+        let c = self
+            .cursor_handle
+            .as_ref()?
+            .load_cursor(&self.conn, cursor_name(cursor))
+            .ok()?;
+        state.cursors.insert(cursor, c);
+        Some(c)
+    }
+
+    fn create_window(&self, params: WindowParams) -> Result<(WindowId, EventReceiver)> {
+        let conn = &self.conn;
+        let window = conn.generate_id()?;
+        let dpi = self.state.lock().unwrap().dpi;
+        let size = (params.size)(dpi);
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            self.root,
+            params.position.x as _,
+            params.position.y as _,
+            size.width as _,
+            size.height as _,
+            0,
+            xproto::WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &xproto::CreateWindowAux::new()
+                .background_pixel(self.white_pixel)
+                .event_mask(event_mask()),
+        )?;
+        conn.change_property8(
+            xproto::PropMode::REPLACE,
+            window,
+            xproto::AtomEnum::WM_NAME,
+            xproto::AtomEnum::STRING,
+            params.title.as_bytes(),
+        )?;
+        conn.change_property8(
+            xproto::PropMode::REPLACE,
+            window,
+            self.atoms._NET_WM_NAME,
+            self.atoms.UTF8_STRING,
+            params.title.as_bytes(),
+        )?;
+        conn.change_property8(
+            xproto::PropMode::REPLACE,
+            window,
+            xproto::AtomEnum::WM_CLASS,
+            xproto::AtomEnum::STRING,
+            b"witas\0witas\0",
+        )?;
+        conn.change_property32(
+            xproto::PropMode::REPLACE,
+            window,
+            self.atoms.WM_PROTOCOLS,
+            xproto::AtomEnum::ATOM,
+            &[self.atoms.WM_DELETE_WINDOW],
+        )?;
+        x11rb::properties::WmSizeHints {
+            position: Some((
+                x11rb::properties::WmSizeHintsSpecification::UserSpecified,
+                params.position.x,
+                params.position.y,
+            )),
+            ..Default::default()
+        }
+        .set_normal_hints(conn, window)?;
+        self.state.lock().unwrap().windows.insert(
+            window,
+            WindowState {
+                position: params.position,
+                size,
+                minimized: false,
+                maximized: false,
+            },
+        );
+        let rx = Context::register_window(window, false, params.queue_policy, params.event_mask);
+        if params.visibility {
+            conn.map_window(window)?;
+        }
+        conn.flush()?;
+        Ok((window.into(), rx))
+    }
+}
+
+pub(crate) struct X11 {
+    shared: Arc<Shared>,
+}
+
+impl X11 {
+    /// Connects to the X server of `DISPLAY` and starts the event thread.
+    pub(crate) fn connect() -> Option<Self> {
+        let (conn, screen_num) = x11rb::connect(None).ok()?;
+        let atoms = Atoms::new(&conn).ok()?.reply().ok()?;
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+        let white_pixel = screen.white_pixel;
+        // Key repeats are reported without KeyRelease so that `prev_pressed` can be detected.
+        if conn
+            .xkb_use_extension(1, 0)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|reply| reply.supported)
+        {
+            conn.xkb_per_client_flags(
+                xkb::ID::USE_CORE_KBD.into(),
+                xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT,
+                xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT,
+                xkb::BoolCtrl::default(),
+                xkb::BoolCtrl::default(),
+                xkb::BoolCtrl::default(),
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok());
+        }
+        conn.change_window_attributes(
+            root,
+            &xproto::ChangeWindowAttributesAux::new()
+                .event_mask(xproto::EventMask::PROPERTY_CHANGE),
+        )
+        .ok()?;
+        let cursor_handle = x11rb::resource_manager::new_from_default(&conn)
+            .ok()
+            .and_then(|db| {
+                x11rb::cursor::Handle::new(&conn, screen_num, &db)
+                    .ok()?
+                    .reply()
+                    .ok()
+            });
+        let state = State {
+            windows: HashMap::new(),
+            focused: None,
+            pressed_keys: HashSet::new(),
+            keymap: Keymap::new(&conn),
+            dpi: read_dpi(&conn),
+            cursors: HashMap::new(),
+        };
+        conn.flush().ok()?;
+        let shared = Arc::new(Shared {
+            conn,
+            root,
+            white_pixel,
+            atoms,
+            cursor_handle,
+            state: Mutex::new(state),
+        });
+        let th = shared.clone();
+        std::thread::Builder::new()
+            .name("witas x11 events".into())
+            .spawn(move || th.run())
+            .ok()?;
+        Some(Self { shared })
+    }
+
+    fn request(&self, f: impl FnOnce(&RustConnection) -> Result<()>) {
+        let conn = &self.shared.conn;
+        f(conn).unwrap_or(());
+        conn.flush().unwrap_or(());
+    }
+}
+
+impl backend::Backend for X11 {
+    fn create_window(&self, params: WindowParams) -> BoxFuture<Result<(WindowId, EventReceiver)>> {
+        let ret = self.shared.create_window(params);
+        Box::pin(async move { ret })
+    }
+
+    // The position and the size are kept by the event thread so that the futures do not block
+    // the executor on round trips.
+    fn position(&self, id: WindowId) -> BoxFuture<Option<ScreenPosition>> {
+        let state = self.shared.state.lock().unwrap();
+        let position = state.windows.get(&id.into()).map(|w| w.position);
+        Box::pin(async move { position })
+    }
+
+    fn inner_size(&self, id: WindowId) -> BoxFuture<Option<PhysicalSize<u32>>> {
+        let state = self.shared.state.lock().unwrap();
+        let size = state.windows.get(&id.into()).map(|w| w.size);
+        Box::pin(async move { size })
+    }
+
+    fn dpi(&self, id: WindowId) -> BoxFuture<Option<u32>> {
+        let state = self.shared.state.lock().unwrap();
+        let dpi = state.windows.contains_key(&id.into()).then_some(state.dpi);
+        Box::pin(async move { dpi })
+    }
+
+    fn show(&self, id: WindowId) {
+        self.request(|conn| {
+            conn.map_window(id.into())?;
+            Ok(())
+        });
+    }
+
+    fn hide(&self, id: WindowId) {
+        self.request(|conn| {
+            conn.unmap_window(id.into())?;
+            Ok(())
+        });
+    }
+
+    fn minimize(&self, id: WindowId) {
+        self.shared.send_to_root(
+            id.into(),
+            self.shared.atoms.WM_CHANGE_STATE,
+            [ICONIC_STATE, 0, 0, 0, 0],
+        );
+        self.shared.conn.flush().unwrap_or(());
+    }
+
+    fn maximize(&self, id: WindowId) {
+        self.shared.set_maximized(id.into(), true);
+        self.show(id);
+    }
+
+    fn restore(&self, id: WindowId) {
+        let window = id.into();
+        let minimized = self
+            .shared
+            .state
+            .lock()
+            .unwrap()
+            .windows
+            .get(&window)
+            .is_some_and(|w| w.minimized);
+        if minimized {
+            self.show(id);
+        } else {
+            self.shared.set_maximized(window, false);
+            self.shared.conn.flush().unwrap_or(());
+        }
+    }
+
+    // Drag and drop (XDND) is not supported.
+    fn accept_drop_files(&self, _id: WindowId, _accept: bool) {}
+
+    // Input methods (XIM) are not supported.
+    fn ime(&self, _id: WindowId, _enable: bool) {}
+
+    fn set_cursor(&self, id: WindowId, cursor: Cursor) {
+        let Some(cursor) = self.shared.load_cursor(cursor) else { return };
+        self.request(|conn| {
+            conn.change_window_attributes(
+                id.into(),
+                &xproto::ChangeWindowAttributesAux::new().cursor(cursor),
+            )?;
+            Ok(())
+        });
+    }
+
+    fn destroy(&self, id: WindowId) {
+        self.request(|conn| {
+            conn.destroy_window(id.into())?;
+            Ok(())
+        });
+    }
+
+    fn redraw(&self, id: WindowId, invalid_rect: Option<PhysicalRect<i32>>) {
+        let rc = invalid_rect.unwrap_or_else(|| PhysicalRect::new((0, 0), (0, 0)));
+        self.request(|conn| {
+            conn.clear_area(
+                true,
+                id.into(),
+                rc.position.x as _,
+                rc.position.y as _,
+                rc.size.width as _,
+                rc.size.height as _,
+            )?;
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+
+    fn params(title: &str) -> WindowParams {
+        WindowParams {
+            title: title.into(),
+            position: ScreenPosition::new(0, 0),
+            size: Box::new(|_| PhysicalSize::new(320, 240)),
+            style: Box::new(WindowStyle::default()),
+            visibility: true,
+            enable_ime: false,
+            visible_ime_candidate_window: false,
+            accept_drop_files: false,
+            enable_raw_input: false,
+            icon: None,
+            queue_policy: QueuePolicy::default(),
+            event_mask: EventMask::ALL,
+            reply_timeout: DEFAULT_REPLY_TIMEOUT,
+            forward_messages: false,
+        }
+    }
+
+    // Run with `DISPLAY=:99 cargo test --features x11 -- --ignored` while `Xvfb :99` is running.
+    #[test]
+    #[ignore = "requires an X server such as Xvfb"]
+    fn create_and_close_window() {
+        let backend = X11::connect().expect("cannot connect to the X server");
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (id, mut rx) = backend
+                .create_window(params("witas x11 test"))
+                .await
+                .unwrap();
+            assert_eq!(
+                backend.inner_size(id).await.unwrap(),
+                PhysicalSize::new(320, 240)
+            );
+            loop {
+                if let Event::Draw(_) = rx.recv().await {
+                    break;
+                }
+            }
+            backend.destroy(id);
+            loop {
+                if let Event::Closed = rx.recv().await {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// A client which plays the role of the user and the window manager.
+    struct Tester {
+        conn: RustConnection,
+        root: xproto::Window,
+    }
+
+    impl Tester {
+        fn connect() -> Self {
+            let (conn, screen_num) = x11rb::connect(None).expect("cannot connect to the X server");
+            let root = conn.setup().roots[screen_num].root;
+            Self { conn, root }
+        }
+
+        fn keycode(&self, keysym: u32) -> u8 {
+            let setup = self.conn.setup();
+            let count = setup.max_keycode - setup.min_keycode + 1;
+            let reply = self
+                .conn
+                .get_keyboard_mapping(setup.min_keycode, count)
+                .unwrap()
+                .reply()
+                .unwrap();
+            let per = reply.keysyms_per_keycode as usize;
+            let i = reply
+                .keysyms
+                .chunks(per)
+                .position(|syms| syms[0] == keysym)
+                .expect("no keycode for the keysym");
+            setup.min_keycode + i as u8
+        }
+
+        fn fake_input(&self, type_: u8, detail: u8, x: i16, y: i16) {
+            use x11rb::protocol::xtest::ConnectionExt as _;
+            self.conn
+                .xtest_fake_input(type_, detail, x11rb::CURRENT_TIME, self.root, x, y, 0)
+                .unwrap();
+            self.sync();
+        }
+
+        fn sync(&self) {
+            self.conn.get_input_focus().unwrap().reply().unwrap();
+        }
+    }
+
+    async fn wait_for(rx: &mut EventReceiver, f: impl Fn(&Event) -> bool) -> Event {
+        let wait = async {
+            loop {
+                let event = rx.recv().await;
+                if f(&event) {
+                    return event;
+                }
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), wait)
+            .await
+            .expect("the event did not come")
+    }
+
+    // Runs on a server without a window manager so that windows are placed as requested.
+    #[test]
+    #[ignore = "requires an X server with XTEST such as Xvfb"]
+    fn translate_input() {
+        let backend = X11::connect().expect("cannot connect to the X server");
+        let tester = Tester::connect();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (id, mut rx) = backend
+                .create_window(params("witas x11 input"))
+                .await
+                .unwrap();
+            wait_for(&mut rx, |e| matches!(e, Event::Draw(_))).await;
+            tester
+                .conn
+                .set_input_focus(
+                    xproto::InputFocus::PARENT,
+                    u32::from(id),
+                    x11rb::CURRENT_TIME,
+                )
+                .unwrap();
+            tester.sync();
+            wait_for(&mut rx, |e| matches!(e, Event::Activated)).await;
+
+            let a = tester.keycode(0x0061);
+            tester.fake_input(xproto::KEY_PRESS_EVENT, a, 0, 0);
+            tester.fake_input(xproto::KEY_RELEASE_EVENT, a, 0, 0);
+            let event = wait_for(&mut rx, |e| matches!(e, Event::KeyInput(_))).await;
+            assert!(matches!(event, Event::KeyInput(e)
+                if e.key_code.vkey == VirtualKey::A
+                    && e.key_state == KeyState::Pressed
+                    && !e.prev_pressed));
+            let event = wait_for(&mut rx, |e| matches!(e, Event::CharInput(_))).await;
+            assert!(matches!(event, Event::CharInput(e) if e.c == 'a'));
+            let event = wait_for(&mut rx, |e| matches!(e, Event::KeyInput(_))).await;
+            assert!(matches!(event, Event::KeyInput(e)
+                if e.key_code.vkey == VirtualKey::A && e.key_state == KeyState::Released));
+
+            tester.fake_input(xproto::MOTION_NOTIFY_EVENT, 0, 50, 60);
+            tester.fake_input(xproto::BUTTON_PRESS_EVENT, 1, 0, 0);
+            tester.fake_input(xproto::BUTTON_RELEASE_EVENT, 1, 0, 0);
+            let event = wait_for(&mut rx, |e| matches!(e, Event::MouseInput(_))).await;
+            assert!(matches!(event, Event::MouseInput(e)
+                if e.button == MouseButton::Left
+                    && e.button_state == ButtonState::Pressed
+                    && e.mouse_state.position == PhysicalPosition::new(50, 60)
+                    && e.mouse_state.buttons.contains(MouseButton::Left)));
+            let event = wait_for(&mut rx, |e| matches!(e, Event::MouseInput(_))).await;
+            assert!(matches!(event, Event::MouseInput(e)
+                if e.button_state == ButtonState::Released
+                    && !e.mouse_state.buttons.contains(MouseButton::Left)));
+
+            tester.fake_input(xproto::BUTTON_PRESS_EVENT, 4, 0, 0);
+            tester.fake_input(xproto::BUTTON_RELEASE_EVENT, 4, 0, 0);
+            let event = wait_for(&mut rx, |e| matches!(e, Event::MouseWheel(_))).await;
+            assert!(matches!(event, Event::MouseWheel(e)
+                if e.axis == MouseWheelAxis::Vertical
+                    && e.distance == WHEEL_DELTA
+                    && e.mouse_state.position == PhysicalPosition::new(50, 60)));
+
+            backend.destroy(id);
+            wait_for(&mut rx, |e| matches!(e, Event::Closed)).await;
+        });
+    }
+
+    #[test]
+    #[ignore = "requires an X server such as Xvfb"]
+    fn configure_and_close_request() {
+        let backend = X11::connect().expect("cannot connect to the X server");
+        let tester = Tester::connect();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (id, mut rx) = backend
+                .create_window(params("witas x11 configure"))
+                .await
+                .unwrap();
+            wait_for(&mut rx, |e| matches!(e, Event::Draw(_))).await;
+            tester
+                .conn
+                .configure_window(
+                    id.into(),
+                    &xproto::ConfigureWindowAux::new()
+                        .x(100)
+                        .y(50)
+                        .width(400)
+                        .height(300),
+                )
+                .unwrap();
+            tester.sync();
+            let event = wait_for(&mut rx, |e| matches!(e, Event::Moved(_))).await;
+            assert!(matches!(event, Event::Moved(e)
+                if e.position == ScreenPosition::new(100, 50)));
+            let event = wait_for(&mut rx, |e| matches!(e, Event::Resized(_))).await;
+            assert!(matches!(event, Event::Resized(e)
+                if e.size == PhysicalSize::new(400, 300)));
+            assert_eq!(
+                backend.position(id).await,
+                Some(ScreenPosition::new(100, 50))
+            );
+            assert_eq!(
+                backend.inner_size(id).await,
+                Some(PhysicalSize::new(400, 300))
+            );
+
+            // Sent as a window manager does when the close button is clicked.
+            let wm_protocols = tester
+                .conn
+                .intern_atom(false, b"WM_PROTOCOLS")
+                .unwrap()
+                .reply()
+                .unwrap()
+                .atom;
+            let wm_delete_window = tester
+                .conn
+                .intern_atom(false, b"WM_DELETE_WINDOW")
+                .unwrap()
+                .reply()
+                .unwrap()
+                .atom;
+            let message = xproto::ClientMessageEvent::new(
+                32,
+                u32::from(id),
+                wm_protocols,
+                [wm_delete_window, x11rb::CURRENT_TIME, 0, 0, 0],
+            );
+            tester
+                .conn
+                .send_event(false, u32::from(id), xproto::EventMask::NO_EVENT, message)
+                .unwrap();
+            tester.sync();
+            let event = wait_for(&mut rx, |e| matches!(e, Event::CloseRequested(_))).await;
+            // The window is destroyed when the request is dropped without being cancelled.
+            drop(event);
+            wait_for(&mut rx, |e| matches!(e, Event::Closed)).await;
+        });
+    }
+}